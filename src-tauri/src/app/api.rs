@@ -2,12 +2,15 @@ use std::collections::{BTreeMap, HashMap};
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde::de::DeserializeOwned;
-use tracing::debug;
+use tracing::{debug, info, warn};
 
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::LAUNCHER_DIRECTORY;
 use crate::app::app_data::LauncherOptions;
+use crate::app::norisk_api::NoriskApi;
+use crate::minecraft::servers::ServerEntry;
 use crate::minecraft::version::AssetObject;
 use crate::utils::get_maven_artifact_path;
 
@@ -19,18 +22,37 @@ pub const CONTENT_FOLDER: &str = "NoRiskClient";
 /// Placeholder struct for API endpoints implementation
 pub struct ApiEndpoints;
 
-pub fn get_launcher_api_base(is_experimental: bool) -> String {
-    return if is_experimental {
-        String::from("https://api-staging.norisk.gg")
-    } else {
-        String::from("https://api.norisk.gg")
-    };
+/// Backend the launcher talks to, independent of the dev mode of the client
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiEnvironment {
+    #[default]
+    #[serde(rename = "production")]
+    Production,
+    #[serde(rename = "staging")]
+    Staging,
+}
+
+impl ApiEnvironment {
+    pub fn api_base_url(&self) -> &'static str {
+        match self {
+            ApiEnvironment::Production => "https://api.norisk.gg",
+            ApiEnvironment::Staging => "https://api-staging.norisk.gg",
+        }
+    }
+
+    /// Base url of the cdn serving the capes
+    pub fn dl_base_url(&self) -> &'static str {
+        match self {
+            ApiEnvironment::Production => "https://dl.norisk.gg",
+            ApiEnvironment::Staging => "https://dl-staging.norisk.gg",
+        }
+    }
 }
 
 impl ApiEndpoints {
     /// Request all available branches
-    pub async fn norisk_branches(is_experimental: bool) -> Result<Vec<String>> {
-        Self::request_from_norisk_endpoint_with_experimental("branches", is_experimental).await
+    pub async fn norisk_branches(environment: ApiEnvironment) -> Result<Vec<String>> {
+        Self::request_from_norisk_endpoint_with_environment("branches", environment).await
     }
 
     /// Request featured mods
@@ -53,7 +75,7 @@ impl ApiEndpoints {
     }
 
     /// Request all available branches
-    pub async fn await_auth_response(id: u32) -> Result<LoginData> {
+    pub async fn await_auth_response(id: u32) -> Result<AuthAwaitResponse> {
         Self::post_from_await_endpoint("auth/await", id).await
     }
 
@@ -74,66 +96,26 @@ impl ApiEndpoints {
 
     /// Request JSON formatted data from launcher API
     pub async fn request_from_norisk_endpoint<T: DeserializeOwned>(endpoint: &str) -> Result<T> {
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/{}/{}", get_launcher_api_base(options.experimental_mode), NORISK_LAUNCHER_API_VERSION, endpoint);
-        println!("URL: {}", url); // Den formatierten String ausgeben
-        Ok(HTTP_CLIENT.get(url)
-            .send().await?
-            .error_for_status()?
-            .json::<T>()
-            .await?
-        )
+        NoriskApi::load().await.get_json(&format!("{}/{}", NORISK_LAUNCHER_API_VERSION, endpoint)).await
     }
 
     //habe das angelegt weil in javascript wurde es schon geändert aber hier ist noch anderer wert?
-    pub async fn request_from_norisk_endpoint_with_experimental<T: DeserializeOwned>(endpoint: &str, is_experimental: bool) -> Result<T> {
-        let url = format!("{}/{}/{}", get_launcher_api_base(is_experimental), NORISK_LAUNCHER_API_VERSION, endpoint);
-        println!("URL: {}", url); // Den formatierten String ausgeben
-        Ok(HTTP_CLIENT.get(url)
-            .send().await?
-            .error_for_status()?
-            .json::<T>()
-            .await?
-        )
+    pub async fn request_from_norisk_endpoint_with_environment<T: DeserializeOwned>(endpoint: &str, environment: ApiEnvironment) -> Result<T> {
+        NoriskApi::new(environment).get_json(&format!("{}/{}", NORISK_LAUNCHER_API_VERSION, endpoint)).await
     }
 
     /// Request JSON formatted data from launcher API
     pub async fn post_from_norisk_endpoint<T: DeserializeOwned>(endpoint: &str) -> Result<T> {
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/{}/{}", get_launcher_api_base(options.experimental_mode), "api/v1", endpoint);
-        println!("URL: {}", url); // Den formatierten String ausgeben
-        Ok(HTTP_CLIENT.post(url)
-            .send().await?
-            .error_for_status()?
-            .json::<T>()
-            .await?
-        )
+        NoriskApi::load().await.post_json(&format!("api/v1/{}", endpoint), None).await
     }
 
     pub async fn post_from_refresh_endpoint<T: DeserializeOwned>(endpoint: &str, request_body: &str) -> Result<T> {
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/{}/{}", get_launcher_api_base(options.experimental_mode), "api/v1", endpoint);
-        println!("URL: {}", url); // Den formatierten String ausgeben
-        Ok(HTTP_CLIENT.post(url)
-            .body(request_body.to_string())
-            .send().await?
-            .error_for_status()?
-            .json::<T>()
-            .await?
-        )
+        NoriskApi::load().await.post_json(&format!("api/v1/{}", endpoint), Some(request_body.as_bytes().to_vec())).await
     }
 
     /// Request JSON formatted data from launcher API
     pub async fn post_from_await_endpoint<T: DeserializeOwned>(endpoint: &str, id: u32) -> Result<T> {
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/{}/{}?{}={}", get_launcher_api_base(options.experimental_mode), "api/v1", endpoint, "id", id);
-        println!("URL: {}", url); // Den formatierten String ausgeben
-        Ok(HTTP_CLIENT.post(url)
-            .send().await?
-            .error_for_status()?
-            .json::<T>()
-            .await?
-        )
+        NoriskApi::load().await.post_json(&format!("api/v1/{}?id={}", endpoint, id), None).await
     }
 }
 
@@ -156,6 +138,15 @@ pub struct AuthPrepareResponse {
     pub url: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AuthAwaitResponse {
+    #[serde(flatten)]
+    pub login_data: LoginData,
+    /// Lifetime of the minecraft token in seconds
+    #[serde(alias = "expiresIn", default)]
+    pub expires_in: Option<i64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AuthTokenResponse {
     pub access_token: String,
@@ -169,6 +160,9 @@ pub struct RefreshResponse {
     pub mc_token: String,
     pub mc_name: String,
     pub norisk_token: String,
+    /// Lifetime of the minecraft token in seconds
+    #[serde(default)]
+    pub expires_in: Option<i64>,
 }
 
 
@@ -186,6 +180,12 @@ pub struct LoginData {
     pub norisk_token: String,
     #[serde(rename = "experimentalToken")]
     pub experimental_token: Option<String>,
+    /// Unix time in seconds at which the minecraft token expires, unknown for accounts of older launchers
+    #[serde(rename = "mcTokenExpiresAt", default)]
+    pub mc_token_expires_at: Option<i64>,
+    /// Set if refreshing the tokens failed, the account has to be logged in again
+    #[serde(rename = "needsRelogin", default)]
+    pub needs_relogin: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -196,7 +196,125 @@ pub struct MinecraftToken {
     //pub duration: u32,
 }
 
+/// Minecraft tokens are valid for a day, used if the response doesn't tell the lifetime
+const MC_TOKEN_LIFETIME: i64 = 24 * 60 * 60;
+
+/// Tokens are refreshed a bit before they expire, so they don't expire while the game is starting
+const MC_TOKEN_REFRESH_MARGIN: i64 = 5 * 60;
+
+///
+/// Refreshes the tokens of the account if its minecraft token is expired or its expiry is unknown and stores the refreshed account.
+/// If the refresh fails, the account is marked as requiring a re-login.
+///
+pub async fn refresh_if_expired(account: &mut LoginData) -> Result<()> {
+    if !account.is_expired(Utc::now().timestamp()) {
+        return Ok(());
+    }
+
+    info!("Token of account {} is expired, refreshing", account.uuid);
+    let result = account.clone().refresh_maybe_fixed().await;
+    let refresh_error = match result {
+        Ok(refreshed) => {
+            *account = refreshed;
+            None
+        }
+        Err(err) => {
+            warn!("Unable to refresh account {}, a re-login is required: {:?}", account.uuid, err);
+            account.needs_relogin = true;
+            Some(err)
+        }
+    };
+
+    LauncherOptions::update(LAUNCHER_DIRECTORY.config_dir(), |options| {
+        options.replace_account(account);
+        Ok(())
+    }).await?;
+
+    match refresh_error {
+        Some(err) => Err(err.context(format!("account {} has to be logged in again", account.username))),
+        None => Ok(())
+    }
+}
+
+/// Accounts which are refreshed at the same time by [refresh_all_accounts]
+const ACCOUNT_REFRESH_CONCURRENCY: usize = 4;
+
+/// Outcome of refreshing an account in [refresh_all_accounts]
+#[derive(Serialize, Debug)]
+pub struct AccountRefreshResult {
+    pub uuid: String,
+    pub username: String,
+    /// Set if the refresh failed and the account has to be logged in again
+    #[serde(rename = "needsRelogin")]
+    pub needs_relogin: bool,
+    pub error: Option<String>,
+}
+
+///
+/// Refreshes all accounts whose minecraft token is expired at the same time and updates them in the options.
+/// A failing account is marked as requiring a re-login without affecting the others. Accounts which already require a re-login are skipped.
+/// The options have to be stored afterwards to update the secret store.
+///
+pub async fn refresh_all_accounts(options: &mut LauncherOptions) -> Vec<AccountRefreshResult> {
+    let now = Utc::now().timestamp();
+    let expired_accounts = options.accounts.iter()
+        .filter(|account| !account.needs_relogin && account.is_expired(now))
+        .cloned()
+        .collect::<Vec<_>>();
+    info!("Refreshing {} expired accounts", expired_accounts.len());
+
+    let refreshed = stream::iter(expired_accounts.into_iter().map(|account| async move {
+        let result = account.clone().refresh_maybe_fixed().await;
+        (account, result)
+    })).buffer_unordered(ACCOUNT_REFRESH_CONCURRENCY).collect::<Vec<_>>().await;
+
+    let mut results = Vec::new();
+    for (mut account, result) in refreshed {
+        let error = match result {
+            Ok(refreshed) => {
+                account = refreshed;
+                None
+            }
+            Err(err) => {
+                warn!("Unable to refresh account {}, a re-login is required: {:?}", account.uuid, err);
+                account.needs_relogin = true;
+                Some(format!("{}", err))
+            }
+        };
+
+        results.push(AccountRefreshResult {
+            uuid: account.uuid.clone(),
+            username: account.username.clone(),
+            needs_relogin: account.needs_relogin,
+            error,
+        });
+        if let Some(stored) = options.accounts.iter_mut().find(|stored| stored.uuid == account.uuid) {
+            *stored = account;
+        }
+    }
+
+    results
+}
+
 impl LoginData {
+    /// Expiry of a minecraft token issued now, the lifetime defaults to a day
+    pub fn mc_token_expiry(expires_in: Option<i64>) -> i64 {
+        Utc::now().timestamp() + expires_in.unwrap_or(MC_TOKEN_LIFETIME)
+    }
+
+    /// Whether the minecraft token has to be refreshed at the given unix time, an unknown expiry counts as expired
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.mc_token_expires_at.map_or(true, |expires_at| expires_at - MC_TOKEN_REFRESH_MARGIN <= now)
+    }
+
+    /// Norisk token of the account for the api environment, staging issues its own tokens
+    pub fn norisk_token_for(&self, environment: ApiEnvironment) -> &str {
+        match environment {
+            ApiEnvironment::Production => &self.norisk_token,
+            ApiEnvironment::Staging => self.experimental_token.as_deref().unwrap_or_default(),
+        }
+    }
+
     /// Refresh access token if necessary
 
     pub async fn refresh_maybe_fixed(self) -> Result<LoginData> {
@@ -210,9 +328,11 @@ impl LoginData {
                     access_token: response.access_token,
                     refresh_token: response.refresh_token,
                     username: response.mc_name,
-                    norisk_token: if options.experimental_mode { self.norisk_token } else { response.norisk_token.clone() },
-                    experimental_token: if options.experimental_mode { Some(response.norisk_token) } else { self.experimental_token },
+                    norisk_token: if options.api_environment == ApiEnvironment::Staging { self.norisk_token } else { response.norisk_token.clone() },
+                    experimental_token: if options.api_environment == ApiEnvironment::Staging { Some(response.norisk_token) } else { self.experimental_token },
                     mc_token: response.mc_token,
+                    mc_token_expires_at: Some(Self::mc_token_expiry(response.expires_in)),
+                    needs_relogin: false,
                 })
             }
             Err(err) => {
@@ -234,6 +354,9 @@ pub struct NoRiskBuild {
     pub jre_version: u32,
     #[serde(rename(serialize = "fabricLoaderVersion"))]
     pub fabric_loader_version: String,
+    /// Server which is added to the server list of the game
+    #[serde(rename(serialize = "defaultServer"), default)]
+    pub default_server: Option<ServerEntry>,
 }
 
 ///
@@ -363,10 +486,30 @@ impl ModSource {
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "name")]
 pub enum LoaderSubsystem {
+    /// An empty manifest generates the profile from Fabric's meta
     #[serde(rename = "fabric")]
     Fabric { manifest: String, mod_directory: String },
+    #[serde(rename = "quilt")]
+    Quilt { loader_version: String, mod_directory: String },
     #[serde(rename = "forge")]
-    Forge { manifest: String, mod_directory: String },
+    Forge {
+        manifest: String,
+        mod_directory: String,
+        /// Installs this Forge version through its installer instead of loading the manifest
+        #[serde(default)]
+        forge_version: Option<String>,
+    },
+}
+
+impl LoaderSubsystem {
+    /// Name of the modloader as used in the manifest
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoaderSubsystem::Fabric { .. } => "fabric",
+            LoaderSubsystem::Quilt { .. } => "quilt",
+            LoaderSubsystem::Forge { .. } => "forge",
+        }
+    }
 }
 
 ///
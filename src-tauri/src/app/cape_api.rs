@@ -6,13 +6,13 @@ use std::io::Read;
 use std::path::PathBuf;
 use std::process::Command;
 
-use reqwest::StatusCode;
+use reqwest::{Method, StatusCode};
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
-use crate::app::api::get_launcher_api_base;
 use crate::app::app_data::LauncherOptions;
+use crate::app::norisk_api::NoriskApi;
 
 /// Placeholder struct for API endpoints implementation
 pub struct CapeApiEndpoints;
@@ -21,24 +21,15 @@ impl CapeApiEndpoints {
     pub async fn equip_cape(token: &str, hash: &str) -> Result<String, String> {
         let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
 
-        let image_url = if options.experimental_mode {
-            format!("https://dl-staging.norisk.gg/capes/prod/{}.png", hash)
-        } else {
-            format!("https://dl.norisk.gg/capes/prod/{}.png", hash)
-        };
+        let image_url = format!("{}/capes/prod/{}.png", options.api_environment.dl_base_url(), hash);
 
         return match reqwest::get(image_url).await {
             Ok(response) => {
                 let image_bytes = response.bytes().await;
 
-                // Baue die URL mit dem Token als Query-Parameter
-                let url = format!("{}/cosmetics/cape/{}", get_launcher_api_base(options.experimental_mode), token);
-
                 // Sende den POST-Request
-                let response = HTTP_CLIENT
-                    .post(&url)
-                    .body(image_bytes.unwrap())
-                    .send()
+                let response = NoriskApi::new(options.api_environment)
+                    .send_authorized(Method::POST, token, |token| format!("cosmetics/cape/{}", token), Some(image_bytes.unwrap().to_vec()))
                     .await
                     .map_err(|err| format!("Fehler beim Senden des Requests: {}", err))?;
 
@@ -70,21 +61,15 @@ impl CapeApiEndpoints {
 
     pub async fn upload_cape(token: &str, image_path: PathBuf) -> Result<String, String> {
         debug!("Image Path {:?}",image_path);
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
         // Lese den Inhalt der Bilddatei in Bytes ein
         match File::open(image_path) {
             Ok(mut file) => {
                 let mut image_data = Vec::new();
                 file.read_to_end(&mut image_data).expect("Error Reading File");
 
-                // Baue die URL mit dem Token als Query-Parameter
-                let url = format!("{}/cosmetics/cape/{}", get_launcher_api_base(options.experimental_mode), token);
-
                 // Sende den POST-Request
-                let response = HTTP_CLIENT
-                    .post(&url)
-                    .body(image_data)
-                    .send()
+                let response = NoriskApi::load().await
+                    .send_authorized(Method::POST, token, |token| format!("cosmetics/cape/{}", token), Some(image_data))
                     .await
                     .map_err(|err| format!("Fehler beim Senden des Requests: {}", err))?;
 
@@ -124,22 +109,14 @@ impl CapeApiEndpoints {
 
     pub async fn cape_hash_by_uuid(uuid: &str) -> Result<String, Box<dyn Error>> {
         debug!("Requesting Cape Hash {}",uuid);
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/cosmetics/user/{}/cape", get_launcher_api_base(options.experimental_mode), uuid);
-        let response = HTTP_CLIENT.get(url).send().await?;
+        let response = NoriskApi::load().await.send(Method::GET, &format!("cosmetics/user/{}/cape", uuid), None).await?;
         let response_text = response.text().await?;
         Ok(response_text)
     }
 
     pub async fn delete_cape(norisk_token: &str) -> Result<String, String> {
-        // Baue die URL mit dem Token als Query-Parameter
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/cosmetics/cape/{}", get_launcher_api_base(options.experimental_mode), norisk_token);
-
-        // Sende den POST-Request
-        let response = HTTP_CLIENT
-            .delete(&url)
-            .send()
+        let response = NoriskApi::load().await
+            .send_authorized(Method::DELETE, norisk_token, |token| format!("cosmetics/cape/{}", token), None)
             .await
             .map_err(|err| format!("Fehler beim Senden des Requests: {}", err))?;
 
@@ -163,9 +140,9 @@ impl CapeApiEndpoints {
 
     pub async fn request_trending_capes(norisk_token: &str, alltime: u32, limit: u32) -> Result<Vec<Cape>, Box<dyn Error>> {
         debug!("Requesting Trending Capes...");
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/cosmetics/cape/{}/trending?alltime={}&limit={}", get_launcher_api_base(options.experimental_mode), norisk_token, alltime, limit);
-        let response = HTTP_CLIENT.get(url).send().await?;
+        let response = NoriskApi::load().await
+            .send_authorized(Method::GET, norisk_token, |token| format!("cosmetics/cape/{}/trending?alltime={}&limit={}", token, alltime, limit), None)
+            .await?;
         let response_text = response.text().await?;
         let trending_capes: Vec<Cape> = serde_json::from_str(&response_text)?;
         Ok(trending_capes)
@@ -173,9 +150,9 @@ impl CapeApiEndpoints {
 
     pub async fn request_owned_capes(norisk_token: &str, limit: u32) -> Result<Vec<Cape>, Box<dyn Error>> {
         debug!("Requesting Owned Capes...");
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/cosmetics/cape/{}/owned?limit={}", get_launcher_api_base(options.experimental_mode), norisk_token, limit);
-        let response = HTTP_CLIENT.get(url).send().await?;
+        let response = NoriskApi::load().await
+            .send_authorized(Method::GET, norisk_token, |token| format!("cosmetics/cape/{}/owned?limit={}", token, limit), None)
+            .await?;
         let response_text = response.text().await?;
         let owned_capes: Vec<Cape> = serde_json::from_str(&response_text)?;
         Ok(owned_capes)
@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use path_absolutize::Absolutize;
+use tokio::fs;
+use tracing::*;
+use walkdir::WalkDir;
+
+use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
+use crate::utils::sha1sum;
+
+/// Folders of the data path which belong to the launcher
+pub const DATA_DIRS: [&str; 7] = ["assets", "gameDir", "libraries", "mod_cache", "natives", "runtimes", "versions"];
+
+/// Amount of files whose checksum is compared after copying
+const VERIFICATION_SAMPLE_SIZE: usize = 32;
+
+///
+/// Copied data path which isn't in use yet. The caller switches the data path and then [DataMigration::finish]es the migration,
+/// which removes the old folders, or [DataMigration::rollback]s it if the data path can't be switched.
+///
+pub struct DataMigration {
+    old: PathBuf,
+    new: PathBuf,
+    /// Files, links and folders created in the new data path, in the order they were created
+    created: Vec<PathBuf>,
+}
+
+///
+/// Copies the launcher folders of the old data path to the new one and verifies the copy. Every file is checked to exist
+/// with the same size, a sample of the files is compared by checksum. Nothing of the old data path is touched, so a failure
+/// leaves the install in the old data path as it was, everything copied so far is removed again.
+/// The new data path has to be empty unless force is set.
+///
+pub async fn migrate_data_path(old: &Path, new: &Path, force: bool, progress: &impl ProgressReceiver) -> Result<DataMigration> {
+    // finishing would remove the folders which were just copied, a nested data path would be copied into itself
+    let (canonical_old, canonical_new) = (canonicalize_existing(old)?, canonicalize_existing(new)?);
+    if canonical_old == canonical_new {
+        bail!("new data path {:?} is the current data path", new);
+    }
+    if canonical_new.starts_with(&canonical_old) || canonical_old.starts_with(&canonical_new) {
+        bail!("new data path {:?} and the current data path {:?} are inside of each other", new, old);
+    }
+
+    if is_non_empty_dir(new)? && !force {
+        bail!("new data path {:?} is not empty", new);
+    }
+
+    let mut migration = DataMigration { old: old.to_path_buf(), new: new.to_path_buf(), created: Vec::new() };
+    match migration.copy(progress).await {
+        Ok(()) => {
+            info!("Copied data path from {:?} to {:?}", old, new);
+            Ok(migration)
+        }
+        Err(e) => {
+            warn!("Unable to copy data path to {:?}, rolling back: {:?}", new, e);
+            if let Err(rollback_error) = migration.rollback().await {
+                error!("Unable to roll back the copy of the data path: {:?}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+impl DataMigration {
+    async fn copy(&mut self, progress: &impl ProgressReceiver) -> Result<()> {
+        if !self.new.exists() {
+            fs::create_dir_all(&self.new).await?;
+            self.created.push(self.new.clone());
+        }
+
+        for dir in DATA_DIRS {
+            let source = self.old.join(dir);
+            if !source.exists() {
+                continue;
+            }
+
+            progress.progress_update(ProgressUpdate::set_label(format!("Copying {}...", dir)));
+            info!("Copying {:?} to {:?}", source, self.new.join(dir));
+            // a linked folder would be followed when walking it
+            if fs::symlink_metadata(&source).await?.is_symlink() {
+                self.copy_symlink(&source, &self.new.join(dir)).await?;
+            } else {
+                self.copy_dir(&source, progress).await?;
+            }
+        }
+
+        progress.progress_update(ProgressUpdate::set_label("Verifying copied files..."));
+        self.verify()
+    }
+
+    async fn copy_dir(&mut self, source: &Path, progress: &impl ProgressReceiver) -> Result<()> {
+        for entry in WalkDir::new(source).into_iter() {
+            let entry = entry?;
+            let destination = self.new.join(entry.path().strip_prefix(&self.old)?);
+
+            if entry.path_is_symlink() {
+                self.copy_symlink(entry.path(), &destination).await?;
+            } else if entry.file_type().is_dir() {
+                self.create_dir(&destination).await?;
+            } else if entry.file_type().is_file() {
+                progress.progress_update(ProgressUpdate::set_label(format!("Copying {}", entry.path().display())));
+                let existed = destination.exists();
+                fs::copy(entry.path(), &destination).await?;
+                if !existed {
+                    self.created.push(destination);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recreates the link in the new data path, links into the old data path are pointed to the new one
+    async fn copy_symlink(&mut self, source: &Path, destination: &Path) -> Result<()> {
+        let link_target = fs::read_link(source).await?;
+        let link_target = match link_target.strip_prefix(&self.old) {
+            Ok(relative) => self.new.join(relative),
+            Err(_) => link_target
+        };
+
+        if fs::symlink_metadata(destination).await.is_ok() {
+            fs::remove_file(destination).await?;
+        }
+        create_symlink(&link_target, destination, source.is_dir()).await?;
+        self.created.push(destination.to_path_buf());
+        Ok(())
+    }
+
+    async fn create_dir(&mut self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            fs::create_dir(path).await?;
+            self.created.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Checks that every file of the old data path exists in the new one with the same size and compares a sample by checksum
+    fn verify(&self) -> Result<()> {
+        let files = data_files(&self.old);
+        for file in &files {
+            let relative_path = file.strip_prefix(&self.old)?;
+            let copied = self.new.join(relative_path);
+            if !copied.exists() || std::fs::metadata(file)?.len() != std::fs::metadata(&copied)?.len() {
+                bail!("{:?} was not copied correctly", relative_path);
+            }
+        }
+
+        let step = (files.len() / VERIFICATION_SAMPLE_SIZE).max(1);
+        for file in files.iter().step_by(step).take(VERIFICATION_SAMPLE_SIZE) {
+            let relative_path = file.strip_prefix(&self.old)?;
+            if sha1sum(file)? != sha1sum(&self.new.join(relative_path))? {
+                bail!("{:?} was not copied correctly", relative_path);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the launcher folders of the old data path, once the new data path is in use
+    pub async fn finish(self, progress: &impl ProgressReceiver) -> Result<()> {
+        for dir in DATA_DIRS {
+            let source = self.old.join(dir);
+            if fs::symlink_metadata(&source).await.is_err() {
+                continue;
+            }
+
+            progress.progress_update(ProgressUpdate::set_label(format!("Removing old {}...", dir)));
+            if fs::symlink_metadata(&source).await?.is_symlink() {
+                fs::remove_file(&source).await?;
+            } else {
+                fs::remove_dir_all(&source).await?;
+            }
+        }
+
+        info!("Moved data path from {:?} to {:?}", self.old, self.new);
+        Ok(())
+    }
+
+    /// Removes everything created in the new data path, files and folders which existed before are left
+    pub async fn rollback(self) -> Result<()> {
+        for path in self.created.iter().rev() {
+            match fs::symlink_metadata(path).await {
+                Ok(metadata) if metadata.is_dir() => {
+                    if let Err(e) = fs::remove_dir(path).await {
+                        warn!("Unable to remove {:?}: {:?}", path, e);
+                    }
+                }
+                Ok(_) => fs::remove_file(path).await?,
+                Err(_) => {}
+            }
+        }
+
+        info!("Rolled back the copy of the data path to {:?}", self.new);
+        Ok(())
+    }
+}
+
+/// Canonical path of a path which may not exist yet, the missing folders are appended to its canonical existing ancestor
+fn canonicalize_existing(path: &Path) -> Result<PathBuf> {
+    let path = path.absolutize()?;
+    let mut missing = Vec::new();
+    let mut existing = path.as_ref();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => bail!("unable to resolve {:?}", path)
+        }
+    }
+
+    let mut canonical = std::fs::canonicalize(existing)?;
+    canonical.extend(missing.iter().rev());
+    Ok(canonical)
+}
+
+fn is_non_empty_dir(path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    Ok(std::fs::read_dir(path)?.next().is_some())
+}
+
+/// Files of the launcher folders, links are not followed
+fn data_files(data_path: &Path) -> Vec<PathBuf> {
+    DATA_DIRS.iter()
+        .flat_map(|dir| WalkDir::new(data_path.join(dir)).into_iter().filter_map(|e| e.ok()))
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+#[cfg(unix)]
+async fn create_symlink(target: &Path, link: &Path, _is_dir: bool) -> Result<()> {
+    fs::symlink(target, link).await?;
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn create_symlink(target: &Path, link: &Path, is_dir: bool) -> Result<()> {
+    if is_dir {
+        fs::symlink_dir(target, link).await?;
+    } else {
+        fs::symlink_file(target, link).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::minecraft::progress::MultiProgressReceiver;
+    use crate::utils::test_util::test_folder;
+
+    use super::*;
+
+    async fn migration_error(old: &Path, new: &Path) -> String {
+        match migrate_data_path(old, new, false, &MultiProgressReceiver::new()).await {
+            Ok(_) => panic!("migration from {:?} to {:?} was started", old, new),
+            Err(err) => err.to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn data_paths_inside_of_each_other_are_rejected() {
+        let old = test_folder("migration-nested");
+        std::fs::create_dir_all(old.join("gameDir")).unwrap();
+
+        assert!(migration_error(&old, &old.join("gameDir").join("new")).await.contains("inside of each other"));
+        assert!(migration_error(&old.join("gameDir"), &old).await.contains("inside of each other"));
+        assert!(!old.join("gameDir").join("new").exists());
+    }
+
+    #[tokio::test]
+    async fn same_data_path_through_another_path_is_rejected() {
+        let old = test_folder("migration-same");
+
+        assert!(migration_error(&old, &old.join("gameDir").join("..")).await.contains("is the current data path"));
+    }
+}
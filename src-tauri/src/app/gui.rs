@@ -1,28 +1,50 @@
-use std::{path::PathBuf, sync::{Arc, Mutex}, thread};
+use std::{path::{Path, PathBuf}, sync::{Arc, Mutex}, thread};
 
 use directories::UserDirs;
 use reqwest::{multipart::{Form, Part}};
 use tauri::{Manager, Window};
 use tauri::api::dialog::blocking::message;
 use tokio::{fs, io::AsyncReadExt};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY, minecraft::{launcher::{LauncherData, LaunchingParameter}, prelauncher, progress::ProgressUpdate}};
-use crate::app::api::{LoginData, NoRiskLaunchManifest};
+use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY, minecraft::{launcher::{self, LauncherData, LaunchingParameterBuilder, QuickPlay}, prelauncher, progress::{FileProgressReceiver, MultiProgressReceiver, ProgressReceiver, ProgressThrottle, ProgressUpdate, WeightedProgress}}};
+use crate::app::api::{AccountRefreshResult, ApiEnvironment, AuthAwaitResponse, LoginData, NoRiskLaunchManifest, refresh_if_expired};
+use crate::error::{CommandContext, CommandError, error_code, LauncherError};
 use crate::app::cape_api::{Cape, CapeApiEndpoints};
 use crate::app::mclogs_api::{McLogsApiEndpoints, McLogsUploadResponse};
+use crate::app::data_migration::{self, DATA_DIRS};
+use crate::app::self_test::SelfTestStep;
 use crate::app::modrinth_api::{CustomMod, InstalledMods, ModInfo, ModrinthApiEndpoints, ModrinthProject, ModrinthSearchRequestParams, ModrinthSearchResponse};
-use crate::minecraft::auth;
-use crate::utils::percentage_of_total_memory;
+use crate::minecraft::install_lock::InstallLock;
+use crate::minecraft::installed::{active_asset_indexes, InstalledVersions, UninstallReport};
+use crate::minecraft::java::{self, JavaInfo};
+use crate::minecraft::version::{PrunedAssets, prune_unused_assets};
+use crate::minecraft::verify::{InstallationDirs, InstallPlan, InvalidFile};
+use crate::utils::{DOWNLOAD_STATS, DownloadStatsSnapshot, percentage_of_total_memory, set_download_retries, set_repository_credentials};
 
-use super::{api::{ApiEndpoints, LoaderMod}, app_data::LauncherOptions};
+use super::{api::{ApiEndpoints, LoaderMod}, app_data::{JvmArgsPolicy, LauncherOptions, ResolvedTheme, Theme}};
 
+/// Launch or game which is currently running, only one can run at a time
 struct RunnerInstance {
+    branch: String,
     terminator: tokio::sync::oneshot::Sender<()>,
+    cancellation: CancellationToken,
+}
+
+/// Sent to the frontend if the game exits with an abnormal exit code
+#[derive(serde::Serialize, Clone)]
+struct CrashReport {
+    branch: String,
+    code: i32,
+    #[serde(rename = "lastOutput")]
+    last_output: Vec<String>,
 }
 
 struct AppState {
     runner_instance: Arc<Mutex<Option<RunnerInstance>>>,
+    /// Cancels the repair which is currently running, if any
+    repair_cancellation: Arc<Mutex<Option<CancellationToken>>>,
 }
 
 
@@ -50,25 +72,25 @@ struct NewMinecraftSkinBody {
 }
 
 #[tauri::command]
-async fn check_online_status() -> Result<(), String> {
+async fn check_online_status() -> Result<(), CommandError> {
     //TODO
     /*HTTP_CLIENT.get("https://api.norisk.gg/launcherapi")
         .send().await
-        .map_err(|e| format!("unable to connect to api.norisk.gg: {:}", e))?
+        .command_context("unable to connect to api.norisk.gg")?
         .error_for_status()
-        .map_err(|e| format!("api.norisk.gg returned an error: {:}", e))?;*/
+        .command_context("api.norisk.gg returned an error")?;*/
     Ok(())
 }
 
 #[tauri::command]
-fn open_url(url: &str) -> Result<(), String> {
+fn open_url(url: &str) -> Result<(), CommandError> {
     open::that(url)
-        .map_err(|e| format!("unable to open url: {:?}", e))?;
+        .command_context("unable to open url")?;
     Ok(())
 }
 
 #[tauri::command]
-async fn upload_cape(norisk_token: &str, window: tauri::Window) -> Result<(), String> {
+async fn upload_cape(norisk_token: &str, window: tauri::Window) -> Result<(), CommandError> {
     debug!("Uploading Cape...");
     use std::path::PathBuf;
     use tauri::api::dialog::blocking::FileDialogBuilder; // Note the updated import
@@ -92,7 +114,7 @@ async fn upload_cape(norisk_token: &str, window: tauri::Window) -> Result<(), St
 }
 
 #[tauri::command]
-async fn equip_cape(norisk_token: &str, hash: &str, window: tauri::Window) -> Result<(), String> {
+async fn equip_cape(norisk_token: &str, hash: &str, window: tauri::Window) -> Result<(), CommandError> {
     debug!("Equiping Cape...");
     use std::path::PathBuf;
     use tauri::api::dialog::blocking::FileDialogBuilder; // Note the updated import
@@ -111,7 +133,7 @@ async fn equip_cape(norisk_token: &str, hash: &str, window: tauri::Window) -> Re
 }
 
 #[tauri::command]
-async fn get_featured_mods(branch: &str, window: tauri::Window) -> Result<Vec<ModInfo>, String> {
+async fn get_featured_mods(branch: &str, window: tauri::Window) -> Result<Vec<ModInfo>, CommandError> {
     debug!("Getting Featured Mods...");
 
     match ApiEndpoints::norisk_featured_mods(&branch).await {
@@ -132,13 +154,13 @@ async fn get_featured_mods(branch: &str, window: tauri::Window) -> Result<Vec<Mo
         }
         Err(err) => {
             message(Some(&window), "Modrinth Error", err.to_string());
-            Err(err.to_string())
+            Err(err.to_string().into())
         }
     }
 }
 
 #[tauri::command]
-async fn search_mods(params: ModrinthSearchRequestParams, window: tauri::Window) -> Result<ModrinthSearchResponse, String> {
+async fn search_mods(params: ModrinthSearchRequestParams, window: tauri::Window) -> Result<ModrinthSearchResponse, CommandError> {
     debug!("Searching Mods...");
 
     match ModrinthApiEndpoints::search_mods(&params).await {
@@ -147,13 +169,13 @@ async fn search_mods(params: ModrinthSearchRequestParams, window: tauri::Window)
         }
         Err(err) => {
             message(Some(&window), "Modrinth Error", err.to_string());
-            Err(err.to_string())
+            Err(err.to_string().into())
         }
     }
 }
 
 #[tauri::command]
-async fn install_mod_and_dependencies(slug: &str, params: &str, required_mods: Vec<LoaderMod>, window: tauri::Window) -> Result<CustomMod, String> {
+async fn install_mod_and_dependencies(slug: &str, params: &str, required_mods: Vec<LoaderMod>, window: tauri::Window) -> Result<CustomMod, CommandError> {
     println!("Installing Mod And Dependencies...");
     match ModrinthApiEndpoints::install_mod_and_dependencies(slug, params, &required_mods).await {
         Ok(installed_mod) => {
@@ -161,13 +183,13 @@ async fn install_mod_and_dependencies(slug: &str, params: &str, required_mods: V
         }
         Err(err) => {
             message(Some(&window), "Modrinth Error", err.to_string());
-            Err(err.to_string())
+            Err(err.to_string().into())
         }
     }
 }
 
 #[tauri::command]
-async fn get_mod_version(slug: &str, params: &str, window: tauri::Window) -> Result<Vec<ModrinthProject>, String> {
+async fn get_mod_version(slug: &str, params: &str, window: tauri::Window) -> Result<Vec<ModrinthProject>, CommandError> {
     println!("Searching Mod Version...");
 
     match ModrinthApiEndpoints::get_mod_version(slug, params).await {
@@ -176,13 +198,13 @@ async fn get_mod_version(slug: &str, params: &str, window: tauri::Window) -> Res
         }
         Err(err) => {
             message(Some(&window), "Modrinth Error", err.to_string());
-            Err(err.to_string())
+            Err(err.to_string().into())
         }
     }
 }
 
 #[tauri::command]
-async fn delete_cape(norisk_token: &str, window: tauri::Window) -> Result<(), String> {
+async fn delete_cape(norisk_token: &str, window: tauri::Window) -> Result<(), CommandError> {
     debug!("Deleting Cape...");
     // dialog_result will be of type Option<PathBuf> now.
 
@@ -198,47 +220,43 @@ async fn delete_cape(norisk_token: &str, window: tauri::Window) -> Result<(), St
 }
 
 #[tauri::command]
-async fn request_trending_capes(norisk_token: &str, alltime: u32, limit: u32) -> Result<Vec<Cape>, String> {
+async fn request_trending_capes(norisk_token: &str, alltime: u32, limit: u32) -> Result<Vec<Cape>, CommandError> {
     match CapeApiEndpoints::request_trending_capes(norisk_token, alltime, limit).await {
         Ok(result) => {
             Ok(result)
         }
         Err(err) => {
-            Err("Error Requesting Trending Capes".to_string())
+            Err("Error Requesting Trending Capes".to_string().into())
         }
     }
 }
 
 #[tauri::command]
-async fn request_owned_capes(norisk_token: &str, limit: u32) -> Result<Vec<Cape>, String> {
+async fn request_owned_capes(norisk_token: &str, limit: u32) -> Result<Vec<Cape>, CommandError> {
     match CapeApiEndpoints::request_owned_capes(norisk_token, limit).await {
         Ok(result) => {
             Ok(result)
         }
         Err(err) => {
-            Err("Error Requesting Owned Capes".to_string())
+            Err("Error Requesting Owned Capes".to_string().into())
         }
     }
 }
 
 #[tauri::command]
-async fn download_template_and_open_explorer() -> Result<(), String> {
+async fn download_template_and_open_explorer() -> Result<(), CommandError> {
     use std::fs::File;
     use std::io::Write;
     let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-    let template_url = if options.experimental_mode {
-        "https://dl-staging.norisk.gg/capes/prod/template.png"
-    } else {
-        "https://dl.norisk.gg/capes/prod/template.png"
-    };
+    let template_url = format!("{}/capes/prod/template.png", options.api_environment.dl_base_url());
     let user_dirs = UserDirs::new().unwrap();
     let downloads_dir = user_dirs.download_dir().unwrap();
     debug!("Downloads directory: {:?}", downloads_dir);
-    let response = HTTP_CLIENT.get(template_url).send().await.map_err(|e| format!("Error downloading template: {:?}", e))?;
-    let template_bytes = response.bytes().await.map_err(|e| format!("Error reading template bytes: {:?}", e))?;
+    let response = HTTP_CLIENT.get(template_url).send().await.command_context("Error downloading template")?;
+    let template_bytes = response.bytes().await.command_context("Error reading template bytes")?;
 
-    let mut file = File::create(downloads_dir.join("nrc_cape_template.png")).map_err(|e| format!("Error creating file: {:?}", e))?;
-    file.write_all(&template_bytes).map_err(|e| format!("Error writing file: {:?}", e))?;
+    let mut file = File::create(downloads_dir.join("nrc_cape_template.png")).command_context("Error creating file")?;
+    file.write_all(&template_bytes).command_context("Error writing file")?;
 
     CapeApiEndpoints::show_in_folder(downloads_dir.join("nrc_cape_template.png").into_os_string().to_str().unwrap());
 
@@ -247,7 +265,7 @@ async fn download_template_and_open_explorer() -> Result<(), String> {
 
 
 #[tauri::command]
-async fn get_options() -> Result<LauncherOptions, String> {
+async fn get_options() -> Result<LauncherOptions, CommandError> {
     let config_dir = LAUNCHER_DIRECTORY.config_dir();
     let options = LauncherOptions::load(config_dir).await.unwrap_or_default(); // default to basic options if unable to load
 
@@ -255,65 +273,93 @@ async fn get_options() -> Result<LauncherOptions, String> {
 }
 
 #[tauri::command]
-async fn get_installed_mods(branch: &str, options: LauncherOptions) -> Result<InstalledMods, String> {
-    let game_dir = options.data_path_buf().join("gameDir").join(branch);
+async fn refresh_all_accounts() -> Result<Vec<AccountRefreshResult>, CommandError> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    let mut options = LauncherOptions::load(config_dir).await
+        .command_context("unable to load options")?;
+
+    // the refresh takes a while, so the refreshed accounts are put into the options stored by then
+    let results = crate::app::api::refresh_all_accounts(&mut options).await;
+    if !results.is_empty() {
+        LauncherOptions::update(config_dir, |stored| {
+            for result in &results {
+                if let Some(account) = options.accounts.iter().find(|account| account.uuid == result.uuid) {
+                    stored.replace_account(account);
+                }
+            }
+            Ok(())
+        }).await
+            .command_context("unable to store options")?;
+    }
+
+    Ok(results)
+}
+
+#[tauri::command]
+fn resolve_theme(theme: Theme) -> ResolvedTheme {
+    theme.resolve()
+}
+
+#[tauri::command]
+async fn get_installed_mods(branch: &str, options: LauncherOptions) -> Result<InstalledMods, CommandError> {
+    let game_dir = launcher::game_directory(&options.data_path_buf(), options.isolate_game_dirs, branch);
     return match tokio::fs::create_dir_all(&game_dir).await {
         Ok(_) => {
             Ok(InstalledMods::load(&game_dir).await.unwrap_or_default()) // default to basic options if unable to load
         }
         Err(err) => {
-            Err(err.to_string())
+            Err(err.to_string().into())
         }
     };
 }
 
 #[tauri::command]
-async fn get_custom_mods_filenames(options: LauncherOptions, branch: &str, mc_version: &str) -> Result<Vec<String>, String> {
+async fn get_custom_mods_filenames(options: LauncherOptions, branch: &str, mc_version: &str) -> Result<Vec<String>, CommandError> {
     let custom_mod_folder = options.data_path_buf().join("custom_mods").join(format!("{}-{}", branch, mc_version));
-    let names = ModrinthApiEndpoints::get_custom_mod_names(&custom_mod_folder).await.map_err(|e| format!("unable to load config filenames: {:?}", e))?;
+    let names = ModrinthApiEndpoints::get_custom_mod_names(&custom_mod_folder).await.command_context("unable to load config filenames")?;
     Ok(names)
 }
 
 #[tauri::command]
-async fn get_custom_mods_folder(options: LauncherOptions, branch: &str, mc_version: &str) -> Result<String, String> {
+async fn get_custom_mods_folder(options: LauncherOptions, branch: &str, mc_version: &str) -> Result<String, CommandError> {
     let custom_mod_folder = options.data_path_buf().join("custom_mods").join(format!("{}-{}", branch, mc_version));
-    return custom_mod_folder.to_str().map(|s| s.to_string()).ok_or_else(|| "Error converting path to string".to_string());
+    return custom_mod_folder.to_str().map(|s| s.to_string()).ok_or_else(|| "Error converting path to string".to_string().into());
 }
 
 #[tauri::command]
-async fn save_custom_mods_to_folder(options: LauncherOptions, branch: &str, mc_version: &str, file: FileData) -> Result<(), String> {
+async fn save_custom_mods_to_folder(options: LauncherOptions, branch: &str, mc_version: &str, file: FileData) -> Result<(), CommandError> {
     let file_path = options.data_path_buf().join("custom_mods").join(format!("{}-{}", branch, mc_version)).join(file.name.clone());
 
     println!("Saving {} to {}-{} custom mods folder.", file.name.clone(), branch, mc_version);
 
     if let Err(err) = fs::copy(PathBuf::from(file.location), &file_path).await {
-        return Err(format!("Error saving custom mod {}: {}", file.name, err));
+        return Err(format!("Error saving custom mod {}: {}", file.name, err).into());
     }
 
     Ok(())
 }
 
 #[tauri::command]
-async fn store_installed_mods(branch: &str, options: LauncherOptions, installed_mods: InstalledMods) -> Result<(), String> {
-    let game_dir = options.data_path_buf().join("gameDir").join(branch);
+async fn store_installed_mods(branch: &str, options: LauncherOptions, installed_mods: InstalledMods) -> Result<(), CommandError> {
+    let game_dir = launcher::game_directory(&options.data_path_buf(), options.isolate_game_dirs, branch);
     return match tokio::fs::create_dir_all(&game_dir).await {
         Ok(_) => {
-            installed_mods.store(&game_dir).await.map_err(|e| format!("unable to store config data: {:?}", e))?; // default to basic options if unable to load
+            installed_mods.store(&game_dir).await.command_context("unable to store config data")?; // default to basic options if unable to load
             Ok(())
         }
         Err(err) => {
-            Err(err.to_string())
+            Err(err.to_string().into())
         }
     };
 }
 
 #[tauri::command]
-async fn get_player_skins(uuid: String) -> Result<Vec<String>, String> {
+async fn get_player_skins(uuid: String) -> Result<Vec<String>, CommandError> {
     let minecraft_profile: Result<MinecraftProfile, reqwest::Error> = HTTP_CLIENT.get(format!("https://sessionserver.mojang.com/session/minecraft/profile/{}", uuid))
         .send().await
-        .map_err(|e| format!("unable to connect to sessionserver.mojang.com: {:}", e))?
+        .command_context("unable to connect to sessionserver.mojang.com")?
         .error_for_status()
-        .map_err(|e| format!("sessionserver.mojang.com returned an error: {:}", e))?
+        .command_context("sessionserver.mojang.com returned an error")?
         .json().await;
 
 
@@ -327,15 +373,15 @@ async fn get_player_skins(uuid: String) -> Result<Vec<String>, String> {
             }
             Ok(textures)
         }
-        Err(_) => Err("Failed to retrieve Minecraft profile".to_string()), // You can provide a custom error message here.
+        Err(_) => Err("Failed to retrieve Minecraft profile".to_string().into()), // You can provide a custom error message here.
     }
 }
 
 #[tauri::command]
-async fn save_player_skin(location: String, slim: bool, access_token: String) -> Result<(), String> {
+async fn save_player_skin(location: String, slim: bool, access_token: String) -> Result<(), CommandError> {
     let file_data = match tokio::fs::read(&location).await {
         Ok(data) => data,
-        Err(e) => return Err(e.to_string()),
+        Err(e) => return Err(e.to_string().into()),
     };
 
     let part = Part::bytes(file_data)
@@ -346,34 +392,34 @@ async fn save_player_skin(location: String, slim: bool, access_token: String) ->
         .multipart(Form::new().text("variant", if slim { "slim" } else { "classic" }).part("file", part))
         .send()
         .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
+        .command_context("Failed to send request")?;
 
     if response.status().is_success() {
         println!("Skin {} saved successfully.", &location);
         Ok(())
     } else {
-        Err(format!("Failed to save the new skin. Status code: {}", response.status()))
+        Err(format!("Failed to save the new skin. Status code: {}", response.status()).into())
     }
 }
 
 #[tauri::command]
-async fn read_local_skin_file(location: String) -> Result<String, String> {
+async fn read_local_skin_file(location: String) -> Result<String, CommandError> {
     match fs::File::open(&location).await {
         Ok(mut file) => {
             let mut buffer = Vec::new();
             if let Err(err) = file.read_to_end(&mut buffer).await {
-                return Err(format!("Failed to read the file: {}", err));
+                return Err(format!("Failed to read the file: {}", err).into());
             }
             Ok(base64::encode(buffer))
         }
         Err(err) => {
-            Err(format!("Failed to open the file: {}", err))
+            Err(format!("Failed to open the file: {}", err).into())
         }
     }
 }
 
 #[tauri::command]
-async fn read_remote_image_file(location: String) -> Result<String, String> {
+async fn read_remote_image_file(location: String) -> Result<String, CommandError> {
     let response = HTTP_CLIENT
         .get(&location)
         .send()
@@ -386,44 +432,109 @@ async fn read_remote_image_file(location: String) -> Result<String, String> {
 
     match response {
         Ok(bytes) => Ok(base64::encode(&bytes)),
-        Err(_) => Err("Failed to fetch cape from remote resource".to_string()),
+        Err(_) => Err("Failed to fetch cape from remote resource".to_string().into()),
     }
 }
 
 #[tauri::command]
-async fn store_options(options: LauncherOptions) -> Result<(), String> {
+async fn store_options(options: LauncherOptions) -> Result<(), CommandError> {
     let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    options.validate_proxy()
+        .command_context("invalid proxy")?;
     options.store(config_dir)
         .await
-        .map_err(|e| format!("unable to store config data: {:?}", e))?;
+        .command_context("unable to store config data")?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn export_settings(path: String) -> Result<(), CommandError> {
+    let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
+    options.export(Path::new(&path))
+        .await
+        .command_context("unable to export settings")?;
 
     Ok(())
 }
 
 #[tauri::command]
-async fn request_norisk_branches(is_experimental: bool) -> Result<Vec<String>, String> {
-    let branches = ApiEndpoints::norisk_branches(is_experimental)
+async fn import_settings(path: String) -> Result<LauncherOptions, CommandError> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    let content = fs::read(&path)
+        .await
+        .command_context("unable to read settings")?;
+    LauncherOptions::update(config_dir, |options| {
+        options.import(&content)?;
+        Ok(options.clone())
+    }).await
+        .command_context("unable to import settings")
+}
+
+#[tauri::command]
+async fn remove_account(uuid: String) -> Result<Option<LoginData>, CommandError> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    LauncherOptions::update(config_dir, |options| options.remove_account(&uuid))
+        .await
+        .command_context("unable to remove account")
+}
+
+#[tauri::command]
+async fn clear_all_accounts() -> Result<usize, CommandError> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    LauncherOptions::update(config_dir, |options| options.clear_all_accounts())
         .await
-        .map_err(|e| format!("unable to request branches: {:?}", e))?;
+        .command_context("unable to clear accounts")
+}
+
+#[tauri::command]
+async fn remove_orphaned_secrets() -> Result<usize, CommandError> {
+    let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await
+        .command_context("unable to load config data")?;
+
+    options.remove_orphaned_secrets()
+        .command_context("unable to remove orphaned tokens")
+}
+
+#[tauri::command]
+async fn set_current_account(uuid: String) -> Result<LoginData, CommandError> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    LauncherOptions::update(config_dir, |options| options.set_current_account(&uuid))
+        .await
+        .command_context("unable to select account")
+}
+
+#[tauri::command]
+async fn request_norisk_branches(api_environment: ApiEnvironment) -> Result<Vec<String>, CommandError> {
+    let branches = ApiEndpoints::norisk_branches(api_environment)
+        .await
+        .command_context("unable to request branches")?;
     Ok(branches)
 }
 
 #[tauri::command]
-async fn get_launch_manifest(branch: &str) -> Result<NoRiskLaunchManifest, String> {
+async fn get_launch_manifest(branch: &str) -> Result<NoRiskLaunchManifest, CommandError> {
     let manifest = ApiEndpoints::launch_manifest(branch).await
-        .map_err(|e| format!("unable to request launch manifest: {:?}", e))?;
+        .command_context("unable to request launch manifest")?;
     Ok(manifest)
 }
 
 #[tauri::command]
-async fn upload_logs(log: String) -> Result<McLogsUploadResponse, String> {
+async fn validate_profile(json: String) -> Result<Vec<String>, CommandError> {
+    let problems = prelauncher::validate_profile(&json).await
+        .command_context("unable to validate profile")?;
+    Ok(problems)
+}
+
+#[tauri::command]
+async fn upload_logs(log: String) -> Result<McLogsUploadResponse, CommandError> {
     let log_response = McLogsApiEndpoints::upload_logs(log).await
-        .map_err(|e| format!("unable to upload logs: {:?}", e))?;
+        .command_context("unable to upload logs")?;
     Ok(log_response)
 }
 
 #[tauri::command]
-async fn login_norisk_microsoft() -> Result<LoginData, String> {
+async fn login_norisk_microsoft() -> Result<LoginData, CommandError> {
     let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
     let auth_prepare_response = ApiEndpoints::auth_prepare_response().await;
     match auth_prepare_response {
@@ -435,21 +546,23 @@ async fn login_norisk_microsoft() -> Result<LoginData, String> {
 
             let login_data = ApiEndpoints::await_auth_response(id).await;
             match login_data {
-                Ok(response) => {
+                Ok(AuthAwaitResponse { login_data: response, expires_in }) => {
                     info!("Received NoRisk Auth Response");
                     Ok(LoginData {
-                        norisk_token: if options.experimental_mode { String::from("") } else { response.norisk_token.clone() },
-                        experimental_token: Option::from(if options.experimental_mode { response.norisk_token } else { String::from("") }),
+                        norisk_token: if options.api_environment == ApiEnvironment::Staging { String::from("") } else { response.norisk_token.clone() },
+                        experimental_token: Option::from(if options.api_environment == ApiEnvironment::Staging { response.norisk_token } else { String::from("") }),
+                        mc_token_expires_at: Some(LoginData::mc_token_expiry(expires_in)),
+                        needs_relogin: false,
                         ..response
                     })
                 }
                 Err(err) => {
-                    Err(format!("await auth error: {:?}", err))
+                    Err(format!("await auth error: {:?}", err).into())
                 }
             }
         }
         Err(err) => {
-            Err(format!("await prepare response error: {:?}", err))
+            Err(format!("await prepare response error: {:?}", err).into())
         }
     }
 }
@@ -476,47 +589,83 @@ fn handle_stderr(window: &Arc<std::sync::Mutex<Window>>, data: &[u8]) -> anyhow:
     Ok(())
 }
 
+/// Progress of launches and other tasks is coalesced before it crosses the IPC bridge, so the core is able to emit freely
+static WINDOW_PROGRESS_THROTTLE: ProgressThrottle = ProgressThrottle::new();
+
 fn handle_progress(window: &Arc<std::sync::Mutex<Window>>, progress_update: ProgressUpdate) -> anyhow::Result<()> {
-    window.lock().unwrap().emit("progress-update", progress_update)?;
+    let flush_window = window.clone();
+    let updates = WINDOW_PROGRESS_THROTTLE.coalesce(progress_update, move |updates| {
+        for progress_update in updates {
+            let _ = flush_window.lock().unwrap().emit("progress-update", progress_update);
+        }
+    });
+
+    for progress_update in updates {
+        window.lock().unwrap().emit("progress-update", progress_update)?;
+    }
     Ok(())
 }
 
+/// Forwards progress of tasks outside of a launch to the window
+struct WindowProgressReceiver(Arc<std::sync::Mutex<Window>>);
+
+impl ProgressReceiver for WindowProgressReceiver {
+    fn progress_update(&self, progress_update: ProgressUpdate) {
+        let _ = handle_progress(&self.0, progress_update);
+    }
+}
+
 #[tauri::command]
-async fn run_client(branch: String, login_data: LoginData, options: LauncherOptions, mods: Vec<LoaderMod>, window: Window, app_state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn run_client(branch: String, mut login_data: LoginData, options: LauncherOptions, mods: Vec<LoaderMod>, quick_play: Option<QuickPlay>, window: Window, app_state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
     info!("Starting Client with branch {}",branch);
+
+    refresh_if_expired(&mut login_data)
+        .await
+        .command_context("unable to refresh account")?;
+
     let window_mutex = Arc::new(std::sync::Mutex::new(window));
 
-    let parameters = LaunchingParameter {
-        dev_mode: options.experimental_mode,
-        memory: percentage_of_total_memory(options.memory_percentage),
-        data_path: options.data_path_buf(),
-        custom_java_path: if !options.custom_java_path.is_empty() { Some(options.custom_java_path) } else { None },
-        custom_java_args: options.custom_java_args,
-        auth_player_name: login_data.username,
-        auth_uuid: login_data.uuid,
-        auth_access_token: login_data.mc_token,
-        auth_xuid: "x".to_string(),
-        clientid: auth::AZURE_CLIENT_ID.to_string(),
-        user_type: "msa".to_string(),
-        keep_launcher_open: options.keep_launcher_open,
-        concurrent_downloads: options.concurrent_downloads,
-    };
+    set_download_retries(options.download_retries);
+    set_repository_credentials(options.repository_credentials());
+
+    let jvm_args_policy = JvmArgsPolicy::load(LAUNCHER_DIRECTORY.config_dir())
+        .await
+        .command_context("unable to load jvm policy")?;
+
+    let cancellation = CancellationToken::new();
+
+    let parameters = LaunchingParameterBuilder::new(options.clone())
+        .branch(&branch)
+        .account(login_data.clone())
+        .jvm_args_policy(jvm_args_policy)
+        .cancellation(cancellation.clone())
+        .quick_play(quick_play)
+        .build()
+        .command_context("invalid launch parameters")?;
+
+    let mut progress_receivers = MultiProgressReceiver::new();
+    if !options.progress_output_path.is_empty() {
+        match FileProgressReceiver::open(&options.progress_output_path) {
+            Ok(receiver) => progress_receivers.add(receiver),
+            Err(e) => error!("Unable to open progress output {}: {:?}", options.progress_output_path, e)
+        }
+    }
 
     let runner_instance = &app_state.runner_instance;
 
-    if runner_instance.lock().map_err(|e| format!("unable to lock runner instance: {:?}", e))?.is_some() {
-        return Err("client is already running".to_string());
+    if let Some(running) = runner_instance.lock().map_err(|e| format!("unable to lock runner instance: {:?}", e))?.as_ref() {
+        return Err(format!("client of branch {} is already running", running.branch).into());
     }
 
     info!("Loading launch manifest...");
     let launch_manifest = ApiEndpoints::launch_manifest(&branch)
         .await
-        .map_err(|e| format!("unable to request launch manifest: {:?}", e))?;
+        .command_context("unable to request launch manifest")?;
 
     let (terminator_tx, terminator_rx) = tokio::sync::oneshot::channel();
 
     *runner_instance.lock().map_err(|e| format!("unable to lock runner instance: {:?}", e))?
-        = Some(RunnerInstance { terminator: terminator_tx });
+        = Some(RunnerInstance { branch: branch.clone(), terminator: terminator_tx, cancellation });
 
     let copy_of_runner_instance = runner_instance.clone();
 
@@ -532,7 +681,7 @@ async fn run_client(branch: String, login_data: LoginData, options: LauncherOpti
                 let keep_launcher_open = parameters.keep_launcher_open;
 
                 if let Err(e) = prelauncher::launch(
-                    &if options.experimental_mode {
+                    &if options.api_environment == ApiEnvironment::Staging {
                         experimental_token
                     } else {
                         norisk_token
@@ -544,6 +693,8 @@ async fn run_client(branch: String, login_data: LoginData, options: LauncherOpti
                         on_stdout: handle_stdout,
                         on_stderr: handle_stderr,
                         on_progress: handle_progress,
+                        progress_receivers,
+                        weighted_progress: WeightedProgress::new(),
                         data: Box::new(window_mutex.clone()),
                         terminator: terminator_rx,
                     },
@@ -553,6 +704,14 @@ async fn run_client(branch: String, login_data: LoginData, options: LauncherOpti
                         window_mutex.lock().unwrap().show().unwrap();
                     }
 
+                    let _ = handle_progress(&window_mutex, ProgressUpdate::failed(format!("{}", e)));
+                    if let Some(LauncherError::GameCrashed { code, last_output }) = e.chain().find_map(|cause| cause.downcast_ref::<LauncherError>()) {
+                        error!("Client of branch {} crashed with exit code {}", branch, code);
+                        let crash_report = CrashReport { branch: branch.clone(), code: *code, last_output: last_output.clone() };
+                        window_mutex.lock().unwrap().emit("client-crashed", crash_report).unwrap();
+                    }
+                    // the code allows the frontend to show a specific message instead of the raw error
+                    window_mutex.lock().unwrap().emit("client-error-code", error_code(&e)).unwrap();
                     window_mutex.lock().unwrap().emit("client-error", format!("Failed to launch client: {:?}", e)).unwrap();
                     handle_stderr(&window_mutex, format!("Failed to launch client: {:?}", e).as_bytes()).unwrap();
                 };
@@ -568,7 +727,17 @@ async fn run_client(branch: String, login_data: LoginData, options: LauncherOpti
 }
 
 #[tauri::command]
-async fn terminate(app_state: tauri::State<'_, AppState>) -> Result<(), String> {
+async fn self_test() -> Result<Vec<SelfTestStep>, CommandError> {
+    Ok(crate::app::self_test::self_test().await)
+}
+
+#[tauri::command]
+fn get_download_stats() -> DownloadStatsSnapshot {
+    DOWNLOAD_STATS.snapshot()
+}
+
+#[tauri::command]
+async fn terminate(app_state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
     let mut lck = app_state.runner_instance.lock()
         .map_err(|e| format!("unable to lock runner instance: {:?}", e))?;
 
@@ -580,9 +749,28 @@ async fn terminate(app_state: tauri::State<'_, AppState>) -> Result<(), String>
 }
 
 #[tauri::command]
-async fn refresh_via_norisk(login_data: LoginData) -> Result<LoginData, String> {
+fn is_game_running(app_state: tauri::State<'_, AppState>) -> Result<bool, CommandError> {
+    let lck = app_state.runner_instance.lock()
+        .map_err(|e| format!("unable to lock runner instance: {:?}", e))?;
+    Ok(lck.is_some())
+}
+
+#[tauri::command]
+async fn cancel_launch(app_state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    let lck = app_state.runner_instance.lock()
+        .map_err(|e| format!("unable to lock runner instance: {:?}", e))?;
+
+    if let Some(inst) = lck.as_ref() {
+        info!("Cancelling launch");
+        inst.cancellation.cancel();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn refresh_via_norisk(login_data: LoginData) -> Result<LoginData, CommandError> {
     let account = login_data.refresh_maybe_fixed().await
-        .map_err(|e| format!("unable to refresh: {:?}", e))?;
+        .command_context("unable to refresh")?;
     Ok(account)
 }
 
@@ -620,26 +808,213 @@ async fn mem_percentage(memory_percentage: i32) -> i64 {
 }
 
 #[tauri::command]
-async fn default_data_folder_path() -> Result<String, String> {
+async fn default_data_folder_path() -> Result<String, CommandError> {
     let data_directory = LAUNCHER_DIRECTORY.data_dir().to_str();
 
     match data_directory {
-        None => Err("unable to get data folder path".to_string()),
+        None => Err("unable to get data folder path".to_string().into()),
         Some(path) => Ok(path.to_string())
     }
 }
 
 #[tauri::command]
-async fn clear_data(options: LauncherOptions) -> Result<(), String> {
+async fn prune_assets(options: LauncherOptions, dry_run: bool, window: Window) -> Result<PrunedAssets, CommandError> {
+    let _install_lock = if dry_run {
+        None
+    } else {
+        Some(InstallLock::acquire(&options.data_path_buf()).command_context("unable to lock the data folder")?)
+    };
+
+    let assets_folder = options.data_path_buf().join("assets");
+    // indexes of uninstalled versions stay in the indexes folder, their objects are unused
+    let active_indexes = active_asset_indexes(&options.data_path_buf())
+        .await
+        .command_context("unable to load the asset indexes of the installed versions")?;
+
+    prune_unused_assets(&assets_folder, &active_indexes, dry_run, &WindowProgressReceiver(Arc::new(Mutex::new(window))))
+        .await
+        .command_context("unable to prune assets")
+}
+
+#[tauri::command]
+async fn get_installed_versions(options: LauncherOptions) -> Result<InstalledVersions, CommandError> {
+    Ok(InstalledVersions::load(&options.data_path_buf()).await)
+}
+
+#[tauri::command]
+async fn uninstall_version(id: String, dry_run: bool, options: LauncherOptions) -> Result<UninstallReport, CommandError> {
+    let _install_lock = if dry_run {
+        None
+    } else {
+        Some(InstallLock::acquire(&options.data_path_buf()).command_context("unable to lock the data folder")?)
+    };
+    crate::minecraft::installed::uninstall_version(&id, &options.data_path_buf(), dry_run)
+        .await
+        .command_context(&format!("unable to uninstall version {}", id))
+}
+
+#[tauri::command]
+async fn verify_installation(branch: String, options: LauncherOptions, window: Window) -> Result<Vec<InvalidFile>, CommandError> {
+    let launch_manifest = ApiEndpoints::launch_manifest(&branch)
+        .await
+        .command_context("unable to request launch manifest")?;
+
+    let data_path = options.data_path_buf();
+    let download_source = options.download_mirror.clone().unwrap_or_default();
+    let profile = prelauncher::load_version_profile(&launch_manifest, &data_path, &download_source)
+        .await
+        .command_context("unable to load version profile")?;
+
+    crate::minecraft::verify::verify_installation(&profile, &InstallationDirs::new(&data_path), &download_source, options.concurrent_downloads as usize, Arc::new(WindowProgressReceiver(Arc::new(Mutex::new(window)))))
+        .await
+        .command_context("unable to verify installation")
+}
+
+#[tauri::command]
+async fn plan_install(branch: String, options: LauncherOptions, window: Window) -> Result<InstallPlan, CommandError> {
+    let launch_manifest = ApiEndpoints::launch_manifest(&branch)
+        .await
+        .command_context("unable to request launch manifest")?;
+
+    let data_path = options.data_path_buf();
+    let download_source = options.download_mirror.clone().unwrap_or_default();
+    let profile = prelauncher::peek_version_profile(&launch_manifest, &data_path, &download_source)
+        .await
+        .command_context("unable to load version profile")?;
+
+    crate::minecraft::verify::plan_install(&profile, &InstallationDirs::new(&data_path), &download_source, options.concurrent_downloads as usize, Arc::new(WindowProgressReceiver(Arc::new(Mutex::new(window)))))
+        .await
+        .command_context("unable to plan install")
+}
+
+#[tauri::command]
+async fn get_launch_command(branch: String, login_data: LoginData, options: LauncherOptions) -> Result<String, CommandError> {
+    let launch_manifest = ApiEndpoints::launch_manifest(&branch)
+        .await
+        .command_context("unable to request launch manifest")?;
+
+    // the token the client would be started with
+    let norisk_token = if options.api_environment == ApiEnvironment::Staging {
+        login_data.experimental_token.clone().unwrap_or_default()
+    } else {
+        login_data.norisk_token.clone()
+    };
+
+    let parameters = LaunchingParameterBuilder::new(options)
+        .branch(&branch)
+        .account(login_data)
+        .build()
+        .command_context("invalid launch parameters")?;
+
+    let profile = prelauncher::peek_version_profile(&launch_manifest, &parameters.data_path, &parameters.download_source)
+        .await
+        .command_context("unable to load version profile")?;
+
+    launcher::dry_run_command(&profile, &parameters, &branch, &norisk_token)
+        .await
+        .command_context("unable to build launch command")
+}
+
+#[tauri::command]
+async fn repair_installation(branch: String, options: LauncherOptions, window: Window, app_state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    let data_path = options.data_path_buf();
+    let _install_lock = InstallLock::acquire(&data_path).command_context("unable to lock the data folder")?;
+
+    let launch_manifest = ApiEndpoints::launch_manifest(&branch)
+        .await
+        .command_context("unable to request launch manifest")?;
+
+    let download_source = options.download_mirror.clone().unwrap_or_default();
+    let profile = prelauncher::load_version_profile(&launch_manifest, &data_path, &download_source)
+        .await
+        .command_context("unable to load version profile")?;
+
+    let cancellation = CancellationToken::new();
+    *app_state.repair_cancellation.lock()
+        .map_err(|e| format!("unable to lock repair cancellation: {:?}", e))? = Some(cancellation.clone());
+
+    let result = crate::minecraft::verify::repair_installation(&profile, &InstallationDirs::new(&data_path), &download_source, options.concurrent_downloads as usize, Arc::new(WindowProgressReceiver(Arc::new(Mutex::new(window)))), cancellation)
+        .await
+        .command_context("unable to repair installation");
+
+    if let Ok(mut repair_cancellation) = app_state.repair_cancellation.lock() {
+        *repair_cancellation = None;
+    }
+    result
+}
+
+#[tauri::command]
+async fn cancel_repair(app_state: tauri::State<'_, AppState>) -> Result<(), CommandError> {
+    let lck = app_state.repair_cancellation.lock()
+        .map_err(|e| format!("unable to lock repair cancellation: {:?}", e))?;
+
+    if let Some(cancellation) = lck.as_ref() {
+        info!("Cancelling repair");
+        cancellation.cancel();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn migrate_data_path(new_path: String, force: bool, window: Window) -> Result<LauncherOptions, CommandError> {
+    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    let options = LauncherOptions::load(config_dir).await
+        .command_context("unable to load config data")?;
+
+    let new_path = PathBuf::from(new_path);
+    if new_path == options.data_path_buf() {
+        return Ok(options);
+    }
+
+    // held until the old folders are removed, the new data path has to stay empty for the migration
+    let _install_lock = InstallLock::acquire(&options.data_path_buf()).command_context("unable to lock the data folder")?;
+
+    let progress = WindowProgressReceiver(Arc::new(Mutex::new(window)));
+    let migration = data_migration::migrate_data_path(&options.data_path_buf(), &new_path, force, &progress)
+        .await
+        .command_context("unable to migrate data path")?;
+
+    // the data path is only changed once everything has been copied, the old folders are only removed once it is changed
+    let stored = LauncherOptions::update(config_dir, |options| {
+        options.data_path = new_path.to_string_lossy().to_string();
+        Ok(options.clone())
+    }).await;
+    let options = match stored {
+        Ok(options) => options,
+        Err(e) => {
+            if let Err(rollback_error) = migration.rollback().await {
+                error!("Unable to roll back the copy of the data path: {:?}", rollback_error);
+            }
+            return Err(e).command_context("unable to store config data");
+        }
+    };
+
+    // the launcher already uses the new data path, leftovers of the old one only take space
+    if let Err(e) = migration.finish(&progress).await {
+        error!("Unable to remove the old data path: {:?}", e);
+    }
+
+    Ok(options)
+}
+
+#[tauri::command]
+async fn validate_java(path: String) -> Result<JavaInfo, CommandError> {
+    java::validate_java(&PathBuf::from(path))
+        .await
+        .command_context("invalid java")
+}
+
+#[tauri::command]
+async fn clear_data(options: LauncherOptions) -> Result<(), CommandError> {
     let _ = store_options(LauncherOptions::default()).await;
 
-    ["assets", "gameDir", "libraries", "mod_cache", "natives", "runtimes", "versions"]
+    DATA_DIRS
         .iter()
         .map(|dir| options.data_path_buf().join(dir))
         .filter(|dir| dir.exists())
         .map(std::fs::remove_dir_all)
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("unable to clear data: {:?}", e))?;
+        .command_context("unable to clear data")?;
     Ok(())
 }
 
@@ -652,13 +1027,22 @@ pub fn gui_main() {
             Ok(())
         })
         .manage(AppState {
-            runner_instance: Arc::new(Mutex::new(None))
+            runner_instance: Arc::new(Mutex::new(None)),
+            repair_cancellation: Arc::new(Mutex::new(None))
         })
         .invoke_handler(tauri::generate_handler![
             open_url,
             check_online_status,
             get_options,
+            refresh_all_accounts,
+            resolve_theme,
             store_options,
+            export_settings,
+            import_settings,
+            remove_account,
+            clear_all_accounts,
+            remove_orphaned_secrets,
+            set_current_account,
             request_norisk_branches,
             login_norisk_microsoft,
             upload_cape,
@@ -678,6 +1062,15 @@ pub fn gui_main() {
             request_owned_capes,
             refresh_via_norisk,
             clear_data,
+            validate_java,
+            migrate_data_path,
+            prune_assets,
+            get_installed_versions,
+            uninstall_version,
+            verify_installation,
+            plan_install,
+            get_launch_command,
+            repair_installation,
             get_installed_mods,
             get_custom_mods_folder,
             save_custom_mods_to_folder,
@@ -685,10 +1078,16 @@ pub fn gui_main() {
             get_mod_version,
             upload_logs,
             get_launch_manifest,
+            validate_profile,
             store_installed_mods,
             get_custom_mods_filenames,
             mem_percentage,
             default_data_folder_path,
+            get_download_stats,
+            self_test,
+            cancel_launch,
+            cancel_repair,
+            is_game_running,
             terminate
         ])
         .run(tauri::generate_context!())
@@ -2,6 +2,10 @@ pub mod gui;
 
 pub mod api;
 pub mod app_data;
+pub mod data_migration;
 pub mod cape_api;
 pub mod modrinth_api;
 pub mod mclogs_api;
+pub mod norisk_api;
+pub mod secret_store;
+pub mod self_test;
@@ -0,0 +1,187 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use reqwest::{Method, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use tracing::{debug, info, warn};
+
+use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::app::api::ApiEnvironment;
+use crate::app::app_data::LauncherOptions;
+
+/// Attempts of a request before a transient failure is returned
+const NORISK_API_ATTEMPTS: u32 = 3;
+
+/// Backoff before the first retry, doubled on every further retry
+const NORISK_API_BACKOFF_MILLIS: u64 = 500;
+
+///
+/// Client of the NoRisk API. Selects the base url of the api environment, retries transient failures
+/// with exponential backoff and refreshes the norisk token once if the api rejects it.
+///
+#[derive(Clone, Debug)]
+pub struct NoriskApi {
+    environment: ApiEnvironment,
+    base_url: String,
+}
+
+impl NoriskApi {
+    pub fn new(environment: ApiEnvironment) -> Self {
+        Self { environment, base_url: environment.api_base_url().to_string() }
+    }
+
+    /// Client for the api environment of the stored options
+    pub async fn load() -> Self {
+        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
+        Self::new(options.api_environment)
+    }
+
+    pub fn environment(&self) -> ApiEnvironment {
+        self.environment
+    }
+
+    /// Absolute url of a path of the api
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    /// Requests JSON formatted data, failing on an error status
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Ok(self.send(Method::GET, path, None).await?
+            .error_for_status()?
+            .json::<T>()
+            .await?)
+    }
+
+    /// Posts the body and parses the JSON formatted response, failing on an error status
+    pub async fn post_json<T: DeserializeOwned>(&self, path: &str, body: Option<Vec<u8>>) -> Result<T> {
+        Ok(self.send(Method::POST, path, body).await?
+            .error_for_status()?
+            .json::<T>()
+            .await?)
+    }
+
+    ///
+    /// Sends the request, retrying network errors, server errors and rate limiting of idempotent methods.
+    /// Other error statuses are returned as response, so the caller can read the message of the api.
+    /// Requests like POST are sent once, the api might have processed them although the response got lost.
+    ///
+    pub async fn send(&self, method: Method, path: &str, body: Option<Vec<u8>>) -> Result<Response> {
+        let url = self.url(path);
+        let attempts = if method.is_idempotent() { NORISK_API_ATTEMPTS } else { 1 };
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            debug!("{} {}", method, url);
+
+            let mut request = HTTP_CLIENT.request(method.clone(), &url);
+            if let Some(body) = &body {
+                request = request.body(body.clone());
+            }
+
+            let retry_reason = match request.send().await {
+                Ok(response) if attempt < attempts && is_retryable_status(response.status()) => format!("status {}", response.status()),
+                Ok(response) => return Ok(response),
+                Err(err) if attempt < attempts && (err.is_timeout() || err.is_connect() || err.is_request()) => format!("{:?}", err),
+                Err(err) if attempt > 1 => bail!("request to {} failed after {} attempts: {:?}", url, attempt, err),
+                Err(err) => return Err(err.into())
+            };
+
+            let backoff = Duration::from_millis(NORISK_API_BACKOFF_MILLIS * 2u64.pow(attempt - 1));
+            warn!("Request to {} failed ({}), retrying in {:?} (attempt {}/{})", url, retry_reason, backoff, attempt, attempts);
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    ///
+    /// Sends a request carrying the norisk token, the path is built from the token.
+    /// If the api rejects the token, the account owning it is refreshed and the request is sent once more with the new token.
+    ///
+    pub async fn send_authorized<F>(&self, method: Method, token: &str, path: F, body: Option<Vec<u8>>) -> Result<Response> where F: Fn(&str) -> String {
+        self.send_refreshing(method, token, path, body, |token| self.refresh_token(token)).await
+    }
+
+    async fn send_refreshing<F, R, Fut>(&self, method: Method, token: &str, path: F, body: Option<Vec<u8>>, refresh: R) -> Result<Response>
+        where F: Fn(&str) -> String, R: FnOnce(String) -> Fut, Fut: Future<Output = Result<String>> {
+        let response = self.send(method.clone(), &path(token), body.clone()).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        info!("NoRisk token was rejected, refreshing it");
+        let refreshed_token = refresh(token.to_string()).await?;
+        self.send(method, &path(&refreshed_token), body).await
+    }
+
+    /// Refreshes the stored account owning the token and returns its new token for the environment
+    async fn refresh_token(&self, token: String) -> Result<String> {
+        let config_dir = LAUNCHER_DIRECTORY.config_dir();
+        let options = LauncherOptions::load(config_dir).await?;
+
+        let account = match options.accounts.into_iter().find(|account| account.norisk_token_for(self.environment) == token) {
+            Some(account) => account,
+            None => bail!("no account owns the rejected norisk token, log in again")
+        };
+        let account = account.refresh_maybe_fixed().await?;
+
+        LauncherOptions::update(config_dir, |options| {
+            options.replace_account(&account);
+            Ok(())
+        }).await?;
+        Ok(account.norisk_token_for(self.environment).to_string())
+    }
+}
+
+/// Server errors and rate limiting are worth retrying
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use crate::utils::test_util::MockServer;
+
+    use super::*;
+
+    /// Api against a mock server serving every request with the status and body for its path
+    async fn mock_api(respond: fn(&str) -> (u16, &'static [u8])) -> (NoriskApi, MockServer) {
+        let server = MockServer::start(Duration::ZERO, respond).await;
+        (NoriskApi { environment: ApiEnvironment::Production, base_url: server.base_url.clone() }, server)
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_refreshed_and_the_request_repeated() {
+        let (api, server) = mock_api(|path| match path {
+            "/cosmetics/old-token" => (401, b""),
+            "/cosmetics/new-token" => (200, b"{}"),
+            _ => (404, b"")
+        }).await;
+
+        let refreshed = AtomicUsize::new(0);
+        let response = api.send_refreshing(Method::GET, "old-token", |token| format!("cosmetics/{}", token), None, |token| {
+            refreshed.fetch_add(1, Ordering::SeqCst);
+            async move {
+                assert_eq!(token, "old-token");
+                Ok("new-token".to_string())
+            }
+        }).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(refreshed.load(Ordering::SeqCst), 1);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn post_is_not_retried() {
+        let (api, server) = mock_api(|_| (503, b"")).await;
+
+        let response = api.send(Method::POST, "launcher/login", Some(b"{}".to_vec())).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(server.requests().len(), 1);
+    }
+}
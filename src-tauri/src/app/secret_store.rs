@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use keyring::Entry as KeyringEntry;
+use once_cell::sync::Lazy;
+use tracing::{info, warn};
+
+use crate::LAUNCHER_DIRECTORY;
+use crate::error::LauncherError;
+
+const KEYRING_SERVICE: &str = "noriskclient-launcher";
+
+/// Secret store picked once at startup, the keyring is preferred if it is available
+pub static SECRET_STORE: Lazy<Box<dyn SecretStore>> = Lazy::new(|| {
+    if KeyringSecretStore::is_available() {
+        info!("Using keyring secret store");
+        Box::new(KeyringSecretStore)
+    } else {
+        warn!("Keyring is unavailable, using encrypted file secret store");
+        Box::new(FileSecretStore::new(LAUNCHER_DIRECTORY.config_dir().to_path_buf()))
+    }
+});
+
+/// Storage for account tokens
+pub trait SecretStore: Send + Sync {
+    /// Returns the secret, or none if it doesn't exist
+    fn get(&self, key: &str) -> Result<Option<String>>;
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+    /// Deletes the secret, deleting a missing secret is not an error
+    fn delete(&self, key: &str) -> Result<()>;
+    /// Keys of all stored secrets
+    fn keys(&self) -> Result<Vec<String>>;
+}
+
+/// The keyring can't list its entries, so the keys are tracked in an entry of their own
+const KEYRING_INDEX_KEY: &str = "secret-index";
+
+/// Serializes the updates of the keyring index
+static KEYRING_INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Secret store backed by the OS keyring
+pub struct KeyringSecretStore;
+
+impl KeyringSecretStore {
+    fn is_available() -> bool {
+        let probe = || -> keyring::Result<()> {
+            let entry = KeyringEntry::new(KEYRING_SERVICE, "availability-probe")?;
+            entry.set_password("probe")?;
+            entry.get_password()?;
+            entry.delete_password()
+        };
+
+        match probe() {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("Keyring probe failed: {:?}", err);
+                false
+            }
+        }
+    }
+}
+
+fn keyring_unavailable(err: keyring::Error) -> anyhow::Error {
+    LauncherError::KeyringUnavailable(err.to_string()).into()
+}
+
+impl KeyringSecretStore {
+    fn read_index(&self) -> Result<Vec<String>> {
+        match self.get(KEYRING_INDEX_KEY)? {
+            Some(index) => Ok(serde_json::from_str(&index).unwrap_or_default()),
+            None => Ok(Vec::new())
+        }
+    }
+
+    fn update_index<F: FnOnce(&mut Vec<String>)>(&self, update: F) -> Result<()> {
+        let _lock = KEYRING_INDEX_LOCK.lock().map_err(|_| anyhow!("secret store lock poisoned"))?;
+
+        let mut index = self.read_index()?;
+        update(&mut index);
+        KeyringEntry::new(KEYRING_SERVICE, KEYRING_INDEX_KEY)
+            .and_then(|entry| entry.set_password(&serde_json::to_string(&index).unwrap_or_default()))
+            .map_err(keyring_unavailable)
+    }
+}
+
+impl SecretStore for KeyringSecretStore {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        match KeyringEntry::new(KEYRING_SERVICE, key).map_err(keyring_unavailable)?.get_password() {
+            Ok(password) => Ok(Some(password)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(keyring_unavailable(err))
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        KeyringEntry::new(KEYRING_SERVICE, key)
+            .and_then(|entry| entry.set_password(value))
+            .map_err(keyring_unavailable)?;
+
+        self.update_index(|index| {
+            if !index.iter().any(|x| x == key) {
+                index.push(key.to_string());
+            }
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        match KeyringEntry::new(KEYRING_SERVICE, key).map_err(keyring_unavailable)?.delete_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => {}
+            Err(err) => return Err(keyring_unavailable(err))
+        }
+
+        self.update_index(|index| index.retain(|x| x != key))
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        self.read_index()
+    }
+}
+
+/// Keystore entry of earlier versions holding the key the secrets file is encrypted with
+const LEGACY_KEY_ENTRY: &str = "secret-store-key";
+
+///
+/// Key of the secrets file stored by earlier versions in the keystore. On linux it was kept in the kernel keyring,
+/// which only holds it until the next reboot, so it is moved to the key file if it is still there.
+///
+fn legacy_key_entry() -> Option<KeyringEntry> {
+    #[cfg(target_os = "linux")]
+    let entry = keyring::keyutils::KeyutilsCredential::new_with_target(None, KEYRING_SERVICE, LEGACY_KEY_ENTRY)
+        .map(|credential| KeyringEntry::new_with_credential(Box::new(credential)));
+    #[cfg(not(target_os = "linux"))]
+    let entry = KeyringEntry::new(KEYRING_SERVICE, LEGACY_KEY_ENTRY);
+
+    entry.ok()
+}
+
+///
+/// Writes the file with permissions for the owner only. The content is written to a temporary file which is renamed into place,
+/// so a failing write never leaves a partially written file behind.
+///
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let mut temp_name = path.file_name().ok_or_else(|| anyhow!("{:?} has no file name", path))?.to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    // a leftover temporary file would keep its permissions
+    let _ = std::fs::remove_file(&temp_path);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let written = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(err.into());
+    }
+
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+///
+/// Secret store keeping the secrets encrypted in a file of the config directory.
+/// Used on systems without a keyring (e.g. headless linux without Secret Service).
+/// The key is kept in a key file next to it which only the owner can read, like the secrets file itself.
+///
+pub struct FileSecretStore {
+    secrets_path: PathBuf,
+    key_path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileSecretStore {
+    pub fn new(config_dir: PathBuf) -> Self {
+        FileSecretStore {
+            secrets_path: config_dir.join("secrets.json"),
+            key_path: config_dir.join("secrets.key"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn cipher(&self) -> Result<ChaCha20Poly1305> {
+        let key = match std::fs::read(&self.key_path) {
+            Ok(key) if key.len() == 32 => *Key::from_slice(&key),
+            Ok(_) => return Err(anyhow!("invalid secret store key")),
+            Err(err) if err.kind() == ErrorKind::NotFound => self.create_key()?,
+            Err(err) => return Err(err.into())
+        };
+
+        Ok(ChaCha20Poly1305::new(&key))
+    }
+
+    /// Writes the key file with the key of the keystore of earlier versions, or a new key
+    fn create_key(&self) -> Result<Key> {
+        let legacy_entry = legacy_key_entry();
+        let legacy_key = legacy_entry.as_ref()
+            .and_then(|entry| entry.get_password().ok())
+            .and_then(|encoded| base64::decode(encoded).ok())
+            .filter(|key| key.len() == 32);
+
+        let key = match &legacy_key {
+            Some(key) => *Key::from_slice(key),
+            None => {
+                // the secrets were encrypted with a key which is gone, so they can't be read anymore
+                if self.secrets_path.exists() {
+                    warn!("Key of the secret store is gone, discarding the stored secrets");
+                    std::fs::remove_file(&self.secrets_path)?;
+                }
+                ChaCha20Poly1305::generate_key(&mut OsRng)
+            }
+        };
+
+        write_private(&self.key_path, key.as_slice())?;
+        if let (Some(entry), Some(_)) = (legacy_entry, legacy_key) {
+            info!("Moved the secret store key from the keystore to {:?}", self.key_path);
+            let _ = entry.delete_password();
+        }
+        Ok(key)
+    }
+
+    fn read_secrets(&self) -> Result<HashMap<String, String>> {
+        if !self.secrets_path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        Ok(serde_json::from_slice(&std::fs::read(&self.secrets_path)?)?)
+    }
+
+    fn write_secrets(&self, secrets: &HashMap<String, String>) -> Result<()> {
+        write_private(&self.secrets_path, &serde_json::to_vec_pretty(secrets)?)
+    }
+}
+
+impl SecretStore for FileSecretStore {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let _lock = self.lock.lock().map_err(|_| anyhow!("secret store lock poisoned"))?;
+
+        // loaded first, the secrets are discarded if their key is gone
+        let cipher = self.cipher()?;
+        let encoded = match self.read_secrets()?.remove(key) {
+            Some(encoded) => encoded,
+            None => return Ok(None)
+        };
+
+        // nonce is stored in front of the cipher text
+        let data = base64::decode(encoded)?;
+        if data.len() < 12 {
+            return Err(anyhow!("invalid secret {}", key));
+        }
+        let (nonce, cipher_text) = data.split_at(12);
+
+        let plain_text = cipher
+            .decrypt(Nonce::from_slice(nonce), cipher_text)
+            .map_err(|_| anyhow!("unable to decrypt secret {}", key))?;
+
+        Ok(Some(String::from_utf8(plain_text)?))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        let _lock = self.lock.lock().map_err(|_| anyhow!("secret store lock poisoned"))?;
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let cipher_text = self.cipher()?
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| anyhow!("unable to encrypt secret {}", key))?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&cipher_text);
+
+        let mut secrets = self.read_secrets()?;
+        secrets.insert(key.to_string(), base64::encode(data));
+        self.write_secrets(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let _lock = self.lock.lock().map_err(|_| anyhow!("secret store lock poisoned"))?;
+
+        let mut secrets = self.read_secrets()?;
+        if secrets.remove(key).is_some() {
+            self.write_secrets(&secrets)?;
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        let _lock = self.lock.lock().map_err(|_| anyhow!("secret store lock poisoned"))?;
+
+        Ok(self.read_secrets()?.into_keys().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::test_util::test_folder;
+
+    use super::*;
+
+    #[test]
+    fn secrets_are_readable_by_a_new_store() {
+        let config_dir = test_folder("secret-store");
+
+        FileSecretStore::new(config_dir.clone()).set("uuid.mcToken", "mc-token").unwrap();
+
+        let store = FileSecretStore::new(config_dir);
+        assert_eq!(store.get("uuid.mcToken").unwrap().as_deref(), Some("mc-token"));
+        assert_eq!(store.keys().unwrap(), vec!["uuid.mcToken"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secrets_and_key_are_only_readable_by_the_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let config_dir = test_folder("secret-store-permissions");
+        FileSecretStore::new(config_dir.clone()).set("uuid.mcToken", "mc-token").unwrap();
+
+        for file in ["secrets.json", "secrets.key"] {
+            let mode = std::fs::metadata(config_dir.join(file)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file);
+        }
+        assert!(!config_dir.join("secrets.json.tmp").exists());
+    }
+}
@@ -0,0 +1,84 @@
+use serde::Serialize;
+use tokio::fs;
+
+use crate::app::secret_store::SECRET_STORE;
+use crate::utils::{download_file_untracked, sha1sum, sha1sum_bytes};
+
+/// Tiny library with a well-known sha1 used to verify the download pipeline
+const SELF_TEST_URL: &str = "https://libraries.minecraft.net/com/mojang/patchy/1.3.9/patchy-1.3.9.jar";
+const SELF_TEST_SHA1: &str = "eb8bb7b66fa0e2152b1b40b3856e82f7619439ee";
+const SELF_TEST_SIZE: usize = 4439;
+
+#[derive(Debug, Serialize)]
+pub struct SelfTestStep {
+    pub name: String,
+    pub success: bool,
+    pub message: String,
+}
+
+impl SelfTestStep {
+    fn new(name: &str, result: Result<String, String>) -> Self {
+        let success = result.is_ok();
+        SelfTestStep {
+            name: name.to_string(),
+            success,
+            message: result.unwrap_or_else(|e| e),
+        }
+    }
+}
+
+///
+/// Downloads a tiny known file, verifies it and reports each step,
+/// to isolate environment issues from Minecraft specific problems.
+///
+pub async fn self_test() -> Vec<SelfTestStep> {
+    let mut steps = Vec::new();
+    let test_file = std::env::temp_dir().join("noriskclient-self-test.jar");
+
+    let download = download_file_untracked(SELF_TEST_URL, &test_file).await
+        .map(|_| format!("Downloaded {} to {}", SELF_TEST_URL, test_file.display()))
+        .map_err(|e| format!("Unable to download {}: {:?}", SELF_TEST_URL, e));
+    let downloaded = download.is_ok();
+    steps.push(SelfTestStep::new("download", download));
+
+    if downloaded {
+        let verify = match sha1sum(&test_file) {
+            Ok(hash) if hash == SELF_TEST_SHA1 => Ok(format!("sha1 {} matches", hash)),
+            Ok(hash) => Err(format!("sha1 {} doesn't match {}", hash, SELF_TEST_SHA1)),
+            Err(e) => Err(format!("Unable to hash file: {:?}", e)),
+        };
+        steps.push(SelfTestStep::new("verify", verify));
+
+        let reread = match fs::read(&test_file).await {
+            Ok(bytes) if bytes.len() != SELF_TEST_SIZE => Err(format!("size {} doesn't match {}", bytes.len(), SELF_TEST_SIZE)),
+            Ok(bytes) if sha1sum_bytes(&bytes) != SELF_TEST_SHA1 => Err("sha1 of re-read file doesn't match".to_string()),
+            Ok(bytes) => Ok(format!("Re-read {} bytes", bytes.len())),
+            Err(e) => Err(format!("Unable to re-read file: {:?}", e)),
+        };
+        steps.push(SelfTestStep::new("reread", reread));
+    }
+
+    steps.push(SelfTestStep::new("secret_store", secret_store_round_trip()));
+
+    if test_file.exists() {
+        let cleanup = fs::remove_file(&test_file).await
+            .map(|_| "Removed test file".to_string())
+            .map_err(|e| format!("Unable to remove test file: {:?}", e));
+        steps.push(SelfTestStep::new("cleanup", cleanup));
+    }
+
+    steps
+}
+
+fn secret_store_round_trip() -> Result<String, String> {
+    SECRET_STORE.set("self-test", SELF_TEST_SHA1)
+        .map_err(|e| format!("Unable to write to secret store: {:?}", e))?;
+    let password = SECRET_STORE.get("self-test")
+        .map_err(|e| format!("Unable to read from secret store: {:?}", e))?;
+    let _ = SECRET_STORE.delete("self-test");
+
+    if password.as_deref() != Some(SELF_TEST_SHA1) {
+        return Err("Secret store returned a different value".to_string());
+    }
+    Ok("Secret store is working".to_string())
+}
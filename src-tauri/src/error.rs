@@ -1,3 +1,4 @@
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,5 +7,99 @@ pub enum LauncherError {
     InvalidVersionProfile(String),
     #[error("Unknown template parameter: {0}")]
     UnknownTemplateParameter(String),
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("Download of {url} failed: {source}")]
+    DownloadFailed {
+        url: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Download of {0} stalled")]
+    DownloadStalled(String),
+    #[error("Checksum mismatch of {file}: expected {expected}, got {got}")]
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        got: String,
+    },
+    #[error("Unable to merge version profiles: {0}")]
+    ProfileMergeConflict(String),
+    #[error("Keyring unavailable: {0}")]
+    KeyringUnavailable(String),
+    #[error("Insufficient disk space: {required} bytes are required, but only {available} bytes are available")]
+    InsufficientDiskSpace {
+        required: u64,
+        available: u64,
+    },
+    #[error("Game crashed with exit code {code}")]
+    GameCrashed {
+        code: i32,
+        /// Last lines of the game output before the crash
+        last_output: Vec<String>,
+    },
+    #[error("Another launcher instance is installing, try again once it is finished")]
+    InstallLocked,
 }
 
+impl LauncherError {
+    /// Stable code of the error, used by the frontend to pick a message
+    pub fn code(&self) -> &'static str {
+        match self {
+            LauncherError::InvalidVersionProfile(_) => "invalid_version_profile",
+            LauncherError::UnknownTemplateParameter(_) => "unknown_template_parameter",
+            LauncherError::Cancelled => "cancelled",
+            LauncherError::DownloadFailed { .. } => "download_failed",
+            LauncherError::DownloadStalled(_) => "download_stalled",
+            LauncherError::ChecksumMismatch { .. } => "checksum_mismatch",
+            LauncherError::ProfileMergeConflict(_) => "profile_merge_conflict",
+            LauncherError::KeyringUnavailable(_) => "keyring_unavailable",
+            LauncherError::InsufficientDiskSpace { .. } => "insufficient_disk_space",
+            LauncherError::GameCrashed { .. } => "game_crashed",
+            LauncherError::InstallLocked => "install_locked",
+        }
+    }
+}
+
+/// Code of the first launcher error in the chain of the error, or "unknown" if it has none
+pub fn error_code(err: &anyhow::Error) -> &'static str {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<LauncherError>())
+        .map_or("unknown", |err| err.code())
+}
+
+///
+/// Error returned by the Tauri commands, the code allows the frontend to show a specific message (see [LauncherError::code]).
+///
+#[derive(Serialize, Debug)]
+pub struct CommandError {
+    pub code: String,
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new(context: &str, err: anyhow::Error) -> Self {
+        CommandError {
+            code: error_code(&err).to_string(),
+            message: format!("{}: {:?}", context, err),
+        }
+    }
+}
+
+/// Errors which are only described by a message
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError { code: "unknown".to_string(), message }
+    }
+}
+
+/// Converts the error of an internal call into a [CommandError] with context
+pub trait CommandContext<T> {
+    fn command_context(self, context: &str) -> Result<T, CommandError>;
+}
+
+impl<T, E: Into<anyhow::Error>> CommandContext<T> for Result<T, E> {
+    fn command_context(self, context: &str) -> Result<T, CommandError> {
+        self.map_err(|err| CommandError::new(context, err.into()))
+    }
+}
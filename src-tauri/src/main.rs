@@ -9,7 +9,7 @@ use once_cell::sync::Lazy;
 use anyhow::Result;
 use directories::ProjectDirs;
 use reqwest::Client;
-use tracing::{debug};
+use tracing::{debug, warn};
 use tracing_subscriber::layer::SubscriberExt;
 
 pub mod app;
@@ -26,18 +26,29 @@ static LAUNCHER_DIRECTORY: Lazy<ProjectDirs> = Lazy::new(|| {
     }
 });
 
-static APP_USER_AGENT: &str = concat!(
-    env!("CARGO_PKG_NAME"),
-    "/",
-    env!("CARGO_PKG_VERSION"),
-);
+/// User agent of all launcher requests, e.g. NoRiskClient-Launcher/0.1.0 (windows)
+static APP_USER_AGENT: Lazy<String> = Lazy::new(|| {
+    format!("NoRiskClient-Launcher/{} ({})", LAUNCHER_VERSION, std::env::consts::OS)
+});
 
 /// HTTP Client with launcher agent
 static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
-    let client = reqwest::ClientBuilder::new()
-            .user_agent(APP_USER_AGENT)
+    let mut builder = reqwest::ClientBuilder::new()
+            .user_agent(APP_USER_AGENT.as_str())
+            .timeout(utils::http_timeout())
+            .connect_timeout(utils::http_connect_timeout());
+    if let Some(proxy) = utils::http_proxy() {
+        builder = builder.proxy(proxy);
+    }
+    let client = builder
             .build()
-            .unwrap_or_else(|_| Client::new());
+            .unwrap_or_else(|err| {
+                warn!("Unable to build the http client, falling back to one without proxy and timeouts: {:?}", err);
+                Client::builder()
+                    .user_agent(APP_USER_AGENT.as_str())
+                    .build()
+                    .unwrap_or_else(|_| Client::new())
+            });
 
     client
 });
@@ -70,8 +81,37 @@ pub fn main() -> Result<()> {
     fs::create_dir_all(LAUNCHER_DIRECTORY.data_dir())?;
     fs::create_dir_all(LAUNCHER_DIRECTORY.config_dir())?;
 
+    // secret store backend is detected once
+    Lazy::force(&app::secret_store::SECRET_STORE);
+
+    // timeouts and the proxy have to be known before the http client is built
+    let options = tauri::async_runtime::block_on(app::app_data::LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()))
+        .unwrap_or_default();
+    utils::set_http_timeouts(options.http_timeout_secs, options.http_connect_timeout_secs, options.download_inactivity_timeout_secs);
+    utils::set_http_proxy(options.proxy.clone());
+    utils::set_repository_credentials(options.repository_credentials());
+
     // app
     app::gui::gui_main();
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::utils::test_util::MockServer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn requests_carry_the_launcher_user_agent() {
+        let server = MockServer::start(Duration::ZERO, |_| (200, b"")).await;
+
+        HTTP_CLIENT.get(format!("{}/version_manifest_v2.json", server.base_url)).send().await.unwrap();
+
+        let expected = format!("user-agent: NoRiskClient-Launcher/{} ({})", LAUNCHER_VERSION, std::env::consts::OS);
+        assert!(server.requests()[0].lines().any(|line| line.eq_ignore_ascii_case(&expected)));
+    }
+}
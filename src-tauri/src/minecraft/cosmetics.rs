@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::*;
+
+/// Hash of a cosmetic file and the size and modification time it had when it was verified
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CosmeticEntry {
+    hash: String,
+    size: u64,
+    mtime: u64,
+}
+
+///
+/// Remembers which cosmetic files of a branch were verified, so unchanged files don't have to be hashed on every launch.
+/// A file is only trusted if its size and modification time still match, everything else is hashed again.
+///
+pub struct CosmeticManifest {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CosmeticEntry>>,
+}
+
+impl CosmeticManifest {
+    /// Loads the manifest, a missing or broken manifest results in an empty one so every file is hashed
+    pub async fn load(path: &Path) -> Self {
+        let entries = match fs::read(path).await {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|err| {
+                warn!("Unable to parse cosmetic manifest {:?}, rehashing all cosmetics: {:?}", path, err);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new()
+        };
+
+        CosmeticManifest { path: path.to_path_buf(), entries: Mutex::new(entries) }
+    }
+
+    pub async fn store(&self) -> Result<()> {
+        let content = match self.entries.lock() {
+            Ok(entries) => serde_json::to_vec(&*entries)?,
+            Err(_) => return Ok(())
+        };
+
+        fs::write(&self.path, content).await?;
+        Ok(())
+    }
+
+    /// Checks if the file was verified with the hash and hasn't changed on disk since
+    pub fn is_unchanged(&self, file_path: &str, hash: &str, disk_path: &Path) -> bool {
+        let (size, mtime) = match file_stamp(disk_path) {
+            Some(stamp) => stamp,
+            None => return false
+        };
+
+        match self.entries.lock() {
+            Ok(entries) => entries.get(file_path).map_or(false, |entry| entry.hash == hash && entry.size == size && entry.mtime == mtime),
+            Err(_) => false
+        }
+    }
+
+    /// Remembers the file as verified with the hash
+    pub fn record(&self, file_path: &str, hash: &str, disk_path: &Path) {
+        let (size, mtime) = match file_stamp(disk_path) {
+            Some(stamp) => stamp,
+            None => return
+        };
+
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(file_path.to_string(), CosmeticEntry { hash: hash.to_string(), size, mtime });
+        }
+    }
+}
+
+/// Size and modification time in milliseconds of the file
+fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+
+    Some((metadata.len(), mtime))
+}
@@ -0,0 +1,158 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::fs;
+use tracing::*;
+
+use crate::HTTP_CLIENT;
+use crate::minecraft::version::VersionProfile;
+
+/// Base urls of a loader which is distributed like Fabric
+pub struct LoaderMeta {
+    /// Artifact of the loader, used in the id of the generated profile
+    pub loader_name: &'static str,
+    pub meta_url: &'static str,
+    pub maven_url: &'static str,
+}
+
+pub const FABRIC: LoaderMeta = LoaderMeta {
+    loader_name: "fabric-loader",
+    meta_url: "https://meta.fabricmc.net/v2",
+    maven_url: "https://maven.fabricmc.net/",
+};
+
+/// Quilt's meta has the same shape as the one of Fabric
+pub const QUILT: LoaderMeta = LoaderMeta {
+    loader_name: "quilt-loader",
+    meta_url: "https://meta.quiltmc.org/v3",
+    maven_url: "https://maven.quiltmc.org/repository/release/",
+};
+
+///
+/// JSON struct of /versions/loader/{mc_version}/{loader_version}
+///
+#[derive(Deserialize)]
+struct LoaderVersion {
+    loader: MavenArtifact,
+    intermediary: Option<MavenArtifact>,
+    /// Quilt's replacement of intermediary
+    hashed: Option<MavenArtifact>,
+    #[serde(rename = "launcherMeta")]
+    launcher_meta: LauncherMeta,
+}
+
+#[derive(Deserialize)]
+struct MavenArtifact {
+    maven: String,
+}
+
+#[derive(Deserialize)]
+struct LauncherMeta {
+    libraries: LauncherMetaLibraries,
+    #[serde(rename = "mainClass")]
+    main_class: MainClass,
+}
+
+#[derive(Deserialize)]
+struct LauncherMetaLibraries {
+    #[serde(default)]
+    common: Vec<serde_json::Value>,
+    #[serde(default)]
+    client: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MainClass {
+    Sided { client: String },
+    Single(String),
+}
+
+/// Id of the generated profile, distinct from fabric-loader-{loader_version}-{mc_version} of the profiles from the launch manifest
+fn generated_profile_id(meta: &LoaderMeta, mc_version: &str, loader_version: &str) -> String {
+    format!("norisk-{}-{}-{}", meta.loader_name, loader_version, mc_version)
+}
+
+///
+/// Generates the version profile of a Fabric (or Quilt) loader from its meta endpoint and writes it to versions/{id}/{id}.json.
+/// An already generated profile is reused, the loader version is part of its id, so it doesn't change and launching works offline.
+/// The profile inherits from the vanilla version, so it is merged and launched like any other profile.
+///
+pub async fn install_fabric(meta: &LoaderMeta, mc_version: &str, loader_version: &str, versions_folder: &Path) -> Result<VersionProfile> {
+    let id = generated_profile_id(meta, mc_version, loader_version);
+    let profile_folder = versions_folder.join(&id);
+    let profile_path = profile_folder.join(format!("{}.json", id));
+
+    if let Some(profile) = read_installed_profile(&profile_path).await {
+        debug!("{} profile is already generated", id);
+        return Ok(profile);
+    }
+
+    let content = generate_profile(meta, &id, mc_version, loader_version).await?;
+
+    fs::create_dir_all(&profile_folder).await?;
+    fs::write(&profile_path, &content).await?;
+    info!("Generated {} profile", id);
+
+    Ok(serde_json::from_slice::<VersionProfile>(&content)?)
+}
+
+/// Like [install_fabric], but a profile which is not generated yet is only generated in memory and not written to disk
+pub async fn peek_fabric(meta: &LoaderMeta, mc_version: &str, loader_version: &str, versions_folder: &Path) -> Result<VersionProfile> {
+    let id = generated_profile_id(meta, mc_version, loader_version);
+    if let Some(profile) = read_installed_profile(&versions_folder.join(&id).join(format!("{}.json", id))).await {
+        return Ok(profile);
+    }
+
+    let content = generate_profile(meta, &id, mc_version, loader_version).await?;
+    Ok(serde_json::from_slice::<VersionProfile>(&content)?)
+}
+
+/// Parses the generated profile, none if it is missing or broken
+async fn read_installed_profile(profile_path: &Path) -> Option<VersionProfile> {
+    let content = fs::read(profile_path).await.ok()?;
+    match serde_json::from_slice::<VersionProfile>(&content) {
+        Ok(profile) => Some(profile),
+        Err(e) => {
+            warn!("Generated profile {:?} is broken, generating it again: {:?}", profile_path, e);
+            None
+        }
+    }
+}
+
+/// Content of the profile of the loader, built from its meta endpoint
+async fn generate_profile(meta: &LoaderMeta, id: &str, mc_version: &str, loader_version: &str) -> Result<Vec<u8>> {
+    let url = format!("{}/versions/loader/{}/{}", meta.meta_url, mc_version, loader_version);
+    debug!("Fetching loader meta from {}", url);
+    let loader = HTTP_CLIENT.get(&url).send().await?
+        .error_for_status()?
+        .json::<LoaderVersion>().await?;
+
+    // the loader and its mappings are hosted on the maven of the loader
+    let mut libraries = vec![json!({ "name": loader.loader.maven, "url": meta.maven_url })];
+    for mappings in loader.intermediary.iter().chain(loader.hashed.iter()) {
+        libraries.push(json!({ "name": mappings.maven, "url": meta.maven_url }));
+    }
+    libraries.extend(loader.launcher_meta.libraries.common);
+    libraries.extend(loader.launcher_meta.libraries.client);
+
+    let main_class = match loader.launcher_meta.main_class {
+        MainClass::Sided { client } => client,
+        MainClass::Single(main_class) => main_class,
+    };
+
+    let profile = json!({
+        "id": id,
+        "inheritsFrom": mc_version,
+        "type": "release",
+        "mainClass": main_class,
+        "libraries": libraries,
+        "arguments": {
+            "game": [],
+            "jvm": []
+        }
+    });
+    Ok(serde_json::to_vec_pretty(&profile)?)
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use async_zip::read::mem::ZipFileReader;
+use serde::Deserialize;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use crate::error::LauncherError;
+use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
+use crate::minecraft::version::{DownloadSource, Library, VersionManifest, VersionProfile};
+use crate::utils::{download_file_untracked, get_maven_artifact_path, OS, sha1sum};
+
+const FORGE_MAVEN: &str = "https://maven.minecraftforge.net/";
+
+///
+/// JSON struct of the install_profile.json inside of the Forge installer
+///
+#[derive(Deserialize)]
+struct InstallProfile {
+    #[serde(default)]
+    data: HashMap<String, SidedData>,
+    #[serde(default)]
+    processors: Vec<Processor>,
+    #[serde(default)]
+    libraries: Vec<Library>,
+    json: Option<String>,
+    /// Only present in installers before 1.13
+    #[serde(rename = "versionInfo")]
+    version_info: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct SidedData {
+    client: String,
+}
+
+#[derive(Deserialize)]
+struct Processor {
+    jar: String,
+    #[serde(default)]
+    classpath: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    outputs: HashMap<String, String>,
+    sides: Option<Vec<String>>,
+}
+
+/// Id of the version profile written by [install_forge]
+pub fn forge_profile_id(mc_version: &str, forge_version: &str) -> String {
+    format!("{}-forge-{}", mc_version, forge_version)
+}
+
+/// Loads the version profile of an installed Forge version
+pub async fn load_installed_profile(versions_folder: &Path, mc_version: &str, forge_version: &str) -> Result<VersionProfile> {
+    let id = forge_profile_id(mc_version, forge_version);
+    let profile_path = versions_folder.join(&id).join(format!("{}.json", id));
+
+    if !profile_path.exists() {
+        return Err(LauncherError::InvalidVersionProfile(format!("forge {} is not installed", id)).into());
+    }
+
+    Ok(serde_json::from_slice::<VersionProfile>(&fs::read(profile_path).await?)?)
+}
+
+///
+/// Installs Forge by downloading its installer, placing its libraries and running the client processors which generate the patched client.
+/// The version profile is written to versions/{id}/{id}.json and inherits from the vanilla profile, so it launches through the same pipeline.
+///
+pub async fn install_forge(mc_version: &str, forge_version: &str, data: &Path, java_bin: &Path, source: &DownloadSource, progress: &impl ProgressReceiver, cancellation: &CancellationToken) -> Result<()> {
+    let id = forge_profile_id(mc_version, forge_version);
+    let versions_folder = data.join("versions");
+    let libraries_folder = data.join("libraries");
+    let profile_folder = versions_folder.join(&id);
+    let profile_path = profile_folder.join(format!("{}.json", id));
+
+    if profile_path.exists() {
+        debug!("Forge {} is already installed", id);
+        return Ok(());
+    }
+
+    progress.progress_update(ProgressUpdate::set_label(format!("Installing Forge {}...", forge_version)));
+
+    // Installer
+    let full_version = format!("{}-{}", mc_version, forge_version);
+    let installer_path = profile_folder.join(format!("forge-{}-installer.jar", full_version));
+    fs::create_dir_all(&profile_folder).await?;
+    if !installer_path.exists() {
+        info!("Downloading Forge installer {}", full_version);
+        download_file_untracked(&format!("{}net/minecraftforge/forge/{v}/forge-{v}-installer.jar", FORGE_MAVEN, v = full_version), &installer_path).await?;
+    }
+
+    let mut installer = ZipFileReader::new(fs::read(&installer_path).await?).await?;
+
+    let install_profile = serde_json::from_slice::<InstallProfile>(&read_entry(&mut installer, "install_profile.json").await?
+        .ok_or_else(|| LauncherError::InvalidVersionProfile("forge installer is missing install_profile.json".to_string()))?)?;
+    if install_profile.version_info.is_some() {
+        return Err(LauncherError::InvalidVersionProfile("forge installers before 1.13 are not supported".to_string()).into());
+    }
+
+    let version_json_name = install_profile.json.as_deref().unwrap_or("/version.json").trim_start_matches('/').to_string();
+    let version_json = read_entry(&mut installer, &version_json_name).await?
+        .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("forge installer is missing {}", version_json_name)))?;
+    let version_profile = serde_json::from_slice::<VersionProfile>(&version_json)?;
+
+    // Libraries of the installer and the version profile, libraries without url are shipped inside of the installer
+    for library in install_profile.libraries.iter().chain(version_profile.libraries.iter()) {
+        let download = library.get_library_download(source)?;
+
+        if download.url.is_empty() {
+            let library_path = libraries_folder.join(&download.path);
+            if !library_path.exists() {
+                let content = read_entry(&mut installer, &format!("maven/{}", download.path)).await?
+                    .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("forge installer is missing library {}", library.name)))?;
+                fs::create_dir_all(library_path.parent().unwrap()).await?;
+                fs::write(&library_path, content).await?;
+            }
+            continue;
+        }
+
+        // only the libraries of the installer are required now, the others are downloaded on launch
+        if install_profile.libraries.iter().any(|x| x.name == library.name) {
+            download.download(library.name.clone(), &libraries_folder, Arc::new(progress), cancellation).await?;
+        }
+    }
+
+    // Processors
+    let processors = install_profile.processors.iter()
+        .filter(|x| x.sides.as_ref().map_or(true, |sides| sides.iter().any(|side| side == "client")))
+        .collect::<Vec<_>>();
+
+    if !processors.is_empty() {
+        let minecraft_jar = download_vanilla_client(mc_version, &versions_folder, source, cancellation).await?;
+        let data_folder = profile_folder.join("data");
+
+        let mut variables = HashMap::new();
+        variables.insert("SIDE".to_string(), "client".to_string());
+        variables.insert("MINECRAFT_JAR".to_string(), path_to_string(&minecraft_jar));
+        variables.insert("MINECRAFT_VERSION".to_string(), mc_version.to_string());
+        variables.insert("ROOT".to_string(), path_to_string(data));
+        variables.insert("INSTALLER".to_string(), path_to_string(&installer_path));
+        variables.insert("LIBRARY_DIR".to_string(), path_to_string(&libraries_folder));
+
+        for (key, value) in &install_profile.data {
+            let value = if value.client.starts_with('/') {
+                // files inside of the installer are extracted to the data folder
+                let entry_name = value.client.trim_start_matches('/');
+                let path = data_folder.join(entry_name);
+                let content = read_entry(&mut installer, entry_name).await?
+                    .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("forge installer is missing {}", entry_name)))?;
+                fs::create_dir_all(path.parent().unwrap()).await?;
+                fs::write(&path, content).await?;
+                path_to_string(&path)
+            } else {
+                resolve_value(&value.client, &libraries_folder, &HashMap::new())?
+            };
+            variables.insert(key.clone(), value);
+        }
+
+        for (idx, processor) in processors.iter().enumerate() {
+            if cancellation.is_cancelled() {
+                return Err(LauncherError::Cancelled.into());
+            }
+
+            progress.progress_update(ProgressUpdate::set_label(format!("Running Forge processor {}/{}...", idx + 1, processors.len())));
+            run_processor(processor, java_bin, &libraries_folder, &variables).await?;
+        }
+    }
+
+    // the profile is written last, so an interrupted install is started over
+    fs::write(&profile_path, &version_json).await?;
+    info!("Installed Forge {}", id);
+
+    Ok(())
+}
+
+/// Downloads the vanilla client jar required by the processors
+async fn download_vanilla_client(mc_version: &str, versions_folder: &Path, source: &DownloadSource, cancellation: &CancellationToken) -> Result<PathBuf> {
+    let manifest = VersionManifest::load_cached(versions_folder, source).await?;
+    let manifest_version = manifest.versions.iter()
+        .find(|x| x.id == mc_version)
+        .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("unable to find version manifest {}", mc_version)))?;
+    let profile = VersionProfile::load_cached(&manifest_version.url, mc_version, manifest_version.sha1.as_deref(), versions_folder).await?;
+    let client_download = profile.downloads.as_ref().and_then(|x| x.client.as_ref())
+        .ok_or_else(|| LauncherError::InvalidVersionProfile("No client JAR downloads were specified.".to_string()))?;
+
+    let client_jar = versions_folder.join(mc_version).join(format!("{}.jar", mc_version));
+    if !client_jar.exists() || sha1sum(&client_jar)? != client_download.sha1 {
+        info!("Downloading vanilla client {}", mc_version);
+        client_download.download(&client_jar, cancellation).await?;
+    }
+
+    Ok(client_jar)
+}
+
+async fn run_processor(processor: &Processor, java_bin: &Path, libraries_folder: &Path, variables: &HashMap<String, String>) -> Result<()> {
+    let outputs = processor.outputs.iter()
+        .map(|(file, sha1)| Ok((resolve_value(file, libraries_folder, variables)?, resolve_value(sha1, libraries_folder, variables)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Skip processors whose outputs already exist
+    if !outputs.is_empty() && outputs.iter().all(|(file, sha1)| sha1sum(&PathBuf::from(file)).map_or(false, |x| &x == sha1)) {
+        debug!("Skipping processor {}, outputs are up to date", processor.jar);
+        return Ok(());
+    }
+
+    let jar = libraries_folder.join(get_maven_artifact_path(&processor.jar)?);
+    let main_class = read_main_class(&jar).await?;
+
+    let mut class_path = vec![path_to_string(&jar)];
+    for library in &processor.classpath {
+        class_path.push(path_to_string(&libraries_folder.join(get_maven_artifact_path(library)?)));
+    }
+
+    let args = processor.args.iter()
+        .map(|arg| resolve_value(arg, libraries_folder, variables))
+        .collect::<Result<Vec<_>>>()?;
+
+    info!("Running processor {} ({})", processor.jar, main_class);
+    let output = Command::new(java_bin)
+        .arg("-cp")
+        .arg(class_path.join(OS.get_path_separator()?))
+        .arg(&main_class)
+        .args(&args)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        bail!("forge processor {} failed: {}", processor.jar, String::from_utf8_lossy(&output.stderr));
+    }
+
+    for (file, sha1) in &outputs {
+        let actual = sha1sum(&PathBuf::from(file))?;
+        if &actual != sha1 {
+            error!("Forge processor {} produced an unexpected {}", processor.jar, file);
+            return Err(LauncherError::ChecksumMismatch {
+                file: file.clone(),
+                expected: sha1.clone(),
+                got: actual,
+            }.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves {VARIABLE}, [maven artifact] and 'literal' values of the install profile
+fn resolve_value(value: &str, libraries_folder: &Path, variables: &HashMap<String, String>) -> Result<String> {
+    if let Some(name) = value.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+        return variables.get(name)
+            .cloned()
+            .ok_or_else(|| LauncherError::UnknownTemplateParameter(name.to_string()).into());
+    }
+    if let Some(artifact) = value.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+        return Ok(path_to_string(&libraries_folder.join(get_maven_artifact_path(&artifact.to_string())?)));
+    }
+    if let Some(literal) = value.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')) {
+        return Ok(literal.to_string());
+    }
+
+    Ok(value.to_string())
+}
+
+/// Reads the Main-Class attribute of the jar manifest
+async fn read_main_class(jar: &Path) -> Result<String> {
+    let mut reader = ZipFileReader::new(fs::read(jar).await?).await?;
+    let manifest = read_entry(&mut reader, "META-INF/MANIFEST.MF").await?
+        .ok_or_else(|| anyhow::anyhow!("{:?} has no manifest", jar))?;
+
+    String::from_utf8_lossy(&manifest)
+        .lines()
+        .find_map(|line| line.strip_prefix("Main-Class:").map(|x| x.trim().to_string()))
+        .ok_or_else(|| anyhow::anyhow!("{:?} has no main class", jar))
+}
+
+/// Reads a file of the zip archive into memory
+async fn read_entry(reader: &mut ZipFileReader, name: &str) -> Result<Option<Vec<u8>>> {
+    let index = match reader.file().entries().iter().position(|x| x.entry().filename() == name) {
+        Some(index) => index,
+        None => return Ok(None)
+    };
+
+    let mut entry_reader = reader.entry(index).await?;
+    let mut content = Vec::new();
+    entry_reader.read_to_end(&mut content).await?;
+
+    Ok(Some(content))
+}
+
+fn path_to_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
@@ -0,0 +1,113 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use fs2::FileExt;
+use tracing::*;
+
+use crate::error::LauncherError;
+
+/// Lock file in the data folder
+const INSTALL_LOCK_FILE: &str = "install.lock";
+
+/// Age after which a lock is considered stale, only used on file systems without OS-level locks
+const INSTALL_LOCK_STALE_SECS: i64 = 30 * 60;
+
+/// Interval in which the lock time is renewed while the lock is held without OS-level locks, well below the stale age
+const INSTALL_LOCK_REFRESH_SECS: u64 = 5 * 60;
+
+///
+/// Advisory lock of the data folder, held while installing, so two launcher instances sharing the data folder
+/// don't write the same files. The OS releases the lock if the launcher crashes. On file systems without
+/// OS-level locks (e.g. some network shares) the time written to the lock file decides whether it is stale,
+/// it is renewed while the lock is held, so long installs aren't taken over. The lock is released when dropped.
+///
+pub struct InstallLock {
+    file: File,
+    path: PathBuf,
+    os_locked: bool,
+    /// Renews the lock time until the sender is dropped, only without OS-level locks
+    refresher: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl InstallLock {
+    /// Acquires the lock or fails with [LauncherError::InstallLocked] if another instance holds it
+    pub fn acquire(data: &Path) -> Result<Self> {
+        std::fs::create_dir_all(data)?;
+        let path = data.join(INSTALL_LOCK_FILE);
+        let mut file = OpenOptions::new().read(true).write(true).create(true).open(&path)?;
+
+        let os_locked = match file.try_lock_exclusive() {
+            Ok(()) => true,
+            // the kind of the contended error is too generic, it would match errors of unsupported file systems
+            Err(err) if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() => return Err(LauncherError::InstallLocked.into()),
+            Err(err) => {
+                warn!("File system doesn't support locking {:?}, falling back to the lock time: {:?}", path, err);
+                if let Some(locked_at) = read_locked_at(&mut file) {
+                    let age = Utc::now().timestamp() - locked_at;
+                    if age < INSTALL_LOCK_STALE_SECS {
+                        return Err(LauncherError::InstallLocked.into());
+                    }
+                    warn!("Taking over stale install lock {:?}, it is {} seconds old", path, age);
+                }
+                false
+            }
+        };
+
+        write_locked_at(&mut file)?;
+        debug!("Acquired install lock {:?}", path);
+
+        let refresher = if os_locked { None } else { Some(spawn_refresher(file.try_clone()?, path.clone())) };
+        Ok(InstallLock { file, path, os_locked, refresher })
+    }
+}
+
+/// Renews the lock time until the returned sender is dropped
+fn spawn_refresher(mut file: File, path: PathBuf) -> (Sender<()>, JoinHandle<()>) {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(Duration::from_secs(INSTALL_LOCK_REFRESH_SECS)) {
+            if let Err(err) = write_locked_at(&mut file) {
+                warn!("Unable to renew install lock {:?}: {:?}", path, err);
+            }
+        }
+    });
+    (stop, handle)
+}
+
+impl Drop for InstallLock {
+    fn drop(&mut self) {
+        // stopped first, so it doesn't write the lock time again after the lock was released
+        if let Some((stop, handle)) = self.refresher.take() {
+            drop(stop);
+            let _ = handle.join();
+        }
+        // an empty lock file is free for the fallback without OS-level locks
+        let _ = self.file.set_len(0);
+        if self.os_locked {
+            let _ = self.file.unlock();
+        }
+        debug!("Released install lock {:?}", self.path);
+    }
+}
+
+/// Writes the process id and the current unix time to the lock file
+fn write_locked_at(file: &mut File) -> Result<()> {
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    write!(file, "{} {}", std::process::id(), Utc::now().timestamp())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Unix time written by the holder of the lock, none if the lock file is empty or broken
+fn read_locked_at(file: &mut File) -> Option<i64> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    content.split_whitespace().nth(1)?.parse().ok()
+}
@@ -0,0 +1,271 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::*;
+use walkdir::WalkDir;
+
+use crate::minecraft::verify::InstallationDirs;
+use crate::minecraft::version::{AssetIndex, AssetIndexLocation, VersionProfile};
+
+/// A version which is installed in the versions folder
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InstalledVersion {
+    pub id: String,
+    #[serde(rename = "installedAt")]
+    pub installed_at: DateTime<Utc>,
+    /// fabric, quilt or forge, none for vanilla
+    pub modloader: Option<String>,
+    /// Size of the version folder, shared libraries and assets are not included
+    #[serde(rename = "sizeOnDisk")]
+    pub size_on_disk: u64,
+    #[serde(rename = "lastPlayed")]
+    pub last_played: Option<DateTime<Utc>>,
+    /// Paths of the libraries relative to the libraries folder, none if they weren't recorded by an older launcher
+    #[serde(default)]
+    pub libraries: Option<Vec<String>>,
+    /// Vanilla version the profile inherits from, its folder only holds the cached profile
+    #[serde(rename = "inheritsFrom", default)]
+    pub inherits_from: Option<String>,
+}
+
+/// Result of [uninstall_version]
+#[derive(Serialize, Debug)]
+pub struct UninstallReport {
+    #[serde(rename = "freedBytes")]
+    pub freed_bytes: u64,
+    #[serde(rename = "removedFiles")]
+    pub removed_files: Vec<PathBuf>,
+    /// True if no library was removed, because it is unknown which libraries the other versions use
+    #[serde(rename = "librariesKept")]
+    pub libraries_kept: bool,
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+}
+
+///
+/// Registry of the versions which are installed in the data folder, unlike the version manifest which lists every available version.
+///
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct InstalledVersions {
+    pub versions: HashMap<String, InstalledVersion>,
+}
+
+impl InstalledVersions {
+    fn path(data: &Path) -> PathBuf {
+        data.join("installed_versions.json")
+    }
+
+    /// Loads the registry, a missing or broken registry results in an empty one
+    pub async fn load(data: &Path) -> Self {
+        let path = Self::path(data);
+        match fs::read(&path).await {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|err| {
+                warn!("Unable to parse installed versions {:?}: {:?}", path, err);
+                InstalledVersions::default()
+            }),
+            Err(_) => InstalledVersions::default()
+        }
+    }
+
+    pub async fn store(&self, data: &Path) -> Result<()> {
+        fs::write(Self::path(data), serde_json::to_vec_pretty(self)?).await?;
+        Ok(())
+    }
+
+    ///
+    /// Records the version as installed and played now. The install time of a version which is already installed is kept.
+    /// The libraries of the inherited version are part of the libraries of the merged profile.
+    ///
+    pub async fn record_launch(data: &Path, id: &str, inherits_from: Option<String>, modloader: Option<String>, libraries: Vec<String>) -> Result<()> {
+        let mut installed_versions = Self::load(data).await;
+        let size_on_disk = version_folder_size(&data.join("versions").join(id));
+        let now = Utc::now();
+
+        let entry = installed_versions.versions.entry(id.to_string()).or_insert_with(|| InstalledVersion {
+            id: id.to_string(),
+            installed_at: now,
+            modloader: None,
+            size_on_disk: 0,
+            last_played: None,
+            libraries: None,
+            inherits_from: None,
+        });
+        entry.inherits_from = inherits_from;
+        entry.modloader = modloader;
+        entry.libraries = Some(libraries);
+        entry.size_on_disk = size_on_disk;
+        entry.last_played = Some(now);
+
+        installed_versions.store(data).await
+    }
+
+    /// Bytes used by the folders of all installed versions
+    pub fn total_disk_usage(&self) -> u64 {
+        self.versions.values().map(|version| version.size_on_disk).sum()
+    }
+}
+
+///
+/// Removes the version folder, including the client jar, and the libraries no other installed version uses.
+/// Assets are shared by most versions and left to the asset pruning. With dry run nothing is deleted, the report shows what would be.
+///
+pub async fn uninstall_version(id: &str, data: &Path, dry_run: bool) -> Result<UninstallReport> {
+    let dirs = InstallationDirs::new(data);
+    let mut installed_versions = InstalledVersions::load(data).await;
+    let mut removed_files = Vec::new();
+    let mut freed_bytes = 0;
+
+    let version_folder = dirs.versions_folder.join(id);
+    if version_folder.exists() {
+        freed_bytes += version_folder_size(&version_folder);
+        removed_files.push(version_folder.clone());
+    }
+
+    // the installed asset index would let a reinstall skip the assets which aren't checked anymore
+    let installed_index = AssetIndex::installed_path(&dirs.assets_folder, id);
+    if installed_index.exists() {
+        freed_bytes += fs::metadata(&installed_index).await?.len();
+        removed_files.push(installed_index);
+    }
+
+    let libraries = unique_libraries(id, &installed_versions, &dirs.versions_folder).await?;
+    let libraries_kept = libraries.is_none();
+    for library in libraries.unwrap_or_default() {
+        let path = dirs.libraries_folder.join(library);
+        if let Ok(metadata) = fs::metadata(&path).await {
+            freed_bytes += metadata.len();
+            removed_files.push(path);
+        }
+    }
+
+    if !dry_run {
+        for path in &removed_files {
+            if path.is_dir() {
+                fs::remove_dir_all(path).await?;
+            } else {
+                fs::remove_file(path).await?;
+            }
+        }
+        installed_versions.versions.remove(id);
+        installed_versions.store(data).await?;
+        info!("Uninstalled {}, freed {} bytes", id, freed_bytes);
+    }
+
+    Ok(UninstallReport { freed_bytes, removed_files, libraries_kept, dry_run })
+}
+
+///
+/// Asset indexes of the installed versions, which the asset pruning has to keep. The index a version was installed with is preferred,
+/// otherwise the index named by its cached profile is loaded. Fails if the index of a version is unknown, as its assets would be removed.
+///
+pub async fn active_asset_indexes(data: &Path) -> Result<Vec<AssetIndex>> {
+    let dirs = InstallationDirs::new(data);
+    let installed_versions = InstalledVersions::load(data).await;
+    let mut indexes = Vec::new();
+    let mut loaded_ids = HashSet::new();
+
+    for version in installed_versions.versions.values() {
+        if let Some(index) = AssetIndex::load_installed(&dirs.assets_folder, &version.id).await {
+            indexes.push(index);
+            continue;
+        }
+
+        let location = cached_asset_index_location(version, &dirs.versions_folder).await
+            .ok_or_else(|| anyhow!("asset index of the installed version {} is unknown", version.id))?;
+        if !loaded_ids.insert(location.id.clone()) {
+            continue;
+        }
+
+        let path = dirs.assets_folder.join("indexes").join(format!("{}.json", location.id));
+        let content = fs::read(&path).await
+            .map_err(|e| anyhow!("asset index {} of the installed version {} is missing: {:?}", location.id, version.id, e))?;
+        indexes.push(serde_json::from_slice::<AssetIndex>(&content)
+            .map_err(|e| anyhow!("unable to parse asset index {:?}: {:?}", path, e))?);
+    }
+
+    Ok(indexes)
+}
+
+/// Asset index named by the cached profile of the version or of the version it inherits from
+async fn cached_asset_index_location(version: &InstalledVersion, versions_folder: &Path) -> Option<AssetIndexLocation> {
+    for id in std::iter::once(&version.id).chain(version.inherits_from.iter()) {
+        let content = match fs::read(versions_folder.join(id).join(format!("{}.json", id))).await {
+            Ok(content) => content,
+            Err(_) => continue
+        };
+        if let Ok(VersionProfile { asset_index_location: Some(location), .. }) = serde_json::from_slice::<VersionProfile>(&content) {
+            return Some(location);
+        }
+    }
+    None
+}
+
+///
+/// Libraries of the version which no other version references. None if the references are unknown,
+/// because a version folder isn't in the registry or was recorded without its libraries.
+/// Folders of inherited versions and folders which only hold cached profiles don't reference any library.
+///
+async fn unique_libraries(id: &str, installed_versions: &InstalledVersions, versions_folder: &Path) -> Result<Option<Vec<String>>> {
+    let libraries = match installed_versions.versions.get(id).and_then(|version| version.libraries.as_ref()) {
+        Some(libraries) => libraries,
+        None => return Ok(None)
+    };
+
+    if versions_folder.exists() {
+        let mut entries = fs::read_dir(versions_folder).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let other_id = entry.file_name().to_string_lossy().to_string();
+            let known = installed_versions.versions.contains_key(&other_id)
+                || installed_versions.versions.values().any(|version| version.inherits_from.as_deref() == Some(other_id.as_str()))
+                || holds_only_profiles(&entry.path()).await?;
+            if other_id != id && entry.path().is_dir() && !known {
+                warn!("Version {} isn't in the registry, keeping all libraries of {}", other_id, id);
+                return Ok(None);
+            }
+        }
+    }
+
+    let mut referenced = HashSet::new();
+    for (other_id, other) in &installed_versions.versions {
+        if other_id == id {
+            continue;
+        }
+        match &other.libraries {
+            Some(other_libraries) => referenced.extend(other_libraries.iter()),
+            None => {
+                warn!("Libraries of version {} are unknown, keeping all libraries of {}", other_id, id);
+                return Ok(None);
+            }
+        }
+    }
+
+    Ok(Some(libraries.iter().filter(|library| !referenced.contains(library)).cloned().collect()))
+}
+
+/// True if the version folder only holds json files, the cached profile and its metadata
+async fn holds_only_profiles(folder: &Path) -> Result<bool> {
+    if !folder.is_dir() {
+        return Ok(false);
+    }
+
+    let mut entries = fs::read_dir(folder).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_file() || entry.path().extension().map_or(true, |x| x != "json") {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn version_folder_size(folder: &Path) -> u64 {
+    WalkDir::new(folder).into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
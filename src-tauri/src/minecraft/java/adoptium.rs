@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use tokio::fs;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use crate::HTTP_CLIENT;
+use crate::utils::{ARCHITECTURE, Checksum, download_file_checksummed, OperatingSystem, OS, tar_gz_extract, zip_extract};
+
+use super::find_java_binary;
+
+const ADOPTIUM_API: &str = "https://api.adoptium.net/v3";
+
+///
+/// JSON struct of /assets/latest/{feature_version}/hotspot
+///
+#[derive(Deserialize)]
+struct AdoptiumAsset {
+    binary: AdoptiumBinary,
+    release_name: String,
+}
+
+#[derive(Deserialize)]
+struct AdoptiumBinary {
+    package: AdoptiumPackage,
+}
+
+#[derive(Deserialize)]
+struct AdoptiumPackage {
+    name: String,
+    link: String,
+    checksum: String,
+    size: u64,
+}
+
+/// Looks up the latest Temurin build of the major version for the current OS and architecture
+async fn latest_package(major: u32, image_type: &str) -> Result<Option<(String, AdoptiumPackage)>> {
+    let url = format!(
+        "{}/assets/latest/{}/hotspot?architecture={}&image_type={}&os={}&vendor=eclipse",
+        ADOPTIUM_API, major, ARCHITECTURE.get_simple_name()?, image_type, OS.get_adoptium_name()?
+    );
+    debug!("Requesting Temurin builds from {}", url);
+
+    let assets = HTTP_CLIENT.get(&url).send().await?
+        .error_for_status()?
+        .json::<Vec<AdoptiumAsset>>().await?;
+
+    Ok(assets.into_iter().next().map(|asset| (asset.release_name, asset.binary.package)))
+}
+
+///
+/// Downloads the latest Temurin runtime of the major version to runtimes/{major} and returns its java binary.
+/// A JDK is used if Temurin doesn't ship a JRE for the platform (e.g. Java 8 on Apple silicon).
+///
+pub async fn adoptium_download<F>(runtimes_folder: &Path, major: u32, cancellation: &CancellationToken, on_progress: F) -> Result<PathBuf> where F : Fn(u64, u64) {
+    let (release_name, package) = match latest_package(major, "jre").await? {
+        Some(package) => package,
+        None => match latest_package(major, "jdk").await? {
+            Some(package) => package,
+            None => bail!("Temurin has no Java {} for {} {}", major, OS, *ARCHITECTURE)
+        }
+    };
+    info!("Downloading Temurin {} ({})", release_name, package.name);
+
+    let runtime_path = runtimes_folder.join(format!("{}", major));
+    if runtime_path.exists() {
+        fs::remove_dir_all(&runtime_path).await?;
+    }
+    fs::create_dir_all(&runtime_path).await?;
+
+    // the archive is kept outside of the runtime folder, the java binary is searched in its first entry
+    let archive_path = runtimes_folder.join(&package.name);
+    download_file_checksummed(&package.link, &archive_path, &Checksum::Sha256(package.checksum), Some(package.size), cancellation, on_progress).await?;
+
+    let archive = fs::File::open(&archive_path).await?;
+    match OS {
+        OperatingSystem::WINDOWS => zip_extract(archive, runtime_path.as_path()).await?,
+        OperatingSystem::LINUX | OperatingSystem::OSX => tar_gz_extract(archive, runtime_path.as_path()).await?,
+        _ => bail!("Unsupported OS")
+    }
+    fs::remove_file(&archive_path).await?;
+
+    find_java_binary(runtimes_folder, major).await
+}
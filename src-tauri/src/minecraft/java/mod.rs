@@ -1,7 +1,13 @@
 pub(crate) mod jre_downloader;
+mod adoptium;
 mod runtime;
+mod system;
+mod validate;
 
 pub use {
+    adoptium::*,
     jre_downloader::*,
     runtime::*,
+    system::*,
+    validate::*,
 };
\ No newline at end of file
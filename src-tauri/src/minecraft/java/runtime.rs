@@ -2,9 +2,16 @@ use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::sync::oneshot::Receiver;
 use tokio::process::{Child, Command};
-use anyhow::{Result, bail};
-use tokio::io::AsyncReadExt;
-use tracing::debug;
+use std::collections::VecDeque;
+use anyhow::Result;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tracing::{debug, warn};
+
+use crate::error::LauncherError;
+
+/// Lines of output which are kept to be reported if the game crashes
+const CRASH_OUTPUT_LINES: usize = 50;
 pub struct JavaRuntime(PathBuf);
 
 impl JavaRuntime {
@@ -26,22 +33,44 @@ impl JavaRuntime {
         Ok(child)
     }
 
-    pub async fn handle_io<D: Send + Sync>(&self, running_task: &mut Child, on_stdout: fn(&D, &[u8]) -> Result<()>, on_stderr: fn(&D, &[u8]) -> Result<()>, terminator: Receiver<()>, data: &D) -> Result<()> {
+    /// Forwards the output of the process until it exits, the output is also written to the log file if given
+    pub async fn handle_io<D: Send + Sync>(&self, running_task: &mut Child, on_stdout: fn(&D, &[u8]) -> Result<()>, on_stderr: fn(&D, &[u8]) -> Result<()>, terminator: Receiver<()>, log_file: Option<File>, data: &D) -> Result<()> {
+        let mut log_writer = log_file.map(BufWriter::new);
+        let result = self.forward_io(running_task, on_stdout, on_stderr, terminator, &mut log_writer, data).await;
+
+        // flushed no matter how the process ended, so the end of the output isn't lost
+        if let Some(log_writer) = &mut log_writer {
+            if let Err(e) = log_writer.flush().await {
+                warn!("Unable to flush the game log: {:?}", e);
+            }
+        }
+
+        result
+    }
+
+    async fn forward_io<D: Send + Sync>(&self, running_task: &mut Child, on_stdout: fn(&D, &[u8]) -> Result<()>, on_stderr: fn(&D, &[u8]) -> Result<()>, terminator: Receiver<()>, log_writer: &mut Option<BufWriter<File>>, data: &D) -> Result<()> {
         let mut stdout = running_task.stdout.take().unwrap();
         let mut stderr = running_task.stderr.take().unwrap();
     
         let mut stdout_buf = vec![0; 1024];
         let mut stderr_buf = vec![0; 1024];
+        let mut output_tail = OutputTail::default();
     
         tokio::pin!(terminator);
     
         loop {
             tokio::select! {
                 read_len = stdout.read(&mut stdout_buf) => {
-                    let _ = (on_stdout)(&data, &stdout_buf[..read_len?]);
+                    let output = &stdout_buf[..read_len?];
+                    write_log(log_writer, output).await;
+                    output_tail.push(output);
+                    let _ = (on_stdout)(&data, output);
                 },
                 read_len = stderr.read(&mut stderr_buf) => {
-                    let _ = (on_stderr)(&data, &stderr_buf[..read_len?]);
+                    let output = &stderr_buf[..read_len?];
+                    write_log(log_writer, output).await;
+                    output_tail.push(output);
+                    let _ = (on_stderr)(&data, output);
                 },
                 _ = &mut terminator => {
                     running_task.kill().await?;
@@ -52,7 +81,7 @@ impl JavaRuntime {
 
                     debug!("Process exited with code: {}", code);
                     if code != 0 && code != -1073740791 { // -1073740791 = happens when the process is killed forcefully, we don't want to bail in this case
-                        bail!("Process exited with non-zero code: {}", code);
+                        return Err(LauncherError::GameCrashed { code, last_output: output_tail.into_lines() }.into());
                     }
                     break;
                 },
@@ -61,4 +90,53 @@ impl JavaRuntime {
         Ok(())
     }
 
+}
+
+/// Last lines of the output of the process, stdout and stderr are interleaved as they arrive
+#[derive(Default)]
+struct OutputTail {
+    lines: VecDeque<String>,
+    /// Output after the last line break
+    partial: String,
+}
+
+impl OutputTail {
+    fn push(&mut self, output: &[u8]) {
+        self.partial.push_str(&String::from_utf8_lossy(output));
+
+        while let Some(line_end) = self.partial.find('\n') {
+            let line = self.partial[..line_end].trim_end_matches('\r').to_string();
+            self.partial.drain(..=line_end);
+            self.push_line(line);
+        }
+    }
+
+    fn push_line(&mut self, line: String) {
+        if self.lines.len() == CRASH_OUTPUT_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    fn into_lines(mut self) -> Vec<String> {
+        if !self.partial.is_empty() {
+            let partial = std::mem::take(&mut self.partial);
+            self.push_line(partial);
+        }
+        self.lines.into()
+    }
+}
+
+/// Writes the output to the log, a failing log is closed so the game keeps running without it
+async fn write_log(log_writer: &mut Option<BufWriter<File>>, output: &[u8]) {
+    if output.is_empty() {
+        return;
+    }
+
+    if let Some(writer) = log_writer {
+        if let Err(e) = writer.write_all(output).await {
+            warn!("Unable to write the game log, stopping the capture: {:?}", e);
+            *log_writer = None;
+        }
+    }
 }
\ No newline at end of file
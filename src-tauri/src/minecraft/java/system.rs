@@ -0,0 +1,53 @@
+use std::env;
+use std::path::PathBuf;
+
+use tracing::*;
+
+use crate::minecraft::java::validate_java;
+use crate::utils::{OperatingSystem, OS};
+
+/// Name of the java binary on the current OS
+fn java_binary_name() -> &'static str {
+    match OS {
+        OperatingSystem::WINDOWS => "javaw.exe",
+        _ => "java"
+    }
+}
+
+/// Java binaries of JAVA_HOME and the PATH, in this order
+fn system_java_candidates() -> Vec<PathBuf> {
+    let binary_name = java_binary_name();
+    let mut candidates = Vec::new();
+
+    if let Some(java_home) = env::var_os("JAVA_HOME") {
+        candidates.push(PathBuf::from(java_home).join("bin").join(binary_name));
+    }
+    if let Some(path) = env::var_os("PATH") {
+        candidates.extend(env::split_paths(&path).map(|dir| dir.join(binary_name)));
+    }
+
+    candidates.dedup();
+    candidates
+}
+
+///
+/// Searches JAVA_HOME and the PATH for a java installation of at least the given major version.
+///
+pub async fn find_system_java(min_major: u32) -> Option<PathBuf> {
+    for candidate in system_java_candidates() {
+        if !candidate.is_file() {
+            continue;
+        }
+
+        match validate_java(&candidate).await {
+            Ok(java_info) if java_info.major >= min_major => {
+                info!("Found system java {:?}: {:?}", candidate, java_info);
+                return Some(candidate);
+            }
+            Ok(java_info) => debug!("System java {:?} is Java {}, but Java {} is required", candidate, java_info.major, min_major),
+            Err(e) => debug!("Skipping system java {:?}: {:?}", candidate, e)
+        }
+    }
+
+    None
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use tokio::process::Command;
+use tracing::debug;
+
+/// Details of a java installation
+#[derive(Debug, Clone, Serialize)]
+pub struct JavaInfo {
+    pub major: u32,
+    pub vendor: String,
+    pub arch: String,
+}
+
+///
+/// Runs the java binary to determine its major version, vendor and architecture.
+/// Fails if the binary is missing, not executable or doesn't behave like java.
+///
+pub async fn validate_java(path: &Path) -> Result<JavaInfo> {
+    if !path.is_file() {
+        bail!("java binary {:?} does not exist", path);
+    }
+
+    // the properties are printed to stderr before the version
+    let output = match Command::new(path).arg("-XshowSettings:properties").arg("-version").output().await {
+        Ok(output) => output,
+        Err(e) => bail!("java binary {:?} is not executable: {}", path, e)
+    };
+    if !output.status.success() {
+        bail!("java binary {:?} exited with {}", path, output.status);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let properties = stderr.lines()
+        .filter_map(|line| line.trim().split_once(" = "))
+        .collect::<HashMap<_, _>>();
+    debug!("Java properties of {:?}: {:?}", path, properties);
+
+    let version = match properties.get("java.specification.version").or(properties.get("java.version")) {
+        Some(version) => version,
+        None => bail!("{:?} does not look like a java binary", path)
+    };
+
+    Ok(JavaInfo {
+        major: parse_major_version(version)?,
+        vendor: properties.get("java.vendor").unwrap_or(&"unknown").to_string(),
+        arch: properties.get("os.arch").unwrap_or(&"unknown").to_string(),
+    })
+}
+
+/// Java 8 and older report their version as 1.x
+fn parse_major_version(version: &str) -> Result<u32> {
+    let mut parts = version.split(|c: char| c == '.' || c == '_' || c == '-' || c == '+');
+    let major = match parts.next() {
+        Some("1") => parts.next(),
+        major => major
+    };
+
+    match major.and_then(|major| major.parse().ok()) {
+        Some(major) => Ok(major),
+        None => bail!("unable to parse java version {}", version)
+    }
+}
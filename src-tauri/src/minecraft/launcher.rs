@@ -1,13 +1,15 @@
 use std::{path::{Path, PathBuf}, collections::HashMap};
 use std::collections::HashSet;
-use std::fmt::Write;
 
 use std::process::exit;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use anyhow::Result;
-use futures::stream::{self, StreamExt};
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
 
 use tracing::*;
 use path_absolutize::*;
@@ -16,12 +18,21 @@ use walkdir::WalkDir;
 
 use crate::{LAUNCHER_VERSION, utils::{OS, OS_VERSION}, app::api::ApiEndpoints, minecraft::version::AssetObject};
 use crate::app::api::NoRiskLaunchManifest;
+use crate::app::api::LoginData;
+use crate::app::app_data::{JvmArgsPolicy, LauncherOptions};
+use crate::app::norisk_api::NoriskApi;
+use crate::minecraft::auth::AZURE_CLIENT_ID;
 use crate::error::LauncherError;
-use crate::minecraft::progress::{get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps};
+use crate::minecraft::cosmetics::CosmeticManifest;
+use crate::minecraft::install_lock::InstallLock;
+use crate::minecraft::installed::InstalledVersions;
+use crate::minecraft::progress::{get_max, InstallPhase, MultiProgressReceiver, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps, track_transfer, WeightedProgress};
 use crate::minecraft::rule_interpreter;
-use crate::minecraft::java::{find_java_binary, JavaRuntime, jre_downloader};
-use crate::minecraft::version::LibraryDownloadInfo;
-use crate::utils::{download_file, sha1sum, zip_extract};
+use crate::minecraft::servers::seed_servers;
+use crate::minecraft::java::{adoptium_download, find_java_binary, find_system_java, JavaRuntime, jre_downloader, validate_java};
+use crate::minecraft::verify::{estimate_missing_bytes, InstallationDirs};
+use crate::minecraft::version::{AssetIndex, AssetIndexLocation, DownloadSource, Library, LoggingClient};
+use crate::utils::{ARCHITECTURE, check_disk_space, download_file_verified, DOWNLOAD_STATS, sha1sum, zip_extract_filtered};
 
 use super::version::VersionProfile;
 
@@ -29,67 +40,83 @@ pub struct LauncherData<D: Send + Sync> {
     pub(crate) on_stdout: fn(&D, &[u8]) -> Result<()>,
     pub(crate) on_stderr: fn(&D, &[u8]) -> Result<()>,
     pub(crate) on_progress: fn(&D, ProgressUpdate) -> Result<()>,
+    pub(crate) progress_receivers: MultiProgressReceiver,
+    /// Bytes downloaded by this launch, see [ProgressReceiver::add_transferred_bytes]
+    pub(crate) weighted_progress: WeightedProgress,
     pub(crate) data: Box<D>,
     pub(crate) terminator: tokio::sync::oneshot::Receiver<()>,
 }
 
 impl<D: Send + Sync> ProgressReceiver for LauncherData<D> {
     fn progress_update(&self, progress_update: ProgressUpdate) {
+        if self.weighted_progress.is_active() {
+            // while downloading, the progress by files is replaced by the progress weighted by bytes
+            let is_progress = matches!(progress_update, ProgressUpdate::SetProgress(_));
+            if is_progress || matches!(progress_update, ProgressUpdate::SetBytes { .. }) {
+                if let Some(weighted) = self.weighted_progress.throttled_update() {
+                    self.forward_progress(weighted);
+                }
+            }
+            if is_progress {
+                return;
+            }
+        }
+
+        self.forward_progress(progress_update);
+    }
+
+    fn add_transferred_bytes(&self, bytes: u64) {
+        self.weighted_progress.add_bytes(bytes);
+    }
+}
+
+impl<D: Send + Sync> LauncherData<D> {
+    fn forward_progress(&self, progress_update: ProgressUpdate) {
+        // weighted progress held back by the throttle is emitted with the next update, so the bar catches up with the downloads
+        if !matches!(progress_update, ProgressUpdate::SetProgress(_)) {
+            if let Some(weighted) = self.weighted_progress.pending_update() {
+                self.progress_receivers.progress_update(weighted.clone());
+                let _ = (self.on_progress)(&self.data, weighted);
+            }
+        }
+
+        self.progress_receivers.progress_update(progress_update.clone());
         let _ = (self.on_progress)(&self.data, progress_update);
     }
 }
 
-pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: NoRiskLaunchManifest, version_profile: VersionProfile, launching_parameter: LaunchingParameter, launcher_data: LauncherData<D>, window: Arc<Mutex<tauri::Window>>) -> Result<()> {
+pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: NoRiskLaunchManifest, version_profile: VersionProfile, launching_parameter: LaunchingParameter, launcher_data: LauncherData<D>, window: Arc<Mutex<tauri::Window>>, install_lock: InstallLock) -> Result<()> {
     let launcher_data_arc = Arc::new(launcher_data);
+    DOWNLOAD_STATS.reset();
 
-    let features: HashSet<String> = HashSet::new();
+    let command_builder = LaunchCommandBuilder::new(&version_profile, &launching_parameter, &manifest.build.branch);
+    let features = &command_builder.features;
 
     info!("Determined OS to be {} {}", OS, OS_VERSION.clone());
 
-    // JRE download
-    let runtimes_folder = data.join("runtimes");
-    if !runtimes_folder.exists() {
-        fs::create_dir(&runtimes_folder).await?;
+    if launching_parameter.quick_play.is_some() && command_builder.quick_play.is_none() {
+        launcher_data_arc.progress_update(ProgressUpdate::warning(format!("Quick play is not supported by {}", version_profile.id)));
     }
 
-    let java_bin = match &launching_parameter.custom_java_path {
-        Some(path) => PathBuf::from(path),
-        None => {
-            info!("Checking for JRE...");
-            launcher_data_arc.progress_update(ProgressUpdate::set_label("Checking for JRE..."));
-
-            match find_java_binary(&runtimes_folder, manifest.build.jre_version).await {
-                Ok(jre) => jre,
-                Err(e) => {
-                    error!("Failed to find JRE: {}", e);
+    // fail early instead of running out of space in the middle of the downloads
+    let required_bytes = estimate_missing_bytes(&version_profile, &InstallationDirs::new(data), &launching_parameter.download_source).await?;
+    check_disk_space(data, required_bytes)?;
+    launcher_data_arc.weighted_progress.start(required_bytes);
 
-                    info!("Download JRE...");
-                    launcher_data_arc.progress_update(ProgressUpdate::set_label("Download JRE..."));
-                    jre_downloader::jre_download(&runtimes_folder, manifest.build.jre_version, |a, b| {
-                        launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadJRE, get_progress(0, a, b), get_max(1)));
-                    }).await?
-                }
-            }
-        }
-    };
+    // JRE download, the client may require a newer java than the vanilla version
+    launcher_data_arc.progress_update(ProgressUpdate::set_phase(InstallPhase::Java));
+    let java_bin = resolve_java_for_profile(data, &version_profile, manifest.build.jre_version, &launching_parameter.custom_java_path, &*launcher_data_arc, &launching_parameter.cancellation).await?;
     debug!("Java binary: {}", java_bin.to_str().unwrap());
 
-    // Launch class path for JRE
-    let mut class_path = String::new();
-
     // Client
-    let versions_folder = data.join("versions");
+    launcher_data_arc.progress_update(ProgressUpdate::set_phase(InstallPhase::ClientJar));
+    let client_jar = command_builder.client_jar();
+    let client_folder = client_jar.parent().unwrap_or(data).to_path_buf();
 
     // Check if json has client download (or doesn't require one)
     if let Some(client_download) = version_profile.downloads.as_ref().and_then(|x| x.client.as_ref()) {
-        let client_folder = versions_folder.join(&version_profile.id);
         fs::create_dir_all(&client_folder).await?;
 
-        let client_jar = client_folder.join(format!("{}.jar", &version_profile.id));
-
-        // Add client jar to class path
-        write!(class_path, "{}{}", &client_jar.absolutize().unwrap().to_str().unwrap(), OS.get_path_separator()?)?;
-
         // Download client jar
         let requires_download = if !client_jar.exists() {
             true
@@ -101,23 +128,17 @@ pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: N
         if requires_download {
             launcher_data_arc.progress_update(ProgressUpdate::set_label("Downloading client..."));
 
-            let retrieved_bytes = download_file(&client_download.url, |a, b| {
+            // the sha1 is checked while downloading
+            download_file_verified(&client_download.url, &client_jar, &client_download.sha1, Some(client_download.size as u64), &launching_parameter.cancellation, track_transfer(&*launcher_data_arc, |a, b| {
                 launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadClientJar, get_progress(0, a, b), get_max(1)));
-            }).await?;
-
-            fs::write(&client_jar, retrieved_bytes).await?;
-
-            // After downloading, check sha1
-            let hash = sha1sum(&client_jar)?;
-            if hash != client_download.sha1 {
-                anyhow::bail!("Client JAR download failed. SHA1 mismatch.");
-            }
+            })).await?;
         }
     } else {
         return Err(LauncherError::InvalidVersionProfile("No client JAR downloads were specified.".to_string()).into());
     }
 
     // Libraries
+    launcher_data_arc.progress_update(ProgressUpdate::set_phase(InstallPhase::Libraries));
     let libraries_folder = data.join("libraries");
     let natives_folder = data.join("natives");
     let natives_path = natives_folder.as_path();
@@ -126,64 +147,18 @@ pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: N
     }
     fs::create_dir_all(&natives_folder).await?;
 
-    let libraries_to_download = version_profile.libraries.iter().map(|x| x.to_owned()).collect::<Vec<_>>();
-    // let libraries_downloaded = Arc::new(AtomicU64::new(0));
+    let libraries_to_download = command_builder.libraries();
     let libraries_max = libraries_to_download.len() as u64;
 
     launcher_data_arc.progress_update(ProgressUpdate::set_label("Checking libraries..."));
     launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadLibraries, 0, libraries_max));
 
-    let class_paths: Vec<Result<Option<String>>> = stream::iter(
-        libraries_to_download.into_iter().filter_map(|library| {
-            // let download_count = libraries_downloaded.clone();
-            let data_clone = launcher_data_arc.clone();
-            let folder_clone = libraries_folder.to_path_buf();
-
-            if !rule_interpreter::check_condition(&library.rules, &features).unwrap_or(false) {
-                return None;
-            }
-
-            Some(async move {
-                if let Some(natives) = &library.natives {
-                    if let Some(required_natives) = natives.get(OS.get_simple_name()?) {
-                        if let Some(classifiers) = library.downloads.as_ref().and_then(|x| x.classifiers.as_ref()) {
-                            if let Some(artifact) = classifiers.get(required_natives).map(LibraryDownloadInfo::from) {
-                                let path = artifact.download(library.name, folder_clone.as_path(), data_clone).await?;
-
-                                info!("Natives zip extract: {:?}", path);
-                                let file = OpenOptions::new().read(true).open(path).await?;
-                                zip_extract(file, natives_path).await?;
-                            }
-                        } else {
-                            return Err(LauncherError::InvalidVersionProfile("missing classifiers, but natives required.".to_string()).into());
-                        }
-                    }
-
-                    return Ok(None);
-                }
-
-                // Download regular artifact
-                let artifact = library.get_library_download()?;
-                let path = artifact.download(library.name, folder_clone.as_path(), data_clone).await?;
-
-                // Natives are not included in the classpath
-                return if library.natives.is_none() {
-                    return Ok(path.absolutize()?.to_str().map(|x| x.to_string()));
-                } else {
-                    Ok(None)
-                };
-            })
-        })
-    ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect().await;
-    for x in class_paths {
-        if let Some(library_path) = x? {
-            write!(class_path, "{}{}", &library_path, OS.get_path_separator()?)?;
-        }
-    }
+    download_libraries(&libraries_to_download, &libraries_folder, natives_path, features, &launching_parameter.download_source, launching_parameter.concurrent_downloads as usize, launcher_data_arc.clone(), launching_parameter.cancellation.clone()).await?;
 
     launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadLibraries, libraries_max, libraries_max));
 
     // Minecraft Assets
+    launcher_data_arc.progress_update(ProgressUpdate::set_phase(InstallPhase::Assets));
     let assets_folder = data.join("assets");
     let indexes_folder: PathBuf = assets_folder.join("indexes");
     let objects_folder: PathBuf = assets_folder.join("objects");
@@ -191,44 +166,61 @@ pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: N
     fs::create_dir_all(&indexes_folder).await?;
     fs::create_dir_all(&objects_folder).await?;
 
-    let asset_index_location = version_profile.asset_index_location.as_ref().ok_or_else(|| LauncherError::InvalidVersionProfile("Asset index unspecified".to_string()))?;
-    let asset_index = asset_index_location.load_asset_index(&indexes_folder).await?;
-    let asset_objects_to_download = asset_index.objects.values().map(|x| x.to_owned()).collect::<Vec<_>>();
-    let assets_downloaded = Arc::new(AtomicU64::new(0));
-    let asset_max = asset_objects_to_download.len() as u64;
+    // Logging configuration
+    if let Some(logging_client) = version_profile.logging.as_ref().and_then(|x| x.client.as_ref()) {
+        let log_configs_folder = assets_folder.join("log_configs");
+        fs::create_dir_all(&log_configs_folder).await?;
 
-    launcher_data_arc.progress_update(ProgressUpdate::set_label("Checking Minecraft assets..."));
-    launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, 0, asset_max));
+        launcher_data_arc.progress_update(ProgressUpdate::set_label("Checking logging configuration..."));
+        logging_client.file.download(&log_configs_folder, &launching_parameter.cancellation).await?;
+    }
 
-    let _: Vec<Result<()>> = stream::iter(
-        asset_objects_to_download.into_iter().map(|asset_object| {
-            let download_count = assets_downloaded.clone();
-            let data_clone = launcher_data_arc.clone();
-            let folder_clone = objects_folder.clone();
+    let asset_index = command_builder.asset_index_location()?.load_asset_index(&indexes_folder).await?;
+    let asset_hosts = Arc::new(launching_parameter.download_source.asset_hosts());
+
+    // only the objects which changed since the last install of the version are checked, the others are in the object store
+    let asset_objects = match AssetIndex::load_installed(&assets_folder, &version_profile.id).await {
+        Some(installed_index) => {
+            let mut changed = asset_index.changed_objects(&installed_index);
+            info!("{} of {} assets changed since the last install of {}", changed.len(), asset_index.objects.len(), version_profile.id);
+
+            // unchanged objects might have been removed from the object store since, their existence is cheap to check
+            let changed_hashes = changed.iter().map(|object| object.hash.clone()).collect::<HashSet<_>>();
+            let missing = asset_index.objects.values()
+                .filter(|object| !changed_hashes.contains(&object.hash) && !objects_folder.join(&object.hash[0..2]).join(&object.hash).exists())
+                .cloned()
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                warn!("{} unchanged assets of {} are missing from the object store", missing.len(), version_profile.id);
+                changed.extend(missing);
+            }
+            changed
+        }
+        None => asset_index.objects.values().cloned().collect()
+    };
 
-            async move {
-                let hash = asset_object.hash.clone();
-                match asset_object.download_destructing(folder_clone, data_clone.clone()).await {
-                    Ok(downloaded) => {
-                        let curr = download_count.fetch_add(1, Ordering::Relaxed);
+    launcher_data_arc.progress_update(ProgressUpdate::set_label("Checking Minecraft assets..."));
+    download_assets(&asset_objects, &objects_folder, asset_hosts, launching_parameter.concurrent_downloads as usize, launcher_data_arc.clone(), launching_parameter.cancellation.clone()).await?;
 
-                        if downloaded {
-                            // the progress bar is only being updated when a asset has been downloaded to improve speeds
-                            data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, curr, asset_max));
-                            data_clone.progress_update(ProgressUpdate::set_label(format!("Downloaded Minecraft asset {}", hash)));
-                        }
-                    }
-                    Err(err) => error!("Unable to download asset {}: {:?}", hash, err)
-                }
+    if let Err(e) = asset_index.store_installed(&assets_folder, &version_profile.id).await {
+        warn!("Unable to store the installed asset index of {}: {:?}", version_profile.id, e);
+    }
 
-                Ok(())
-            }
-        })
-    ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect().await;
+    let game_dir = command_builder.game_dir.clone();
+    fs::create_dir_all(&game_dir).await?;
 
-    launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, asset_max, asset_max));
+    if let Some(default_server) = &manifest.build.default_server {
+        if let Err(err) = seed_servers(&game_dir, std::slice::from_ref(default_server)).await {
+            warn!("Unable to add the default server to the server list: {:?}", err);
+        }
+    }
 
-    let game_dir = data.join("gameDir").join(manifest.build.branch.clone());
+    // Legacy asset layouts
+    if let Some(game_assets) = &command_builder.legacy_game_assets(&asset_index)? {
+        launcher_data_arc.progress_update(ProgressUpdate::set_label("Copying legacy Minecraft assets..."));
+        let copied = asset_index.materialize(&objects_folder, game_assets).await?;
+        info!("Copied {} legacy assets to {:?}", copied, game_assets);
+    }
 
     // Norisk Assets
     let norisk_asset_dir = game_dir.join("NoRiskClient").join("assets");
@@ -245,95 +237,69 @@ pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: N
     };
 
     if norisk_asset_objects_to_download.len() > 0 {
-        let norisk_assets_downloaded = Arc::new(AtomicU64::new(0));
-        let norisk_asset_max = norisk_asset_objects_to_download.values().map(|x| x.to_owned()).collect::<Vec<_>>().len() as u64;
-
-        launcher_data_arc.progress_update(ProgressUpdate::set_label("Checking Norisk assets..."));
-        launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, 0, norisk_asset_max));
-
-        let _: Vec<Result<()>> = stream::iter(
-            norisk_asset_objects_to_download.clone().into_iter().map(|asset_object| {
-                let download_count = norisk_assets_downloaded.clone();
-                let data_clone = launcher_data_arc.clone();
-                let folder_clone = norisk_asset_dir.clone();
-                let branch_clone = manifest.build.branch.clone();
-
-                async move {
-                    let hash = asset_object.1.hash.clone();
-
-                    match asset_object.1.download_norisk_cosmetic_destructing(branch_clone, asset_object.0, folder_clone, data_clone.clone()).await {
-                        Ok(downloaded) => {
-                            let curr = download_count.fetch_add(1, Ordering::Relaxed);
-
-                            if downloaded {
-                                // the progress bar is only being updated when a asset has been downloaded to improve speeds
-                                data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, curr, norisk_asset_max));
-                                data_clone.progress_update(ProgressUpdate::set_label(format!("Downloaded Norisk asset {}", hash)));
-                            }
-                        }
-                        Err(err) => error!("Unable to download Norisk asset {}: {:?}", hash, err)
-                    }
+        // kept next to the assets folder, unknown files in it are removed
+        let cosmetic_manifest = Arc::new(CosmeticManifest::load(&game_dir.join("NoRiskClient").join("assets-manifest.json")).await);
+        let failed_cosmetics = download_norisk_cosmetics(&manifest.build.branch, &norisk_asset_objects_to_download, &norisk_asset_dir, cosmetic_manifest.clone(), launching_parameter.concurrent_downloads as usize, launcher_data_arc.clone(), launching_parameter.cancellation.clone()).await;
 
-                    Ok(())
-                }
-            })
-        ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect().await;
+        // cosmetics are not required to play, so failed ones don't abort the launch
+        if !failed_cosmetics.is_empty() {
+            launcher_data_arc.progress_update(ProgressUpdate::warning(format!("Unable to download {} cosmetics", failed_cosmetics.len())));
+        }
+
+        if let Err(err) = cosmetic_manifest.store().await {
+            warn!("Unable to store the cosmetic manifest: {:?}", err);
+        }
 
-        launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, norisk_asset_max, norisk_asset_max));
+        if launching_parameter.cancellation.is_cancelled() {
+            return Err(LauncherError::Cancelled.into());
+        }
 
         // Delete usused norisk assets
 
         verify_norisk_assets(&norisk_asset_dir.clone(), norisk_asset_objects_to_download, launcher_data_arc.clone()).await;
     }
 
+    // the registry is only informational, so a failure doesn't stop the launch
+    let library_paths = libraries_to_download.iter()
+        .filter_map(|library| if library.natives.is_some() {
+            library.get_natives_download(&launching_parameter.download_source).ok().flatten()
+        } else {
+            library.get_library_download(&launching_parameter.download_source).ok()
+        })
+        .map(|download| download.path)
+        .collect();
+    if let Err(e) = InstalledVersions::record_launch(data, &version_profile.id, version_profile.inherits_from.clone(), Some(manifest.subsystem.name().to_string()), library_paths).await {
+        warn!("Unable to record the installed version {}: {:?}", version_profile.id, e);
+    }
+
     // Game
     let java_runtime = JavaRuntime::new(java_bin);
 
-    let mut command_arguments = Vec::new();
-
-    // JVM Args
-    version_profile.arguments.add_jvm_args_to_vec(norisk_token, &mut command_arguments, &launching_parameter, &features)?;
-
-    // Main class
-    command_arguments.push(version_profile.main_class.as_ref().ok_or_else(|| LauncherError::InvalidVersionProfile("Main class unspecified".to_string()))?.to_owned());
-
-    // Game args
-    version_profile.arguments.add_game_args_to_vec(&mut command_arguments, &features)?;
-
-    let mut mapped: Vec<String> = Vec::with_capacity(command_arguments.len());
-
-    for x in command_arguments.iter() {
-        mapped.push(
-            process_templates(x, |output, param| {
-                match param {
-                    "auth_player_name" => output.push_str(&launching_parameter.auth_player_name),
-                    "version_name" => output.push_str(&version_profile.id),
-                    "game_directory" => output.push_str(game_dir.absolutize().unwrap().to_str().unwrap()),
-                    "assets_root" => output.push_str(assets_folder.absolutize().unwrap().to_str().unwrap()),
-                    "assets_index_name" => output.push_str(&asset_index_location.id),
-                    "auth_uuid" => output.push_str(&launching_parameter.auth_uuid),
-                    "auth_access_token" => output.push_str(&launching_parameter.auth_access_token),
-                    "user_type" => output.push_str(&launching_parameter.user_type),
-                    "version_type" => output.push_str(&version_profile.version_type),
-                    "natives_directory" => output.push_str(natives_folder.absolutize().unwrap().to_str().unwrap()),
-                    "launcher_name" => output.push_str("NoRiskClient"),
-                    "launcher_version" => output.push_str(LAUNCHER_VERSION),
-                    "classpath" => output.push_str(&class_path),
-                    "user_properties" => output.push_str("{}"),
-                    "clientid" => output.push_str(&launching_parameter.clientid),
-                    "auth_xuid" => output.push_str(&launching_parameter.auth_xuid),
-                    _ => return Err(LauncherError::UnknownTemplateParameter(param.to_owned()).into())
-                };
-
-                Ok(())
-            })?
-        );
-    }
+    let command_arguments = command_builder.build(&libraries_to_download, &asset_index, &launching_parameter.auth_access_token, norisk_token)?;
 
+    launcher_data_arc.progress_update(ProgressUpdate::SetDownloadStats(DOWNLOAD_STATS.snapshot()));
     launcher_data_arc.progress_update(ProgressUpdate::set_label("Launching..."));
+    launcher_data_arc.weighted_progress.finish();
     launcher_data_arc.progress_update(ProgressUpdate::set_to_max());
 
-    let mut running_task = java_runtime.execute(mapped, &game_dir).await?;
+    // a missing log file shouldn't prevent playing
+    let game_log = if launching_parameter.capture_game_logs {
+        match create_game_log(data, &manifest.build.branch).await {
+            Ok(game_log) => Some(game_log),
+            Err(e) => {
+                warn!("Unable to create the game log file, the output won't be captured: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // everything is installed, other instances are able to install while the game is running
+    drop(install_lock);
+
+    let mut running_task = java_runtime.execute(command_arguments, &game_dir).await?;
+    launcher_data_arc.progress_update(ProgressUpdate::Completed);
 
     if !launching_parameter.keep_launcher_open {
         // Hide launcher window
@@ -345,7 +311,7 @@ pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: N
     let terminator = launcher_data.terminator;
     let data = launcher_data.data;
 
-    java_runtime.handle_io(&mut running_task, launcher_data.on_stdout, launcher_data.on_stderr, terminator, &data)
+    java_runtime.handle_io(&mut running_task, launcher_data.on_stdout, launcher_data.on_stderr, terminator, game_log, &data)
         .await?;
 
     if !launching_parameter.keep_launcher_open {
@@ -356,6 +322,524 @@ pub async fn launch<D: Send + Sync>(norisk_token: &str, data: &Path, manifest: N
     Ok(())
 }
 
+///
+/// Assembles the arguments the java binary is started with: the JVM arguments, the main class and the game arguments,
+/// with the placeholders replaced by the values of the context.
+///
+pub fn build_launch_command(profile: &VersionProfile, norisk_token: &str, parameter: &LaunchingParameter, features: &HashSet<String>, logging: Option<(&LoggingClient, &Path)>, ctx: &LaunchContext) -> Result<Vec<String>> {
+    let main_class = profile.main_class.as_ref()
+        .ok_or_else(|| LauncherError::InvalidVersionProfile("Main class unspecified".to_string()))?;
+
+    let mut command_arguments = Vec::new();
+
+    // JVM Args
+    profile.arguments.add_jvm_args_to_vec(norisk_token, &mut command_arguments, parameter, features, logging)?;
+
+    // Main class
+    command_arguments.push(main_class.to_owned());
+
+    // Game args
+    profile.arguments.add_game_args_to_vec(&mut command_arguments, features)?;
+
+    substitute_placeholders(&mut command_arguments, ctx)?;
+    Ok(command_arguments)
+}
+
+/// Replaces the tokens in the command of a dry run
+const REDACTED_TOKEN: &str = "<redacted>";
+
+///
+/// Assembles the launch command of a version profile from the folders of the data path.
+/// The launch and the dry run share it, so the command shown for troubleshooting is the one which is run.
+///
+pub struct LaunchCommandBuilder<'a> {
+    profile: &'a VersionProfile,
+    parameter: &'a LaunchingParameter,
+    /// Features the rules of the arguments and libraries are checked against
+    pub features: HashSet<String>,
+    /// Quick play of the parameter, none if the version doesn't support it
+    pub quick_play: Option<&'a QuickPlay>,
+    pub game_dir: PathBuf,
+}
+
+impl<'a> LaunchCommandBuilder<'a> {
+    pub fn new(profile: &'a VersionProfile, parameter: &'a LaunchingParameter, branch: &str) -> Self {
+        let mut features = build_feature_set(parameter);
+
+        // older versions don't know the quick play arguments and are launched normally
+        let quick_play = match &parameter.quick_play {
+            Some(quick_play) if profile.arguments.supports_feature(quick_play.feature()) => Some(quick_play),
+            Some(quick_play) => {
+                features.remove(quick_play.feature());
+                warn!("Version {} does not support quick play {:?}, launching normally", profile.id, quick_play);
+                None
+            }
+            None => None
+        };
+
+        LaunchCommandBuilder { profile, parameter, features, quick_play, game_dir: parameter.game_directory(branch) }
+    }
+
+    fn folder(&self, name: &str) -> PathBuf {
+        self.parameter.data_path.join(name)
+    }
+
+    pub fn client_jar(&self) -> PathBuf {
+        self.folder("versions").join(&self.profile.id).join(format!("{}.jar", &self.profile.id))
+    }
+
+    /// Libraries whose rules apply, selected for the architecture
+    pub fn libraries(&self) -> Vec<Library> {
+        Library::select_for_architecture(self.profile.libraries.iter()
+            .filter(|library| rule_interpreter::check_condition(&library.rules, &self.features).unwrap_or(false))
+            .map(|x| x.to_owned())
+            .collect::<Vec<_>>())
+    }
+
+    pub fn asset_index_location(&self) -> Result<&'a AssetIndexLocation> {
+        Ok(self.profile.asset_index_location.as_ref().ok_or_else(|| LauncherError::InvalidVersionProfile("Asset index unspecified".to_string()))?)
+    }
+
+    /// Folder the assets are copied to by their name for versions with a legacy asset layout, none for the hashed layout
+    pub fn legacy_game_assets(&self, asset_index: &AssetIndex) -> Result<Option<PathBuf>> {
+        Ok(if asset_index.map_to_resources {
+            Some(self.game_dir.join("resources"))
+        } else if asset_index.is_virtual {
+            Some(self.folder("assets").join("virtual").join(&self.asset_index_location()?.id))
+        } else {
+            None
+        })
+    }
+
+    /// Arguments the java binary is started with, the logging configuration is expected in assets/log_configs
+    pub fn build(&self, libraries: &[Library], asset_index: &AssetIndex, access_token: &str, norisk_token: &str) -> Result<Vec<String>> {
+        let assets_folder = self.folder("assets");
+        let libraries_folder = self.folder("libraries");
+
+        let logging_client = self.profile.logging.as_ref().and_then(|x| x.client.as_ref());
+        let logging_config = match logging_client {
+            Some(logging_client) => Some(assets_folder.join("log_configs").join(&logging_client.file.id).absolutize()?.to_path_buf()),
+            None => None
+        };
+
+        let quick_play = self.quick_play;
+        let launch_context = LaunchContext {
+            auth_player_name: self.parameter.auth_player_name.clone(),
+            auth_uuid: self.parameter.auth_uuid.clone(),
+            auth_access_token: access_token.to_string(),
+            auth_xuid: self.parameter.auth_xuid.clone(),
+            clientid: self.parameter.clientid.clone(),
+            user_type: self.parameter.user_type.clone(),
+            version_name: self.profile.id.clone(),
+            version_type: self.profile.version_type.clone(),
+            game_directory: absolute_path_string(&self.game_dir)?,
+            assets_root: absolute_path_string(&assets_folder)?,
+            assets_index_name: self.asset_index_location()?.id.clone(),
+            game_assets: absolute_path_string(&self.legacy_game_assets(asset_index)?.unwrap_or_else(|| assets_folder.clone()))?,
+            natives_directory: absolute_path_string(&self.folder("natives"))?,
+            library_directory: absolute_path_string(&libraries_folder)?,
+            classpath: build_classpath(libraries, &libraries_folder, &self.client_jar(), &self.features, &self.parameter.download_source)?,
+            classpath_separator: OS.get_path_separator()?.to_string(),
+            resolution_width: self.parameter.width.map(|x| x.to_string()).unwrap_or_default(),
+            resolution_height: self.parameter.height.map(|x| x.to_string()).unwrap_or_default(),
+            quick_play_singleplayer: match quick_play { Some(QuickPlay::World(world)) => world.clone(), _ => String::new() },
+            quick_play_multiplayer: match quick_play { Some(QuickPlay::Server(server)) => server.clone(), _ => String::new() },
+            quick_play_realms: match quick_play { Some(QuickPlay::Realm(realm)) => realm.clone(), _ => String::new() },
+        };
+
+        build_launch_command(self.profile, norisk_token, self.parameter, &self.features, logging_client.zip(logging_config.as_deref()), &launch_context)
+    }
+}
+
+///
+/// Command the launcher would run for the version profile, for troubleshooting launch issues.
+/// Nothing is downloaded or written, a missing asset index is only fetched to choose the legacy asset folder.
+/// The access token, the norisk token and session tokens are replaced by a placeholder, so the command can be shared.
+///
+pub async fn dry_run_command(profile: &VersionProfile, params: &LaunchingParameter, branch: &str, norisk_token: &str) -> Result<String> {
+    let command_builder = LaunchCommandBuilder::new(profile, params, branch);
+    let asset_index = command_builder.asset_index_location()?.peek_asset_index(&params.data_path.join("assets").join("indexes")).await?;
+    let command_arguments = command_builder.build(&command_builder.libraries(), &asset_index, REDACTED_TOKEN, REDACTED_TOKEN)?;
+
+    // the java binary is only resolved when launching, it may have to be downloaded first
+    let java = params.custom_java_path.clone().filter(|path| !path.is_empty()).unwrap_or_else(|| "java".to_string());
+    let command = std::iter::once(java)
+        .chain(command_arguments)
+        .map(|arg| quote_argument(&redact_tokens(&arg, &[&params.auth_access_token, norisk_token])))
+        .collect::<Vec<_>>();
+
+    Ok(command.join(" "))
+}
+
+/// Replaces the tokens in an argument, custom java arguments and legacy session arguments (token:<access token>:<uuid>) may contain them
+fn redact_tokens(arg: &str, tokens: &[&str]) -> String {
+    tokens.iter()
+        .filter(|token| !token.is_empty())
+        .fold(arg.to_string(), |arg, token| arg.replace(token, REDACTED_TOKEN))
+}
+
+/// Quotes arguments containing whitespace or quotes, so the command can be pasted into a shell
+fn quote_argument(arg: &str) -> String {
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        format!("\"{}\"", arg.replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+/// Game logs kept per branch, older ones are deleted when a new launch starts
+const GAME_LOGS_PER_BRANCH: usize = 10;
+
+///
+/// Creates the log file of this launch in the logs folder, named after the branch and the time of the launch.
+/// Only the newest logs of the branch are kept.
+///
+async fn create_game_log(data: &Path, branch: &str) -> Result<fs::File> {
+    let logs_folder = data.join("logs");
+    fs::create_dir_all(&logs_folder).await?;
+
+    let prefix = format!("game-{}-", branch);
+    let mut previous_logs = Vec::new();
+    let mut entries = fs::read_dir(&logs_folder).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        // the timestamp after the prefix keeps branches which start with this branch name apart
+        if file_name.strip_prefix(&prefix).map_or(false, |rest| rest.starts_with(|c: char| c.is_ascii_digit())) {
+            previous_logs.push(entry.path());
+        }
+    }
+
+    // the timestamp in the name sorts chronologically
+    previous_logs.sort();
+    let outdated = previous_logs.len().saturating_sub(GAME_LOGS_PER_BRANCH - 1);
+    for path in &previous_logs[..outdated] {
+        if let Err(e) = fs::remove_file(path).await {
+            warn!("Unable to delete old game log {:?}: {:?}", path, e);
+        }
+    }
+
+    let path = logs_folder.join(format!("{}{}.log", prefix, chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")));
+    info!("Capturing game output to {:?}", path);
+    Ok(fs::File::create(path).await?)
+}
+
+/// Java binaries resolved during this session, by required major version and custom java path
+static RESOLVED_JAVA: Lazy<Mutex<HashMap<(u32, Option<String>), PathBuf>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+///
+/// Resolves the java binary to launch the version profile with. The required version is taken from the profile's java version
+/// (or guessed from the minecraft version) and raised to the JRE version of the branch if that is newer.
+///
+pub async fn resolve_java_for_profile(data: &Path, profile: &VersionProfile, branch_jre_version: u32, custom_java_path: &Option<String>, progress: &impl ProgressReceiver, cancellation: &CancellationToken) -> Result<PathBuf> {
+    let jre_version = profile.required_java_major().max(branch_jre_version);
+    resolve_java_binary(data, jre_version, custom_java_path, progress, cancellation).await
+}
+
+///
+/// Returns the java binary for the given major version, in order of precedence:
+/// the custom java path if set, the provisioned JRE of the launcher which is downloaded if missing, or a java installation of the system.
+/// The result is cached for the session as long as the binary still exists.
+///
+pub async fn resolve_java_binary(data: &Path, jre_version: u32, custom_java_path: &Option<String>, progress: &impl ProgressReceiver, cancellation: &CancellationToken) -> Result<PathBuf> {
+    let cache_key = (jre_version, custom_java_path.clone());
+    let cached = RESOLVED_JAVA.lock().ok().and_then(|resolved| resolved.get(&cache_key).cloned());
+    if let Some(java_bin) = cached.filter(|java_bin| java_bin.exists()) {
+        debug!("Using previously resolved Java {}: {:?}", jre_version, java_bin);
+        return Ok(java_bin);
+    }
+
+    let java_bin = match custom_java_path {
+        Some(path) => resolve_custom_java(path, jre_version).await?,
+        None => match resolve_provisioned_java(data, jre_version, progress, cancellation).await {
+            Ok(java_bin) => java_bin,
+            // a cancelled launch doesn't look for another java
+            Err(e) if cancellation.is_cancelled() => return Err(e),
+            Err(e) => {
+                warn!("Unable to provision Java {}, looking for a system java: {:?}", jre_version, e);
+                progress.progress_update(ProgressUpdate::set_label("Looking for a system java..."));
+
+                match find_system_java(jre_version).await {
+                    Some(java_bin) => {
+                        progress.progress_update(ProgressUpdate::warning(format!("Unable to download Java {}, using the system java", jre_version)));
+                        java_bin
+                    }
+                    None => anyhow::bail!("no Java {} or newer is available: the runtime couldn't be downloaded ({}) and no system java satisfies the requirement. Set a custom java path to a Java {} installation.", jre_version, e, jre_version)
+                }
+            }
+        }
+    };
+
+    if let Ok(mut resolved) = RESOLVED_JAVA.lock() {
+        resolved.insert(cache_key, java_bin.clone());
+    }
+    Ok(java_bin)
+}
+
+/// Validates the custom java binary against the required major version
+async fn resolve_custom_java(path: &str, jre_version: u32) -> Result<PathBuf> {
+    let java_bin = PathBuf::from(path);
+    let java_info = validate_java(&java_bin).await
+        .with_context(|| format!("the custom java path {} is not a working java installation", path))?;
+    info!("Custom java {} is {:?}", path, java_info);
+
+    if java_info.major < jre_version {
+        anyhow::bail!("the custom java at {} is Java {}, but this version requires Java {} or newer. Change the custom java path or clear it to use the bundled runtime.", path, java_info.major, jre_version);
+    }
+    Ok(java_bin)
+}
+
+/// Returns the JRE of the given version from the runtimes folder, which is downloaded if missing
+async fn resolve_provisioned_java(data: &Path, jre_version: u32, progress: &impl ProgressReceiver, cancellation: &CancellationToken) -> Result<PathBuf> {
+    let runtimes_folder = data.join("runtimes");
+    if !runtimes_folder.exists() {
+        fs::create_dir(&runtimes_folder).await?;
+    }
+
+    info!("Checking for JRE...");
+    progress.progress_update(ProgressUpdate::set_label("Checking for JRE..."));
+
+    Ok(match find_java_binary(&runtimes_folder, jre_version).await {
+        Ok(jre) => jre,
+        Err(e) => {
+            error!("Failed to find JRE: {}", e);
+
+            info!("Download JRE...");
+            progress.progress_update(ProgressUpdate::set_label("Download JRE..."));
+            let on_progress = |a, b| {
+                progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadJRE, get_progress(0, a, b), get_max(1)));
+            };
+
+            match jre_downloader::jre_download(&runtimes_folder, jre_version, on_progress).await {
+                Ok(jre) => jre,
+                Err(e) => {
+                    // Temurin is used if the launcher api doesn't offer a runtime for this platform
+                    warn!("Failed to download JRE from the launcher api, falling back to Temurin: {:?}", e);
+                    progress.progress_update(ProgressUpdate::warning("The launcher api has no JRE for this platform, using Temurin"));
+                    progress.progress_update(ProgressUpdate::set_label("Download Temurin JRE..."));
+                    adoptium_download(&runtimes_folder, jre_version, cancellation, track_transfer(progress, on_progress)).await?
+                }
+            }
+        }
+    })
+}
+
+///
+/// Builds the class path of the libraries whose rules apply, followed by the client jar.
+/// Natives libraries are not part of the class path and duplicate paths are only included once.
+///
+pub fn build_classpath(libraries: &[Library], libraries_folder: &Path, client_jar: &Path, features: &HashSet<String>, source: &DownloadSource) -> Result<String> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+
+    for library in libraries {
+        if library.natives.is_some() || !rule_interpreter::check_condition(&library.rules, features)? {
+            continue;
+        }
+
+        let path = libraries_folder.join(library.get_library_download(source)?.path).absolutize()?.to_path_buf();
+        if seen.insert(path.clone()) {
+            entries.push(path.to_string_lossy().to_string());
+        }
+    }
+    entries.push(client_jar.absolutize()?.to_string_lossy().to_string());
+
+    Ok(entries.join(OS.get_path_separator()?))
+}
+
+///
+/// Downloads all libraries in parallel bounded by the given concurrency and extracts their natives.
+/// The first failing library aborts the whole batch, the class path is built by [build_classpath].
+/// Tripping the cancellation token aborts the batch with [LauncherError::Cancelled].
+///
+pub async fn download_libraries(libraries: &[Library], libraries_folder: &Path, natives_folder: &Path, features: &HashSet<String>, source: &DownloadSource, concurrency: usize, progress: Arc<impl ProgressReceiver>, cancellation: CancellationToken) -> Result<()> {
+    let libraries_downloaded = Arc::new(AtomicU64::new(0));
+    let libraries_max = libraries.len() as u64;
+    DOWNLOAD_STATS.enqueue(libraries_max);
+
+    stream::iter(
+        libraries.iter().cloned().map(|library| {
+            let download_count = libraries_downloaded.clone();
+            let data_clone = progress.clone();
+            let folder_clone = libraries_folder.to_path_buf();
+            let name = library.name.clone();
+            let cancellation = cancellation.clone();
+
+            let download = DOWNLOAD_STATS.track(async move {
+                if cancellation.is_cancelled() {
+                    return Err(LauncherError::Cancelled.into());
+                }
+
+                if library.natives.is_some() {
+                    return extract_natives(&library, folder_clone.as_path(), natives_folder, features, source, data_clone, &cancellation).await;
+                }
+
+                // Download regular artifact
+                let artifact = library.get_library_download(source)?;
+                artifact.download(library.name, folder_clone.as_path(), data_clone.clone(), &cancellation).await?;
+
+                let curr = download_count.fetch_add(1, Ordering::Relaxed) + 1;
+                data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadLibraries, curr, libraries_max));
+                Ok(())
+            });
+
+            async move {
+                download.await.with_context(|| format!("unable to download library {}", name))
+            }
+        })
+    ).buffer_unordered(concurrency).try_collect::<Vec<()>>().await.map_err(|e| {
+        if cancellation.is_cancelled() {
+            LauncherError::Cancelled.into()
+        } else {
+            e
+        }
+    })?;
+
+    Ok(())
+}
+
+///
+/// Downloads the NoRisk cosmetics of the branch in parallel, files which are already verified are skipped.
+/// A failed cosmetic doesn't stop the others, the paths of the failed ones are returned.
+///
+pub async fn download_norisk_cosmetics(branch: &str, cosmetics: &HashMap<String, AssetObject>, assets_folder: &Path, manifest: Arc<CosmeticManifest>, concurrency: usize, progress: Arc<impl ProgressReceiver>, cancellation: CancellationToken) -> Vec<String> {
+    let cosmetics_checked = Arc::new(AtomicU64::new(0));
+    let cosmetics_max = cosmetics.len() as u64;
+    DOWNLOAD_STATS.enqueue(cosmetics_max);
+
+    progress.progress_update(ProgressUpdate::set_label("Checking Norisk assets..."));
+    progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, 0, cosmetics_max));
+
+    // every cosmetic is downloaded from the api environment of the options, they are only loaded once
+    let api = Arc::new(NoriskApi::load().await);
+
+    let failed_files: Vec<Option<String>> = stream::iter(
+        cosmetics.clone().into_iter().map(|(file_path, asset_object)| {
+            let checked_count = cosmetics_checked.clone();
+            let data_clone = progress.clone();
+            let folder_clone = assets_folder.to_path_buf();
+            let branch_clone = branch.to_string();
+            let manifest_clone = manifest.clone();
+            let cancellation = cancellation.clone();
+            let api = api.clone();
+
+            async move {
+                if cancellation.is_cancelled() {
+                    return None;
+                }
+
+                let result = DOWNLOAD_STATS.track(asset_object.download_norisk_cosmetic_destructing(api, branch_clone, file_path.clone(), folder_clone, manifest_clone, data_clone.clone(), cancellation)).await;
+                let curr = checked_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+                match result {
+                    Ok(downloaded) => {
+                        if downloaded {
+                            // the progress bar is only being updated when a cosmetic has been downloaded to improve speeds
+                            data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, curr, cosmetics_max));
+                            data_clone.progress_update(ProgressUpdate::set_label(format!("Downloading cosmetics {}/{}", curr, cosmetics_max)));
+                        }
+                        None
+                    }
+                    Err(err) => {
+                        error!("Unable to download Norisk asset {}: {:?}", file_path, err);
+                        Some(file_path)
+                    }
+                }
+            }
+        })
+    ).buffer_unordered(concurrency).collect().await;
+
+    progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, cosmetics_max, cosmetics_max));
+    failed_files.into_iter().flatten().collect()
+}
+
+///
+/// Downloads all objects of the asset index which are missing or invalid, at most `concurrency` at a time.
+/// A failing object doesn't abort the other downloads, all failed hashes are reported at the end.
+///
+pub async fn download_assets(asset_objects: &[AssetObject], objects_folder: &Path, hosts: Arc<Vec<String>>, concurrency: usize, progress: Arc<impl ProgressReceiver>, cancellation: CancellationToken) -> Result<()> {
+    let assets_checked = Arc::new(AtomicU64::new(0));
+    let asset_max = asset_objects.len() as u64;
+    DOWNLOAD_STATS.enqueue(asset_max);
+
+    progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, 0, asset_max));
+
+    let failed_hashes: Vec<Option<String>> = stream::iter(
+        asset_objects.iter().cloned().map(|asset_object| {
+            let checked_count = assets_checked.clone();
+            let data_clone = progress.clone();
+            let folder_clone = objects_folder.to_path_buf();
+            let hosts_clone = hosts.clone();
+            let cancellation = cancellation.clone();
+
+            async move {
+                let hash = asset_object.hash.clone();
+                let result = DOWNLOAD_STATS.track(asset_object.download_destructing(hosts_clone, folder_clone, data_clone.clone(), cancellation)).await;
+                let curr = checked_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+                match result {
+                    Ok(downloaded) => {
+                        if downloaded {
+                            // the progress bar is only being updated when a asset has been downloaded to improve speeds
+                            data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, curr, asset_max));
+                            data_clone.progress_update(ProgressUpdate::set_label(format!("Downloaded {}/{} assets", curr, asset_max)));
+                        }
+                        None
+                    }
+                    Err(err) => {
+                        error!("Unable to download asset {}: {:?}", hash, err);
+                        Some(hash)
+                    }
+                }
+            }
+        })
+    ).buffer_unordered(concurrency).collect().await;
+
+    if cancellation.is_cancelled() {
+        return Err(LauncherError::Cancelled.into());
+    }
+
+    let failed_hashes = failed_hashes.into_iter().flatten().collect::<Vec<_>>();
+    if !failed_hashes.is_empty() {
+        anyhow::bail!("unable to download {} of {} assets: {}", failed_hashes.len(), asset_max, failed_hashes.join(", "));
+    }
+
+    progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, asset_max, asset_max));
+    Ok(())
+}
+
+///
+/// Downloads the natives artifact of the library for the current OS and architecture and extracts it to the natives folder.
+/// Libraries whose rules don't apply or without natives for the current OS are skipped.
+///
+pub async fn extract_natives(library: &Library, libraries_folder: &Path, natives_folder: &Path, features: &HashSet<String>, source: &DownloadSource, progress: Arc<impl ProgressReceiver>, cancellation: &CancellationToken) -> Result<()> {
+    if !rule_interpreter::check_condition(&library.rules, features)? {
+        return Ok(());
+    }
+
+    let artifact = match library.get_natives_download(source)? {
+        Some(artifact) => artifact,
+        None => {
+            debug!("Library {} has no natives for {} {}", library.name, OS, *ARCHITECTURE);
+            return Ok(());
+        }
+    };
+
+    let path = artifact.download(library.name.clone(), libraries_folder, progress, cancellation).await?;
+
+    info!("Natives zip extract: {:?}", path);
+    let file = OpenOptions::new().read(true).open(path).await?;
+    let extracted = zip_extract_filtered(file, natives_folder, |name| library.should_extract(name)).await?;
+
+    #[cfg(unix)]
+    for path in &extracted {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+    debug!("Extracted {} natives of {}", extracted.len(), library.name);
+
+    Ok(())
+}
+
 async fn verify_norisk_assets<D: Send + Sync>(dir: &Path, asset_objetcs: HashMap<String, AssetObject>, launcher_data_arc: Arc<LauncherData<D>>) {
     let mut keys_vec: Vec<&str> = vec![];
     for location in asset_objetcs.keys() {
@@ -394,11 +878,15 @@ async fn verify_norisk_assets<D: Send + Sync>(dir: &Path, asset_objetcs: HashMap
 }
 
 pub struct LaunchingParameter {
-    pub dev_mode: bool,
+    /// Value of -Dnorisk.experimental, resolved from the dev mode and the option to disable the flag
+    pub norisk_experimental: bool,
     pub memory: i64,
+    pub initial_memory: i64,
+    pub use_default_gc_flags: bool,
     pub data_path: PathBuf,
     pub custom_java_path: Option<String>,
     pub custom_java_args: String,
+    pub jvm_args_policy: Option<JvmArgsPolicy>,
     pub auth_player_name: String,
     pub auth_uuid: String,
     pub auth_access_token: String,
@@ -407,9 +895,279 @@ pub struct LaunchingParameter {
     pub user_type: String,
     pub keep_launcher_open: bool,
     pub concurrent_downloads: i32,
+    pub download_source: DownloadSource,
+    pub cancellation: CancellationToken,
+    pub demo: bool,
+    /// Window size of the game, both have to be set to be applied
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub quick_play: Option<QuickPlay>,
+    pub capture_game_logs: bool,
+    pub isolate_game_dirs: bool,
+}
+
+/// Game directory all branches use if the game directories are not isolated
+const SHARED_GAME_DIR: &str = "_shared";
+
+impl LaunchingParameter {
+    ///
+    /// Game directory of the branch with its saves, configs and mods. Without isolation every branch uses the same directory,
+    /// the saves and configs of the existing branch directories are not moved into it and have to be copied over manually.
+    ///
+    pub fn game_directory(&self, branch: &str) -> PathBuf {
+        game_directory(&self.data_path, self.isolate_game_dirs, branch)
+    }
+}
+
+/// Game directory of the branch, for callers without a launching parameter like the mod list
+pub fn game_directory(data_path: &Path, isolate_game_dirs: bool, branch: &str) -> PathBuf {
+    let game_dirs = data_path.join("gameDir");
+    if isolate_game_dirs {
+        game_dirs.join(branch)
+    } else {
+        game_dirs.join(SHARED_GAME_DIR)
+    }
+}
+
+///
+/// Assembles the launching parameter from the options and the selected account.
+/// The overrides of the branch are applied and everything is validated before the launch starts.
+///
+pub struct LaunchingParameterBuilder {
+    options: LauncherOptions,
+    account: Option<LoginData>,
+    jvm_args_policy: Option<JvmArgsPolicy>,
+    cancellation: CancellationToken,
+    quick_play: Option<QuickPlay>,
+    demo: bool,
+}
+
+impl LaunchingParameterBuilder {
+    pub fn new(options: LauncherOptions) -> Self {
+        LaunchingParameterBuilder {
+            options,
+            account: None,
+            jvm_args_policy: None,
+            cancellation: CancellationToken::new(),
+            quick_play: None,
+            demo: false,
+        }
+    }
+
+    /// Applies the overrides of the branch to the options
+    pub fn branch(mut self, branch: &str) -> Self {
+        self.options = self.options.with_profile_overrides(branch);
+        self
+    }
+
+    pub fn account(mut self, account: LoginData) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    pub fn jvm_args_policy(mut self, jvm_args_policy: Option<JvmArgsPolicy>) -> Self {
+        self.jvm_args_policy = jvm_args_policy;
+        self
+    }
+
+    pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn quick_play(mut self, quick_play: Option<QuickPlay>) -> Self {
+        self.quick_play = quick_play;
+        self
+    }
+
+    pub fn demo(mut self, demo: bool) -> Self {
+        self.demo = demo;
+        self
+    }
+
+    pub fn build(self) -> Result<LaunchingParameter> {
+        let options = self.options;
+        let account = self.account.ok_or_else(|| anyhow::anyhow!("no account selected"))?;
+        if account.mc_token.is_empty() {
+            anyhow::bail!("account {} has no minecraft token, a re-login is required", account.username);
+        }
+
+        let memory = options.memory_setting().megabytes();
+        if memory <= 0 {
+            anyhow::bail!("memory has to be greater than 0, got {}M", memory);
+        }
+        let initial_memory = options.initial_memory_megabytes(memory);
+        if initial_memory <= 0 {
+            anyhow::bail!("initial memory has to be greater than 0, got {}M", initial_memory);
+        }
+
+        // arguments pasted over multiple lines are one argument list
+        let custom_java_args = options.custom_java_args.replace(['\r', '\n'], " ").trim().to_string();
+        let custom_java_path = Some(options.custom_java_path.trim().to_string()).filter(|path| !path.is_empty());
+
+        Ok(LaunchingParameter {
+            norisk_experimental: options.dev_mode && !options.disable_experimental_flag,
+            memory,
+            initial_memory,
+            use_default_gc_flags: options.use_default_gc_flags,
+            data_path: options.data_path_buf(),
+            custom_java_path,
+            custom_java_args,
+            jvm_args_policy: self.jvm_args_policy,
+            auth_player_name: account.username,
+            auth_uuid: account.uuid,
+            auth_access_token: account.mc_token,
+            auth_xuid: "x".to_string(),
+            clientid: AZURE_CLIENT_ID.to_string(),
+            user_type: "msa".to_string(),
+            keep_launcher_open: options.keep_launcher_open,
+            concurrent_downloads: options.concurrent_downloads,
+            download_source: options.download_mirror.unwrap_or_default(),
+            cancellation: self.cancellation,
+            demo: self.demo,
+            width: options.game_width,
+            height: options.game_height,
+            quick_play: self.quick_play,
+            capture_game_logs: options.capture_game_logs,
+            isolate_game_dirs: options.isolate_game_dirs,
+        })
+    }
+}
+
+///
+/// Target the game joins right after starting, supported since 1.20
+///
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "value")]
+pub enum QuickPlay {
+    /// Name of the world folder in the saves
+    World(String),
+    /// Address of the server, optionally with the port
+    Server(String),
+    /// Id of the realm
+    Realm(String),
+}
+
+impl QuickPlay {
+    /// Feature of the argument rules which enables the quick play argument
+    pub fn feature(&self) -> &'static str {
+        match self {
+            QuickPlay::World(_) => "is_quick_play_singleplayer",
+            QuickPlay::Server(_) => "is_quick_play_multiplayer",
+            QuickPlay::Realm(_) => "is_quick_play_realms",
+        }
+    }
+}
+
+///
+/// Features the argument rules of the version profile are evaluated against, a normal launch has none.
+/// The quick play feature is included if requested, even if the version doesn't support it.
+///
+pub fn build_feature_set(params: &LaunchingParameter) -> HashSet<String> {
+    let mut features = HashSet::new();
+
+    if params.demo {
+        features.insert("is_demo_user".to_string());
+    }
+    if params.width.is_some() && params.height.is_some() {
+        features.insert("has_custom_resolution".to_string());
+    }
+    if let Some(quick_play) = &params.quick_play {
+        features.insert(quick_play.feature().to_string());
+    }
+
+    features
+}
+
+///
+/// Values of the placeholders in the launch arguments
+///
+#[derive(Default)]
+pub struct LaunchContext {
+    pub auth_player_name: String,
+    pub auth_uuid: String,
+    pub auth_access_token: String,
+    pub auth_xuid: String,
+    pub clientid: String,
+    pub user_type: String,
+    pub version_name: String,
+    pub version_type: String,
+    pub game_directory: String,
+    pub assets_root: String,
+    pub assets_index_name: String,
+    pub game_assets: String,
+    pub natives_directory: String,
+    pub library_directory: String,
+    pub classpath: String,
+    pub classpath_separator: String,
+    pub resolution_width: String,
+    pub resolution_height: String,
+    pub quick_play_singleplayer: String,
+    pub quick_play_multiplayer: String,
+    pub quick_play_realms: String,
 }
 
-fn process_templates<F: Fn(&mut String, &str) -> Result<()>>(input: &String, retriever: F) -> Result<String> {
+impl LaunchContext {
+    fn resolve(&self, placeholder: &str) -> Option<&str> {
+        let value: &str = match placeholder {
+            "auth_player_name" => &self.auth_player_name,
+            "auth_uuid" => &self.auth_uuid,
+            "auth_access_token" => &self.auth_access_token,
+            "auth_xuid" => &self.auth_xuid,
+            "clientid" => &self.clientid,
+            "user_type" => &self.user_type,
+            "version_name" => &self.version_name,
+            "version_type" => &self.version_type,
+            "game_directory" => &self.game_directory,
+            "assets_root" => &self.assets_root,
+            "assets_index_name" => &self.assets_index_name,
+            "game_assets" => &self.game_assets,
+            "natives_directory" => &self.natives_directory,
+            "library_directory" => &self.library_directory,
+            "classpath" => &self.classpath,
+            "classpath_separator" => &self.classpath_separator,
+            "resolution_width" => &self.resolution_width,
+            "resolution_height" => &self.resolution_height,
+            "quickPlaySingleplayer" => &self.quick_play_singleplayer,
+            "quickPlayMultiplayer" => &self.quick_play_multiplayer,
+            "quickPlayRealms" => &self.quick_play_realms,
+            "launcher_name" => "NoRiskClient",
+            "launcher_version" => LAUNCHER_VERSION,
+            "user_properties" => "{}",
+            _ => return None
+        };
+
+        Some(value)
+    }
+}
+
+///
+/// Replaces the ${placeholders} of the arguments with the values of the context.
+/// Unknown placeholders are kept as they are, so the game receives them instead of an empty argument.
+///
+pub fn substitute_placeholders(args: &mut Vec<String>, ctx: &LaunchContext) -> Result<()> {
+    for arg in args.iter_mut() {
+        *arg = process_templates(arg, |output, param| {
+            match ctx.resolve(param) {
+                Some(value) => output.push_str(value),
+                None => {
+                    warn!("Unknown placeholder ${{{}}} in launch argument {}", param, arg);
+                    output.push_str(&format!("${{{}}}", param));
+                }
+            };
+
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
+
+fn absolute_path_string(path: &Path) -> Result<String> {
+    Ok(path.absolutize()?.to_string_lossy().to_string())
+}
+
+pub(crate) fn process_templates<F: Fn(&mut String, &str) -> Result<()>>(input: &String, retriever: F) -> Result<String> {
     let mut output = String::with_capacity(input.len() * 3 / 2);
 
     let mut chars = input.chars().peekable();
@@ -445,3 +1203,304 @@ fn process_templates<F: Fn(&mut String, &str) -> Result<()>>(input: &String, ret
 
     Ok(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::app::app_data::MemorySetting;
+    use crate::utils::sha1sum_bytes;
+    use crate::utils::test_util::test_folder;
+
+    use super::*;
+
+    fn account(mc_token: &str) -> LoginData {
+        LoginData {
+            mc_token: mc_token.to_string(),
+            access_token: "access-token".to_string(),
+            refresh_token: "refresh-token".to_string(),
+            uuid: "uuid".to_string(),
+            username: "Player".to_string(),
+            norisk_token: "norisk-token".to_string(),
+            experimental_token: None,
+            mc_token_expires_at: None,
+            needs_relogin: false,
+        }
+    }
+
+    fn options_with_memory(megabytes: i32) -> LauncherOptions {
+        LauncherOptions { memory: Some(MemorySetting::Absolute(megabytes)), ..LauncherOptions::default() }
+    }
+
+    fn rejection(builder: LaunchingParameterBuilder) -> String {
+        match builder.build() {
+            Ok(_) => panic!("builder accepted invalid options"),
+            Err(err) => err.to_string()
+        }
+    }
+
+    #[test]
+    fn builder_requires_an_account() {
+        let error = rejection(LaunchingParameterBuilder::new(options_with_memory(1024)));
+
+        assert!(error.contains("no account selected"));
+    }
+
+    #[test]
+    fn builder_rejects_an_account_without_minecraft_token() {
+        let error = rejection(LaunchingParameterBuilder::new(options_with_memory(1024)).account(account("")));
+
+        assert!(error.contains("has no minecraft token"));
+    }
+
+    #[test]
+    fn builder_rejects_memory_of_zero_or_less() {
+        for megabytes in [0, -512] {
+            let error = rejection(LaunchingParameterBuilder::new(options_with_memory(megabytes)).account(account("mc-token")));
+
+            assert!(error.contains("memory has to be greater than 0"));
+        }
+    }
+
+    #[test]
+    fn builder_accepts_valid_options() {
+        let parameter = LaunchingParameterBuilder::new(options_with_memory(1024)).account(account("mc-token")).build().unwrap();
+
+        assert_eq!(parameter.memory, 1024);
+        assert_eq!(parameter.auth_access_token, "mc-token");
+    }
+
+    fn library(json: serde_json::Value) -> Library {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn classpath_has_the_applying_libraries_once_and_the_client_jar_last() {
+        let data = test_folder("classpath");
+        let libraries_folder = data.join("libraries");
+        let client_jar = data.join("versions").join("1.20.1").join("1.20.1.jar");
+        let libraries = [
+            library(serde_json::json!({ "name": "org.ow2.asm:asm:9.6" })),
+            library(serde_json::json!({ "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4", "natives": { "linux": "natives-linux", "osx": "natives-osx", "windows": "natives-windows" } })),
+            library(serde_json::json!({ "name": "com.mojang:realms:1.10.22", "rules": [{ "action": "allow", "features": { "is_demo_user": true } }] })),
+            library(serde_json::json!({ "name": "org.ow2.asm:asm:9.6" })),
+            library(serde_json::json!({ "name": "com.mojang:brigadier:1.1.8" })),
+        ];
+
+        let classpath = build_classpath(&libraries, &libraries_folder, &client_jar, &HashSet::new(), &DownloadSource::default()).unwrap();
+
+        let separator = if cfg!(windows) { ";" } else { ":" };
+        let expected = [
+            libraries_folder.join("org/ow2/asm/asm/9.6/asm-9.6.jar"),
+            libraries_folder.join("com/mojang/brigadier/1.1.8/brigadier-1.1.8.jar"),
+            client_jar,
+        ].iter().map(|path| path.absolutize().unwrap().to_string_lossy().to_string()).collect::<Vec<_>>();
+        assert_eq!(classpath, expected.join(separator));
+    }
+
+    fn launch_context() -> LaunchContext {
+        LaunchContext {
+            auth_player_name: "Player".to_string(),
+            auth_uuid: "c06f8906-4c8a-4911-9c29-ea1dbd1aab82".to_string(),
+            auth_access_token: "access-token".to_string(),
+            user_type: "msa".to_string(),
+            version_name: "1.20.1".to_string(),
+            version_type: "release".to_string(),
+            game_directory: "/data/gameDir/prod".to_string(),
+            assets_root: "/data/assets".to_string(),
+            assets_index_name: "5".to_string(),
+            natives_directory: "/data/natives".to_string(),
+            classpath: "/data/libraries/a.jar:/data/versions/1.20.1/1.20.1.jar".to_string(),
+            classpath_separator: ":".to_string(),
+            ..LaunchContext::default()
+        }
+    }
+
+    #[test]
+    fn placeholders_of_the_arguments_are_substituted() {
+        let mut args: Vec<String> = [
+            "-Djava.library.path=${natives_directory}", "-Dminecraft.launcher.brand=${launcher_name}", "-cp", "${classpath}",
+            "--username", "${auth_player_name}", "--version", "${version_name}", "--gameDir", "${game_directory}",
+            "--assetsDir", "${assets_root}", "--assetIndex", "${assets_index_name}", "--uuid", "${auth_uuid}",
+            "--accessToken", "${auth_access_token}", "--userType", "${user_type}", "--versionType", "${version_type}"
+        ].iter().map(|arg| arg.to_string()).collect();
+
+        substitute_placeholders(&mut args, &launch_context()).unwrap();
+
+        assert_eq!(args, vec![
+            "-Djava.library.path=/data/natives", "-Dminecraft.launcher.brand=NoRiskClient", "-cp", "/data/libraries/a.jar:/data/versions/1.20.1/1.20.1.jar",
+            "--username", "Player", "--version", "1.20.1", "--gameDir", "/data/gameDir/prod",
+            "--assetsDir", "/data/assets", "--assetIndex", "5", "--uuid", "c06f8906-4c8a-4911-9c29-ea1dbd1aab82",
+            "--accessToken", "access-token", "--userType", "msa", "--versionType", "release"
+        ]);
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        let mut args = vec!["--demoName".to_string(), "${unknown_placeholder}".to_string(), "--name=${auth_player_name}-${unknown}".to_string()];
+
+        substitute_placeholders(&mut args, &launch_context()).unwrap();
+
+        assert_eq!(args, vec!["--demoName", "${unknown_placeholder}", "--name=Player-${unknown}"]);
+    }
+
+    fn launching_parameter(builder: impl FnOnce(LaunchingParameterBuilder) -> LaunchingParameterBuilder) -> LaunchingParameter {
+        let account = LoginData {
+            mc_token: "mc-token".to_string(),
+            access_token: "access-token".to_string(),
+            refresh_token: "refresh-token".to_string(),
+            uuid: "c06f8906-4c8a-4911-9c29-ea1dbd1aab82".to_string(),
+            username: "Player".to_string(),
+            norisk_token: "norisk-token".to_string(),
+            experimental_token: None,
+            mc_token_expires_at: None,
+            needs_relogin: false,
+        };
+        builder(LaunchingParameterBuilder::new(LauncherOptions::default()).account(account)).build().unwrap()
+    }
+
+    /// Arguments of the vanilla 1.20.1 profile
+    fn vanilla_profile() -> VersionProfile {
+        let feature_rule = |feature: &str| serde_json::json!([{ "action": "allow", "features": { feature: true } }]);
+        serde_json::from_value(serde_json::json!({
+            "id": "1.20.1", "type": "release", "mainClass": "net.minecraft.client.main.Main", "libraries": [],
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}", "--version", "${version_name}", "--gameDir", "${game_directory}",
+                    "--assetsDir", "${assets_root}", "--assetIndex", "${assets_index_name}", "--uuid", "${auth_uuid}",
+                    "--accessToken", "${auth_access_token}", "--userType", "${user_type}", "--versionType", "${version_type}",
+                    { "rules": feature_rule("is_demo_user"), "value": "--demo" },
+                    { "rules": feature_rule("has_custom_resolution"), "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"] },
+                    { "rules": feature_rule("is_quick_play_singleplayer"), "value": ["--quickPlaySingleplayer", "${quickPlaySingleplayer}"] }
+                ],
+                "jvm": [
+                    { "rules": [{ "action": "allow", "os": { "name": "osx" } }], "value": ["-XstartOnFirstThread"] },
+                    { "rules": [{ "action": "allow", "os": { "arch": "x86" } }], "value": "-Xss1M" },
+                    "-Djava.library.path=${natives_directory}", "-Dminecraft.launcher.brand=${launcher_name}", "-cp", "${classpath}"
+                ]
+            }
+        })).unwrap()
+    }
+
+    #[test]
+    fn vanilla_command_is_jvm_args_main_class_and_game_args() {
+        let parameter = launching_parameter(|builder| builder);
+        let features = build_feature_set(&parameter);
+
+        let command = build_launch_command(&vanilla_profile(), "norisk-token", &parameter, &features, None, &launch_context()).unwrap();
+
+        let main_class = command.iter().position(|arg| arg == "net.minecraft.client.main.Main").unwrap();
+        assert!(command[0].starts_with("-Xmx"));
+        assert!(command[..main_class].contains(&"-Djava.library.path=/data/natives".to_string()));
+        assert!(command[..main_class].contains(&"-Dminecraft.launcher.brand=NoRiskClient".to_string()));
+        assert_eq!(command[main_class - 2..main_class], ["-cp", "/data/libraries/a.jar:/data/versions/1.20.1/1.20.1.jar"]);
+        assert_eq!(command[main_class + 1..], [
+            "--username", "Player", "--version", "1.20.1", "--gameDir", "/data/gameDir/prod",
+            "--assetsDir", "/data/assets", "--assetIndex", "5", "--uuid", "c06f8906-4c8a-4911-9c29-ea1dbd1aab82",
+            "--accessToken", "access-token", "--userType", "msa", "--versionType", "release"
+        ]);
+    }
+
+    #[test]
+    fn profile_without_main_class_has_no_command() {
+        let mut profile = vanilla_profile();
+        profile.main_class = None;
+        let parameter = launching_parameter(|builder| builder);
+
+        assert!(build_launch_command(&profile, "norisk-token", &parameter, &HashSet::new(), None, &launch_context()).is_err());
+    }
+
+    fn features(parameter: &LaunchingParameter) -> Vec<String> {
+        let mut features = build_feature_set(parameter).into_iter().collect::<Vec<_>>();
+        features.sort();
+        features
+    }
+
+    #[test]
+    fn normal_launch_has_no_features() {
+        assert!(features(&launching_parameter(|builder| builder)).is_empty());
+    }
+
+    #[test]
+    fn features_follow_the_launching_parameter() {
+        assert_eq!(features(&launching_parameter(|builder| builder.demo(true))), vec!["is_demo_user"]);
+        assert_eq!(features(&launching_parameter(|builder| builder.quick_play(Some(QuickPlay::Server("play.norisk.gg".to_string()))))), vec!["is_quick_play_multiplayer"]);
+
+        let mut parameter = launching_parameter(|builder| builder.quick_play(Some(QuickPlay::World("New World".to_string()))));
+        parameter.width = Some(1280);
+        parameter.height = Some(720);
+        assert_eq!(features(&parameter), vec!["has_custom_resolution", "is_quick_play_singleplayer"]);
+    }
+
+    #[test]
+    fn resolution_needs_width_and_height() {
+        let mut parameter = launching_parameter(|builder| builder);
+        parameter.width = Some(1280);
+
+        assert!(features(&parameter).is_empty());
+    }
+
+    #[test]
+    fn tokens_are_replaced_by_the_placeholder() {
+        let tokens = ["access-token", "norisk-token"];
+
+        assert_eq!(redact_tokens("token:access-token:uuid", &tokens), "token:<redacted>:uuid");
+        assert_eq!(redact_tokens("-Dnorisk.token=norisk-token", &tokens), "-Dnorisk.token=<redacted>");
+        assert_eq!(redact_tokens("--accessToken", &tokens), "--accessToken");
+    }
+
+    #[test]
+    fn empty_tokens_are_ignored() {
+        assert_eq!(redact_tokens("-Xmx2G", &["", "norisk-token"]), "-Xmx2G");
+    }
+
+    const ASSET_INDEX: &[u8] = br#"{"objects":{}}"#;
+
+    /// Profile of a version which passes the access token as legacy session argument
+    fn legacy_session_profile() -> VersionProfile {
+        serde_json::from_value(serde_json::json!({
+            "id": "1.5.2", "type": "release", "mainClass": "net.minecraft.client.Minecraft", "libraries": [],
+            "minecraftArguments": "${auth_player_name} token:${auth_access_token}:${auth_uuid} --accessToken ${auth_access_token}",
+            "assetIndex": { "id": "pre-1.6", "sha1": sha1sum_bytes(ASSET_INDEX), "size": ASSET_INDEX.len(), "totalSize": 0, "url": "" }
+        })).unwrap()
+    }
+
+    /// Launching parameter with the data path in a test folder, which holds the asset index of the legacy profile
+    fn parameter_with_asset_index(name: &str) -> LaunchingParameter {
+        let data_path = test_folder(name);
+        let indexes_folder = data_path.join("assets").join("indexes");
+        std::fs::create_dir_all(&indexes_folder).unwrap();
+        std::fs::write(indexes_folder.join("pre-1.6.json"), ASSET_INDEX).unwrap();
+
+        let mut parameter = launching_parameter(|builder| builder);
+        parameter.data_path = data_path;
+        parameter.custom_java_args = "-Dcustom.session=mc-token".to_string();
+        parameter
+    }
+
+    #[test]
+    fn command_builder_passes_the_tokens() {
+        let profile = legacy_session_profile();
+        let parameter = parameter_with_asset_index("command-builder");
+        let asset_index = serde_json::from_slice::<AssetIndex>(ASSET_INDEX).unwrap();
+
+        let command = LaunchCommandBuilder::new(&profile, &parameter, "prod").build(&[], &asset_index, "mc-token", "norisk-token").unwrap();
+
+        assert!(command.contains(&"-Dnorisk.token=norisk-token".to_string()));
+        assert!(command.contains(&format!("token:mc-token:{}", parameter.auth_uuid)));
+        assert!(command.windows(2).any(|pair| pair == ["--accessToken", "mc-token"]));
+    }
+
+    #[tokio::test]
+    async fn dry_run_command_redacts_the_tokens() {
+        let profile = legacy_session_profile();
+        let parameter = parameter_with_asset_index("dry-run");
+
+        let command = dry_run_command(&profile, &parameter, "prod", "norisk-token").await.unwrap();
+
+        assert!(!command.contains("mc-token"));
+        assert!(!command.contains("norisk-token"));
+        assert!(command.contains("-Dnorisk.token=<redacted>"));
+        assert!(command.contains("-Dcustom.session=<redacted>"));
+        assert!(command.contains(&format!("token:<redacted>:{}", parameter.auth_uuid)));
+        assert!(command.contains("--accessToken <redacted>"));
+    }
+}
@@ -4,4 +4,12 @@ mod rule_interpreter;
 pub mod auth;
 pub mod prelauncher;
 pub mod progress;
-pub mod java;
\ No newline at end of file
+pub mod verify;
+pub mod forge;
+pub mod fabric;
+pub mod mods;
+pub mod java;
+pub mod cosmetics;
+pub mod installed;
+pub mod install_lock;
+pub mod servers;
\ No newline at end of file
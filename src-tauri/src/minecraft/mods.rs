@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use crate::app::api::{LoaderMod, ModSource};
+use crate::error::LauncherError;
+use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
+use crate::minecraft::version::LibraryDownloadInfo;
+use crate::utils::get_maven_artifact_path;
+
+/// Lists the jars of a mods folder which were installed by the launcher, every other jar belongs to the user
+const MANAGED_MODS_FILE: &str = ".launcher-managed.json";
+
+///
+/// A mod which is managed by the launcher
+///
+#[derive(Clone, Debug)]
+pub struct Mod {
+    /// Maven coordinate of the mod, e.g. gg.norisk:nrc-core:1.0.0
+    pub name: String,
+    pub url: String,
+    pub sha1: Option<String>,
+}
+
+impl Mod {
+    /// Resolves the download url of the loader mod from the repositories of the launch manifest
+    pub fn from_loader_mod(loader_mod: &LoaderMod, repositories: &BTreeMap<String, String>) -> Result<Self> {
+        match &loader_mod.source {
+            ModSource::Repository { repository, artifact, url } => {
+                let url = match url {
+                    Some(url) => url.clone(),
+                    None => {
+                        let repository_url = repositories.get(repository).ok_or_else(|| LauncherError::InvalidVersionProfile(format!("There is no repository specified with the name {}", repository)))?;
+                        format!("{}{}", repository_url, get_maven_artifact_path(artifact)?)
+                    }
+                };
+
+                Ok(Mod { name: artifact.clone(), url, sha1: None })
+            }
+        }
+    }
+
+    /// File name of the jar in the mods folder
+    pub fn file_name(&self) -> Result<String> {
+        let artifact_path = get_maven_artifact_path(&self.name)?;
+        Ok(artifact_path.rsplit('/').next().unwrap_or(&artifact_path).to_string())
+    }
+
+    fn download_info(&self) -> Result<LibraryDownloadInfo> {
+        Ok(LibraryDownloadInfo {
+            path: self.file_name()?,
+            sha1: self.sha1.clone(),
+            sha256: None,
+            size: None,
+            url: self.url.clone(),
+            fallback_url: None,
+            packed: false,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ManagedMods {
+    files: HashSet<String>,
+}
+
+impl ManagedMods {
+    async fn load(mods_dir: &Path) -> Result<Self> {
+        let path = mods_dir.join(MANAGED_MODS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_json::from_slice(&fs::read(path).await?)?)
+    }
+
+    async fn store(&self, mods_dir: &Path) -> Result<()> {
+        fs::write(mods_dir.join(MANAGED_MODS_FILE), serde_json::to_vec_pretty(self)?).await?;
+        Ok(())
+    }
+}
+
+/// True if the launcher tracks the mods it installed into the mods folder
+pub fn is_managed(mods_dir: &Path) -> bool {
+    mods_dir.join(MANAGED_MODS_FILE).exists()
+}
+
+/// Records files the launcher copied into the mods folder, the next sync removes them unless they are required
+pub async fn track_managed(mods_dir: &Path, files: Vec<String>) -> Result<()> {
+    let mut managed = ManagedMods::load(mods_dir).await?;
+    managed.files.extend(files);
+    managed.store(mods_dir).await
+}
+
+///
+/// Downloads the required mods into the mods folder and removes mods installed by an earlier sync which are no longer required.
+/// Jars added by the user are never touched.
+///
+pub async fn sync_mods(required: &[Mod], mods_dir: &Path, progress: Arc<impl ProgressReceiver>, cancellation: &CancellationToken) -> Result<()> {
+    fs::create_dir_all(mods_dir).await?;
+
+    let mut managed = ManagedMods::load(mods_dir).await?;
+    let mut required_files = HashSet::new();
+
+    for required_mod in required {
+        if cancellation.is_cancelled() {
+            return Err(LauncherError::Cancelled.into());
+        }
+
+        progress.progress_update(ProgressUpdate::set_label(format!("Checking mod {}", required_mod.name)));
+        required_mod.download_info()?.download(required_mod.name.clone(), mods_dir, progress.clone(), cancellation).await?;
+
+        let file_name = required_mod.file_name()?;
+        managed.files.insert(file_name.clone());
+        required_files.insert(file_name);
+
+        // the list is stored after every mod, so an aborted sync doesn't lose track of downloaded mods
+        managed.store(mods_dir).await?;
+    }
+
+    let outdated = managed.files.iter()
+        .filter(|file| !required_files.contains(*file))
+        .cloned()
+        .collect::<Vec<_>>();
+    for file in outdated {
+        let path = mods_dir.join(&file);
+        if path.exists() {
+            info!("Removing mod {} which is no longer required", file);
+            fs::remove_file(&path).await?;
+        }
+        managed.files.remove(&file);
+    }
+
+    managed.store(mods_dir).await?;
+    Ok(())
+}
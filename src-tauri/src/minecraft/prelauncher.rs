@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::path::Path;
 use std::ptr::null;
 use std::sync::{Mutex, Arc};
@@ -8,69 +9,255 @@ use tracing::*;
 use tokio::fs;
 use tokio::io::AsyncReadExt;
 
-use crate::app::api::{LoaderSubsystem, ModSource, LoaderMod, NoRiskLaunchManifest};
+use crate::app::api::{LoaderSubsystem, LoaderMod, NoRiskLaunchManifest};
 use crate::error::LauncherError;
 use crate::LAUNCHER_DIRECTORY;
-use crate::minecraft::launcher;
+use crate::minecraft::{fabric, forge, launcher, mods};
+use crate::minecraft::mods::Mod;
+use crate::minecraft::install_lock::InstallLock;
 use crate::minecraft::launcher::{LauncherData, LaunchingParameter};
-use crate::minecraft::progress::{get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps};
-use crate::minecraft::version::{VersionManifest, VersionProfile};
-use crate::utils::{download_file, get_maven_artifact_path};
+use crate::minecraft::progress::{InstallPhase, ProgressReceiver, ProgressUpdate};
+use crate::minecraft::version::{DownloadSource, VersionManifest, VersionProfile};
 
 ///
 /// Prelaunching client
 ///
 pub(crate) async fn launch<D: Send + Sync>(norisk_token: &str, launch_manifest: NoRiskLaunchManifest, launching_parameter: LaunchingParameter, additional_mods: Vec<LoaderMod>, progress: LauncherData<D>, window: Arc<Mutex<tauri::Window>>) -> Result<()> {
-    info!("Loading minecraft version manifest...");
-    let mc_version_manifest = VersionManifest::download().await?;
-
-    let build = &launch_manifest.build;
-    let subsystem = &launch_manifest.subsystem;
-
     progress.progress_update(ProgressUpdate::set_max());
     progress.progress_update(ProgressUpdate::SetProgress(0));
 
     let data_directory = launching_parameter.data_path.clone();
+    // held until the game is started, so another instance doesn't install into the same data folder meanwhile
+    let install_lock = InstallLock::acquire(&data_directory)?;
+    let game_dir = launching_parameter.game_directory(&launch_manifest.build.branch);
+    if !launching_parameter.isolate_game_dirs {
+        info!("Game directories are not isolated, using {:?}", game_dir);
+    }
+
+    progress.progress_update(ProgressUpdate::set_phase(InstallPhase::Mods));
+    let mods_dir = game_dir.join("mods");
+    // before the launcher tracked the mods it installed, every jar of the mods folder was replaced on launch
+    if !mods::is_managed(&mods_dir) {
+        clear_mods(&game_dir).await?;
+    }
+    let required_mods = required_mods(&launch_manifest, &additional_mods)?;
+    mods::sync_mods(&required_mods, &mods_dir, Arc::new(&progress), &launching_parameter.cancellation).await?;
+
+    copy_custom_mods(&data_directory, &game_dir, &launch_manifest, &progress).await?;
+
+    progress.progress_update(ProgressUpdate::set_phase(InstallPhase::Profile));
+
+    if let LoaderSubsystem::Forge { forge_version: Some(forge_version), .. } = &launch_manifest.subsystem {
+        let java_bin = launcher::resolve_java_binary(&data_directory, launch_manifest.build.jre_version, &launching_parameter.custom_java_path, &progress, &launching_parameter.cancellation).await?;
+        forge::install_forge(&launch_manifest.build.mc_version, forge_version, &data_directory, &java_bin, &launching_parameter.download_source, &progress, &launching_parameter.cancellation).await?;
+    }
+
+    let version = load_version_profile(&launch_manifest, &data_directory, &launching_parameter.download_source).await?;
+
+    info!("Launching {}...", launch_manifest.build.branch);
 
-    // Copy retrieve and copy mods from manifest
-    clear_mods(&data_directory, &launch_manifest).await?;
-    let installed_mods = retrieve_and_copy_mods(&data_directory, &launch_manifest, &launch_manifest.mods, &progress, &Vec::new(), &window).await?;
-    retrieve_and_copy_mods(&data_directory, &launch_manifest, &additional_mods, &progress, &installed_mods, &window).await?;
+    launcher::launch(norisk_token,&data_directory, launch_manifest, version, launching_parameter, progress, window, install_lock).await?;
+    Ok(())
+}
+
+/// Loads the version profile of the launch manifest's subsystem including the version it inherits from
+pub(crate) async fn load_version_profile(launch_manifest: &NoRiskLaunchManifest, data_directory: &Path, download_source: &DownloadSource) -> Result<VersionProfile> {
+    resolve_version_profile(launch_manifest, data_directory, download_source, false).await
+}
+
+///
+/// Like [load_version_profile], but nothing is installed or written to the data folder. Cached profiles are used if present, missing ones are only fetched.
+/// A Forge build which is not installed yet resolves to its vanilla version, the libraries of Forge are only known after running its installer.
+///
+pub(crate) async fn peek_version_profile(launch_manifest: &NoRiskLaunchManifest, data_directory: &Path, download_source: &DownloadSource) -> Result<VersionProfile> {
+    resolve_version_profile(launch_manifest, data_directory, download_source, true).await
+}
 
-    copy_custom_mods(&data_directory, &launch_manifest, &progress).await?;
+async fn resolve_version_profile(launch_manifest: &NoRiskLaunchManifest, data_directory: &Path, download_source: &DownloadSource, dry_run: bool) -> Result<VersionProfile> {
+    let build = &launch_manifest.build;
+    let versions_folder = data_directory.join("versions");
+
+    info!("Loading minecraft version manifest...");
+    let mc_version_manifest = if dry_run {
+        VersionManifest::peek_cached(&versions_folder, download_source).await?
+    } else {
+        VersionManifest::load_cached(&versions_folder, download_source).await?
+    };
 
     info!("Loading version profile...");
-    let manifest_url = match subsystem {
-        LoaderSubsystem::Fabric { manifest, .. } => manifest
-            .replace("{MINECRAFT_VERSION}", &build.mc_version)
-            .replace("{FABRIC_LOADER_VERSION}", &build.fabric_loader_version),
-        LoaderSubsystem::Forge { manifest, .. } => manifest.clone()
+    let mut version = match &launch_manifest.subsystem {
+        LoaderSubsystem::Fabric { manifest, .. } if manifest.is_empty() => load_loader_profile(&fabric::FABRIC, &build.mc_version, &build.fabric_loader_version, &versions_folder, dry_run).await?,
+        LoaderSubsystem::Quilt { loader_version, .. } => load_loader_profile(&fabric::QUILT, &build.mc_version, loader_version, &versions_folder, dry_run).await?,
+        LoaderSubsystem::Fabric { manifest, .. } => {
+            let manifest_url = manifest
+                .replace("{MINECRAFT_VERSION}", &build.mc_version)
+                .replace("{FABRIC_LOADER_VERSION}", &build.fabric_loader_version);
+            let profile_id = format!("fabric-loader-{}-{}", build.fabric_loader_version, build.mc_version);
+            load_profile(&manifest_url, &profile_id, None, &versions_folder, dry_run).await?
+        }
+        LoaderSubsystem::Forge { forge_version: Some(forge_version), .. } => match forge::load_installed_profile(&versions_folder, &build.mc_version, forge_version).await {
+            Ok(version) => version,
+            Err(e) if dry_run => {
+                warn!("Forge {} is not installed, resolving the vanilla version {}: {:?}", forge_version, build.mc_version, e);
+                let manifest_version = mc_version_manifest.versions.iter()
+                    .find(|x| x.id == build.mc_version)
+                    .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("unable to find version manifest {}", build.mc_version)))?;
+                load_profile(&manifest_version.url, &manifest_version.id, manifest_version.sha1.as_deref(), &versions_folder, dry_run).await?
+            }
+            Err(e) => return Err(e)
+        },
+        LoaderSubsystem::Forge { manifest, .. } => load_profile(manifest, &format!("forge-{}-{}", build.branch, build.mc_version), None, &versions_folder, dry_run).await?
     };
-    let mut version = VersionProfile::load(&manifest_url).await?;
+    resolve_profile(&mut version, &build.mc_version, &mc_version_manifest, &versions_folder, dry_run).await?;
 
+    Ok(version)
+}
+
+/// Loads the version profile through the cache of the versions folder, a dry run doesn't write to the cache
+async fn load_profile(url: &String, id: &str, sha1: Option<&str>, versions_folder: &Path, dry_run: bool) -> Result<VersionProfile> {
+    if dry_run {
+        VersionProfile::peek_cached(url, id, sha1, versions_folder).await
+    } else {
+        VersionProfile::load_cached(url, id, sha1, versions_folder).await
+    }
+}
+
+/// Generated profile of a loader distributed like Fabric, a dry run only generates a missing profile in memory
+async fn load_loader_profile(meta: &fabric::LoaderMeta, mc_version: &str, loader_version: &str, versions_folder: &Path, dry_run: bool) -> Result<VersionProfile> {
+    if dry_run {
+        fabric::peek_fabric(meta, mc_version, loader_version, versions_folder).await
+    } else {
+        fabric::install_fabric(meta, mc_version, loader_version, versions_folder).await
+    }
+}
+
+/// Resolves the inheritance of the version profile and makes sure it ends up with an asset index
+async fn resolve_profile(version: &mut VersionProfile, mc_version: &str, mc_version_manifest: &VersionManifest, versions_folder: &Path, dry_run: bool) -> Result<()> {
+    resolve_inheritance(version, mc_version_manifest, Some(versions_folder), dry_run).await?;
+    ensure_asset_index(version, mc_version, mc_version_manifest, versions_folder, dry_run).await
+}
+
+///
+/// Some broken modloader profiles end up without an asset index even after merging, which launches the game without any assets.
+/// The asset index is taken from the vanilla version of the profile in that case (the inherited version or the minecraft version of the build),
+/// if there is none the profile is rejected.
+///
+async fn ensure_asset_index(version: &mut VersionProfile, mc_version: &str, mc_version_manifest: &VersionManifest, versions_folder: &Path, dry_run: bool) -> Result<()> {
+    if version.asset_index_location.is_none() {
+        let vanilla_id = version.inherits_from.clone().unwrap_or_else(|| mc_version.to_string());
+        let manifest_version = mc_version_manifest.versions.iter().find(|x| x.id == vanilla_id);
+
+        match manifest_version {
+            // the vanilla profile itself has no asset index, loading it again won't help
+            Some(manifest_version) if manifest_version.id != version.id => {
+                warn!("Version profile {} has no asset index, using the one of {}", version.id, vanilla_id);
+                let vanilla = load_profile(&manifest_version.url, &vanilla_id, manifest_version.sha1.as_deref(), versions_folder, dry_run).await?;
+                version.asset_index_location = vanilla.asset_index_location;
+                if version.assets.is_none() {
+                    version.assets = vanilla.assets;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let asset_index_location = match &version.asset_index_location {
+        Some(asset_index_location) => asset_index_location,
+        None => return Err(LauncherError::InvalidVersionProfile("no asset index after inheritance resolution".to_string()).into())
+    };
+
+    // the assets id is the id of the asset index in every vanilla profile
+    if version.assets.is_none() {
+        version.assets = Some(asset_index_location.id.clone());
+    }
+
+    Ok(())
+}
+
+/// Merges the version profile with the version it inherits from, if any.
+/// The inherited profile is cached in the versions folder when one is given, a dry run only reads the cache.
+pub(crate) async fn resolve_inheritance(version: &mut VersionProfile, mc_version_manifest: &VersionManifest, versions_folder: Option<&Path>, dry_run: bool) -> Result<()> {
     if let Some(inherited_version) = &version.inherits_from {
-        let url = mc_version_manifest.versions
+        let manifest_version = mc_version_manifest.versions
             .iter()
             .find(|x| &x.id == inherited_version)
-            .map(|x| &x.url)
             .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("unable to find inherited version manifest {}", inherited_version)))?;
+        let url = &manifest_version.url;
 
         debug!("Determined {}'s download url to be {}", inherited_version, url);
-        info!("Downloading inherited version {}...", inherited_version);
+        info!("Loading inherited version {}...", inherited_version);
 
-        let parent_version = VersionProfile::load(url).await?;
+        let parent_version = match versions_folder {
+            Some(versions_folder) => load_profile(url, inherited_version, manifest_version.sha1.as_deref(), versions_folder, dry_run).await?,
+            None => VersionProfile::load(url).await?
+        };
 
         version.merge(parent_version)?;
     }
 
-    info!("Launching {}...", launch_manifest.build.branch);
-
-    launcher::launch(norisk_token,&data_directory, launch_manifest, version, launching_parameter, progress, window).await?;
     Ok(())
 }
 
-pub(crate) async fn clear_mods(data: &Path, manifest: &NoRiskLaunchManifest) -> Result<()> {
-    let mods_path = data.join("gameDir").join(&manifest.build.branch).join("mods");
+///
+/// Validates a version profile json without downloading any of its files.
+/// Returns every problem found, an empty list means the profile is valid.
+///
+pub(crate) async fn validate_profile(json: &str) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    let mut version = match serde_json::from_str::<VersionProfile>(json) {
+        Ok(version) => version,
+        Err(e) => {
+            problems.push(format!("unable to parse version profile: {}", e));
+            return Ok(problems);
+        }
+    };
+
+    if version.inherits_from.is_some() {
+        let mc_version_manifest = VersionManifest::download(&DownloadSource::default()).await?;
+
+        if let Err(e) = resolve_inheritance(&mut version, &mc_version_manifest, None, false).await {
+            problems.push(format!("unable to resolve inheritance: {}", e));
+        }
+    }
+
+    if version.main_class.is_none() {
+        problems.push("main class unspecified".to_string());
+    }
+    if version.asset_index_location.is_none() {
+        problems.push("asset index unspecified".to_string());
+    }
+    if version.downloads.as_ref().and_then(|x| x.client.as_ref()).is_none() {
+        problems.push("no client JAR download specified".to_string());
+    }
+
+    for library in &version.libraries {
+        if let Some(natives) = &library.natives {
+            let classifiers = library.downloads.as_ref().and_then(|x| x.classifiers.as_ref());
+            for (os, classifier) in natives {
+                if !classifiers.map_or(false, |x| x.contains_key(classifier)) {
+                    problems.push(format!("library {} is missing the natives classifier {} for {}", library.name, classifier, os));
+                }
+            }
+            continue;
+        }
+
+        if let Err(e) = library.get_library_download(&DownloadSource::default()) {
+            problems.push(format!("library {} does not resolve to a download: {}", library.name, e));
+        }
+    }
+
+    let mut arguments = Vec::new();
+    let features = HashSet::new();
+    if let Err(e) = version.arguments.add_game_args_to_vec(&mut arguments, &features) {
+        problems.push(format!("invalid game arguments: {}", e));
+    }
+
+    Ok(problems)
+}
+
+pub(crate) async fn clear_mods(game_dir: &Path) -> Result<()> {
+    let mods_path = game_dir.join("mods");
 
     if !mods_path.exists() {
         return Ok(());
@@ -86,91 +273,91 @@ pub(crate) async fn clear_mods(data: &Path, manifest: &NoRiskLaunchManifest) ->
     Ok(())
 }
 
-pub async fn retrieve_and_copy_mods(data: &Path, manifest: &NoRiskLaunchManifest, mods: &Vec<LoaderMod>, progress: &impl ProgressReceiver, already_installed_mods: &Vec<LoaderMod>, window: &Arc<Mutex<tauri::Window>>) -> Result<Vec<LoaderMod>> {
-    let mod_cache_path = data.join("mod_cache");
-    let mods_path = data.join("gameDir").join(&manifest.build.branch).join("mods");
-
-    fs::create_dir_all(&mod_cache_path).await?;
-    fs::create_dir_all(&mods_path).await?;
-
-    let mut installed_mods: Vec<LoaderMod> = Vec::new();
-
-    // Download and copy mods
-    let max = get_max(mods.len());
-
-    for (mod_idx, current_mod) in mods.iter().enumerate() {
-        // Skip mods that are not needed
-        if !current_mod.required && !current_mod.enabled {
+///
+/// Mods of the launch manifest and the additional mods selected by the user which are required or enabled.
+/// An additional mod with the same slug as a mod of the manifest is skipped.
+///
+fn required_mods(manifest: &NoRiskLaunchManifest, additional_mods: &[LoaderMod]) -> Result<Vec<Mod>> {
+    let mut selected: Vec<&LoaderMod> = Vec::new();
+    for loader_mod in manifest.mods.iter().chain(additional_mods.iter()) {
+        if !loader_mod.required && !loader_mod.enabled {
             continue;
         }
 
-        if already_installed_mods.iter().any(|loader_mod| {
-            return loader_mod.is_same_slug(current_mod);
-        }) {
-            let already_installed = already_installed_mods.iter().find(|&loader_mod| {
-                return loader_mod.is_same_slug(current_mod);
-            }).unwrap();
-            println!("Skipping Mod {:?} cuz {:?} is already installed",current_mod,already_installed);
+        if let Some(installed) = selected.iter().find(|selected_mod| selected_mod.is_same_slug(loader_mod)) {
+            debug!("Skipping mod {:?}, {:?} is already installed", loader_mod, installed);
             continue;
         }
-
-        progress.progress_update(ProgressUpdate::set_label(format!("Downloading recommended mod {}", current_mod.name)));
-
-        let current_mod_path = mod_cache_path.join(current_mod.source.get_path()?);
-
-        // Do we need to download the mod?
-        if !current_mod_path.exists() {
-            // Make sure that the parent directory exists
-            fs::create_dir_all(&current_mod_path.parent().unwrap()).await?;
-
-            match &current_mod.source {
-                ModSource::Repository { repository, artifact, url } => {
-                    let mut download_url: String = "".to_owned();
-                    if (url.clone().is_some()) {
-                        download_url = url.clone().unwrap();
-                    } else {
-                        let repository_url = manifest.repositories.get(repository).ok_or_else(|| LauncherError::InvalidVersionProfile(format!("There is no repository specified with the name {}", repository)))?;
-                        let maven_artifact_path = get_maven_artifact_path(artifact)?;
-                        download_url = format!("{}{}", repository_url, maven_artifact_path);
-                    }
-
-                    println!("downloading mod {} from {}", artifact, download_url);
-
-                    let retrieved_bytes = download_file(&download_url, |a, b| {
-                        progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskClientMods, get_progress(mod_idx, a, b), max));
-                    }).await?;
-
-                    fs::write(&current_mod_path, retrieved_bytes).await?;
-                }
-                _ => {}
-            }
-        }
-
-        // Copy the mod.
-        fs::copy(&current_mod_path, mods_path.join(format!("{}.jar", current_mod.name.replace(".jar","")))).await?;
-
-        println!("Installed Mod {:?}",current_mod);
-        installed_mods.push(current_mod.clone())
+        selected.push(loader_mod);
     }
 
-    Ok(installed_mods)
+    selected.into_iter()
+        .map(|loader_mod| Mod::from_loader_mod(loader_mod, &manifest.repositories))
+        .collect()
 }
 
-pub async fn copy_custom_mods(data: &Path, manifest: &NoRiskLaunchManifest, progress: &impl ProgressReceiver) -> Result<()> {
+pub async fn copy_custom_mods(data: &Path, game_dir: &Path, manifest: &NoRiskLaunchManifest, progress: &impl ProgressReceiver) -> Result<()> {
     let mod_cache_path = data.join("custom_mods").join(format!("{}-{}", manifest.build.branch, manifest.build.mc_version));
-    let mods_path = data.join("gameDir").join(&manifest.build.branch).join("mods");
+    let mods_path = game_dir.join("mods");
 
     fs::create_dir_all(&mod_cache_path).await?;
     fs::create_dir_all(&mods_path).await?;
 
     // Copy all mods from custom_mods to mods
+    let mut copied_mods = Vec::new();
     let mut mods_read = fs::read_dir(&mod_cache_path).await?;
     while let Some(entry) = mods_read.next_entry().await? {
         if entry.file_type().await?.is_file() {
             progress.progress_update(ProgressUpdate::set_label(format!("Copied custom mod {}", entry.file_name().to_str().unwrap_or_default())));
             fs::copy(entry.path(), mods_path.join(entry.file_name())).await?;
+            copied_mods.push(entry.file_name().to_string_lossy().to_string());
         }
     }
 
-    Ok(())
+    // the next sync removes them again, so custom mods deleted in the meantime don't stay in the mods folder
+    mods::track_managed(&mods_path, copied_mods).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::test_util::test_folder;
+
+    use super::*;
+
+    fn profile(json: serde_json::Value) -> VersionProfile {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn profile_without_asset_index_is_rejected() {
+        let mut version = profile(serde_json::json!({ "id": "broken-loader", "type": "release", "libraries": [] }));
+        let manifest = VersionManifest { versions: Vec::new() };
+
+        let err = ensure_asset_index(&mut version, "1.20.1", &manifest, &test_folder("versions-rejected"), true).await.unwrap_err();
+
+        assert!(err.to_string().contains("no asset index after inheritance resolution"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn missing_asset_index_is_taken_from_the_vanilla_profile() {
+        let folder = test_folder("versions-recovered");
+        let asset_index = serde_json::json!({
+            "id": "5", "sha1": "f9df1f4a8a4cd0bd1fd3a2aa2b4a2b0e8a4e4ecb", "size": 410722, "totalSize": 622411213,
+            "url": "https://piston-meta.mojang.com/v1/packages/f9df1f4a8a4cd0bd1fd3a2aa2b4a2b0e8a4e4ecb/5.json"
+        });
+        std::fs::create_dir_all(folder.join("1.20.1")).unwrap();
+        std::fs::write(folder.join("1.20.1").join("1.20.1.json"), serde_json::json!({
+            "id": "1.20.1", "type": "release", "libraries": [], "assetIndex": asset_index, "assets": "5"
+        }).to_string()).unwrap();
+        let manifest: VersionManifest = serde_json::from_value(serde_json::json!({ "versions": [{
+            "id": "1.20.1", "type": "release", "url": "https://piston-meta.mojang.com/v1/packages/1.20.1.json",
+            "time": "2023-06-12T13:25:51+00:00", "releaseTime": "2023-06-12T13:25:51+00:00"
+        }] })).unwrap();
+        let mut version = profile(serde_json::json!({ "id": "broken-loader", "type": "release", "libraries": [] }));
+
+        ensure_asset_index(&mut version, "1.20.1", &manifest, &folder, true).await.unwrap();
+
+        assert_eq!(version.asset_index_location.map(|location| location.id), Some("5".to_string()));
+        assert_eq!(version.assets.as_deref(), Some("5"));
+    }
 }
@@ -1,6 +1,18 @@
 use core::convert::AsRef;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
+use anyhow::Result;
 use serde::Serialize;
+use tracing::*;
+
+use crate::utils::DownloadStatsSnapshot;
 
 #[derive(Debug)]
 pub enum ProgressUpdateSteps {
@@ -12,6 +24,27 @@ pub enum ProgressUpdateSteps {
     VerifyAssets,
 }
 
+/// Stages of a launch in the order they are run, announced with ProgressUpdate::SetPhase
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum InstallPhase {
+    Mods,
+    Profile,
+    Java,
+    ClientJar,
+    /// Includes extracting the natives
+    Libraries,
+    Assets,
+}
+
+impl InstallPhase {
+    const ALL: [InstallPhase; 6] = [InstallPhase::Mods, InstallPhase::Profile, InstallPhase::Java, InstallPhase::ClientJar, InstallPhase::Libraries, InstallPhase::Assets];
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
 pub fn get_progress(idx: usize, curr: u64, max: u64) -> u64 {
     idx as u64 * 100 + (curr * 100 / max.max(1))
 }
@@ -46,6 +79,20 @@ pub enum ProgressUpdate {
     SetProgress(u64),
     #[serde(rename = "label")] 
     SetLabel(String),
+    #[serde(rename = "downloadStats")]
+    SetDownloadStats(DownloadStatsSnapshot),
+    #[serde(rename = "bytes")]
+    SetBytes { downloaded: u64, total: u64 },
+    /// Something went wrong, but the task is able to continue
+    #[serde(rename = "warning")]
+    Warning(String),
+    #[serde(rename = "completed")]
+    Completed,
+    #[serde(rename = "failed")]
+    Failed(String),
+    /// A new phase has begun, index starts at 0
+    #[serde(rename = "phase")]
+    SetPhase { phase: InstallPhase, index: usize, count: usize },
 }
 
 const PER_STEP: u64 = 1024;
@@ -65,9 +112,410 @@ impl ProgressUpdate {
     pub fn set_label<S: AsRef<str>>(str: S) -> Self {
         return Self::SetLabel(str.as_ref().to_owned());
     }
+    pub fn set_bytes(downloaded: u64, total: u64) -> Self {
+        Self::SetBytes { downloaded, total }
+    }
+    pub fn warning<S: AsRef<str>>(str: S) -> Self {
+        Self::Warning(str.as_ref().to_owned())
+    }
+    pub fn failed<S: AsRef<str>>(str: S) -> Self {
+        Self::Failed(str.as_ref().to_owned())
+    }
+    pub fn set_phase(phase: InstallPhase) -> Self {
+        Self::SetPhase { phase, index: phase.index(), count: InstallPhase::ALL.len() }
+    }
+}
+
+/// Minimum time between two updates of the [WeightedProgress]
+const WEIGHTED_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+///
+/// Progress weighted by the downloaded bytes instead of the downloaded files, so a thousand small assets
+/// don't move the bar as far as the client jar. Knows the bytes to download from the install plan,
+/// the downloads report their transferred bytes to the progress receiver of the launch, which emits the progress throttled.
+///
+pub struct WeightedProgress {
+    total: AtomicU64,
+    transferred: AtomicU64,
+    throttle: Mutex<WeightedThrottle>,
+}
+
+struct WeightedThrottle {
+    last_emitted: Option<Instant>,
+    /// An update was held back, it is emitted with the next update
+    pending: bool,
+}
+
+impl WeightedProgress {
+    pub const fn new() -> Self {
+        WeightedProgress {
+            total: AtomicU64::new(0),
+            transferred: AtomicU64::new(0),
+            throttle: Mutex::new(WeightedThrottle { last_emitted: None, pending: false }),
+        }
+    }
+
+    /// Starts weighting with the bytes which are going to be downloaded, nothing to download keeps the progress by files
+    pub fn start(&self, total_bytes: u64) {
+        self.transferred.store(0, Ordering::SeqCst);
+        self.total.store(total_bytes, Ordering::SeqCst);
+    }
+
+    /// Stops weighting, e.g. when the downloads are done
+    pub fn finish(&self) {
+        self.total.store(0, Ordering::SeqCst);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.total.load(Ordering::SeqCst) > 0
+    }
+
+    /// Called with the bytes the downloads reported by [ProgressReceiver::add_transferred_bytes]
+    pub fn add_bytes(&self, delta: u64) {
+        self.transferred.fetch_add(delta, Ordering::SeqCst);
+    }
+
+    ///
+    /// Overall progress on the scale of [ProgressUpdate::set_max], none if it was emitted too recently.
+    /// A held back update isn't lost, [Self::pending_update] returns the latest progress with the next update.
+    ///
+    pub fn throttled_update(&self) -> Option<ProgressUpdate> {
+        let total = self.total.load(Ordering::SeqCst);
+        if total == 0 {
+            return None;
+        }
+
+        let mut throttle = self.throttle.lock().ok()?;
+        let now = Instant::now();
+        if throttle.last_emitted.map_or(false, |last| now.duration_since(last) < WEIGHTED_PROGRESS_INTERVAL) {
+            throttle.pending = true;
+            return None;
+        }
+        throttle.last_emitted = Some(now);
+        throttle.pending = false;
+
+        Some(self.progress(total))
+    }
+
+    /// Latest progress if an update was held back since the last emitted one
+    pub fn pending_update(&self) -> Option<ProgressUpdate> {
+        let total = self.total.load(Ordering::SeqCst);
+        let mut throttle = self.throttle.lock().ok()?;
+        if total == 0 || !throttle.pending {
+            return None;
+        }
+        throttle.last_emitted = Some(Instant::now());
+        throttle.pending = false;
+
+        Some(self.progress(total))
+    }
+
+    fn progress(&self, total: u64) -> ProgressUpdate {
+        // the java runtime and retried downloads aren't part of the plan, so the bytes may exceed it
+        let transferred = self.transferred.load(Ordering::SeqCst).min(total);
+        let max = ProgressUpdateSteps::len() as u64 * PER_STEP;
+        ProgressUpdate::SetProgress((transferred as u128 * max as u128 / total as u128) as u64)
+    }
+}
+
+/// Minimum time between two flushes of the [ProgressThrottle]
+const PROGRESS_THROTTLE_INTERVAL: Duration = Duration::from_millis(50);
+
+///
+/// Coalesces frequent progress updates, so byte level progress doesn't flood a slow receiver like the Tauri IPC bridge.
+/// Progress, bytes and download stats keep only their latest value and are flushed at most every 50ms,
+/// every other update (labels, phases, completion) flushes them and is passed through immediately.
+/// Held back updates are flushed by a timer, so the latest value arrives even if no further update follows.
+///
+pub struct ProgressThrottle {
+    state: Mutex<ThrottleState>,
+}
+
+struct ThrottleState {
+    last_flush: Option<Instant>,
+    /// A timer is going to flush the held back updates
+    flush_scheduled: bool,
+    progress: Option<ProgressUpdate>,
+    bytes: Option<ProgressUpdate>,
+    download_stats: Option<ProgressUpdate>,
+}
+
+impl ThrottleState {
+    fn take_pending(&mut self) -> Vec<ProgressUpdate> {
+        [self.progress.take(), self.bytes.take(), self.download_stats.take()].into_iter().flatten().collect()
+    }
+}
+
+impl ProgressThrottle {
+    pub const fn new() -> Self {
+        ProgressThrottle {
+            state: Mutex::new(ThrottleState { last_flush: None, flush_scheduled: false, progress: None, bytes: None, download_stats: None }),
+        }
+    }
+
+    ///
+    /// Returns the updates which have to be emitted now, in order. If the update is held back and no flush is scheduled yet,
+    /// the held back updates are passed to `flush` once the interval is over.
+    ///
+    pub fn coalesce(&'static self, update: ProgressUpdate, flush: impl FnOnce(Vec<ProgressUpdate>) + Send + 'static) -> Vec<ProgressUpdate> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return vec![update]
+        };
+        let now = Instant::now();
+
+        let immediate = match update {
+            ProgressUpdate::SetProgress(_) => { state.progress = Some(update); None }
+            ProgressUpdate::SetBytes { .. } => { state.bytes = Some(update); None }
+            ProgressUpdate::SetDownloadStats(_) => { state.download_stats = Some(update); None }
+            update => Some(update)
+        };
+
+        let due = state.last_flush.map_or(true, |last| now.duration_since(last) >= PROGRESS_THROTTLE_INTERVAL);
+        if immediate.is_none() && !due {
+            if !state.flush_scheduled {
+                state.flush_scheduled = true;
+                let delay = state.last_flush.map_or(Duration::ZERO, |last| PROGRESS_THROTTLE_INTERVAL.saturating_sub(now.duration_since(last)));
+                self.schedule_flush(delay, flush);
+            }
+            return Vec::new();
+        }
+
+        state.last_flush = Some(now);
+        let mut updates = state.take_pending();
+        updates.extend(immediate);
+        updates
+    }
+
+    fn schedule_flush(&'static self, delay: Duration, flush: impl FnOnce(Vec<ProgressUpdate>) + Send + 'static) {
+        let flush_pending = move || {
+            let updates = match self.state.lock() {
+                Ok(mut state) => {
+                    state.flush_scheduled = false;
+                    state.last_flush = Some(Instant::now());
+                    state.take_pending()
+                }
+                Err(_) => return
+            };
+            // an update passed through meanwhile may already have flushed everything
+            if !updates.is_empty() {
+                flush(updates);
+            }
+        };
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    flush_pending();
+                });
+            }
+            Err(_) => {
+                thread::spawn(move || {
+                    thread::sleep(delay);
+                    flush_pending();
+                });
+            }
+        }
+    }
 }
 
 pub trait ProgressReceiver {
     fn progress_update(&self, update: ProgressUpdate);
+
+    /// Bytes a download transferred since its last report, only receivers weighting the progress by bytes use them
+    fn add_transferred_bytes(&self, _bytes: u64) {}
+}
+
+impl<P: ProgressReceiver + ?Sized> ProgressReceiver for &P {
+    fn progress_update(&self, update: ProgressUpdate) {
+        (**self).progress_update(update)
+    }
+
+    fn add_transferred_bytes(&self, bytes: u64) {
+        (**self).add_transferred_bytes(bytes)
+    }
+}
+
+///
+/// Wraps the progress callback of a single download, so the bytes it transfers are reported to the receiver.
+/// The first call only marks where the download starts, bytes resumed from a partial file were transferred before.
+///
+pub fn track_transfer<'a, P: ProgressReceiver + ?Sized>(progress: &'a P, on_progress: impl Fn(u64, u64) + 'a) -> impl Fn(u64, u64) + 'a {
+    let reported = AtomicU64::new(u64::MAX);
+    move |downloaded, total| {
+        let previous = reported.swap(downloaded, Ordering::Relaxed);
+        // a download which starts over reports a smaller value, the bytes it transfers again are counted again
+        if previous != u64::MAX && downloaded > previous {
+            progress.add_transferred_bytes(downloaded - previous);
+        }
+        on_progress(downloaded, total)
+    }
+}
+
+/// Forwards every progress update to all of its receivers
+#[derive(Default)]
+pub struct MultiProgressReceiver {
+    receivers: Vec<Box<dyn ProgressReceiver + Send + Sync>>,
+}
+
+impl MultiProgressReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, receiver: impl ProgressReceiver + Send + Sync + 'static) {
+        self.receivers.push(Box::new(receiver));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receivers.is_empty()
+    }
+}
+
+impl ProgressReceiver for MultiProgressReceiver {
+    fn progress_update(&self, update: ProgressUpdate) {
+        for receiver in &self.receivers {
+            receiver.progress_update(update.clone());
+        }
+    }
+
+    fn add_transferred_bytes(&self, bytes: u64) {
+        for receiver in &self.receivers {
+            receiver.add_transferred_bytes(bytes);
+        }
+    }
+}
+
+/// Writes every progress update as a JSON line to a file or named pipe,
+/// so external tools (overlays, scripts) are able to follow the launch progress.
+/// The file is opened and written by its own thread, a pipe nobody reads must not block the launch.
+pub struct FileProgressReceiver {
+    lines: SyncSender<String>,
+}
+
+/// Lines which are kept while the writer is blocked, further updates are dropped
+const PROGRESS_OUTPUT_BUFFER: usize = 1024;
+
+impl FileProgressReceiver {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (lines, received_lines) = mpsc::sync_channel::<String>(PROGRESS_OUTPUT_BUFFER);
+
+        thread::Builder::new()
+            .name("progress-output".to_string())
+            .spawn(move || {
+                // opening a named pipe blocks until it is read
+                let mut file = match OpenOptions::new().create(true).append(true).open(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        error!("Unable to open progress output {:?}: {:?}", path, e);
+                        return;
+                    }
+                };
+
+                // ends once the receiver is dropped
+                for line in received_lines {
+                    if writeln!(file, "{}", line).and_then(|_| file.flush()).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(FileProgressReceiver { lines })
+    }
+}
+
+impl ProgressReceiver for FileProgressReceiver {
+    fn progress_update(&self, update: ProgressUpdate) {
+        let line = match serde_json::to_string(&update) {
+            Ok(line) => line,
+            Err(_) => return
+        };
+
+        // progress output is best effort, a closed or full pipe must never interrupt the launch
+        let _ = self.lines.try_send(line);
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn progress_values(updates: &[ProgressUpdate]) -> Vec<u64> {
+        updates.iter().filter_map(|update| match update {
+            ProgressUpdate::SetProgress(progress) => Some(*progress),
+            _ => None
+        }).collect()
+    }
+
+    #[tokio::test]
+    async fn rapid_updates_are_collapsed() {
+        static THROTTLE: ProgressThrottle = ProgressThrottle::new();
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+
+        let mut emitted = Vec::new();
+        for progress in 0..1000 {
+            let flushed = flushed.clone();
+            emitted.extend(THROTTLE.coalesce(ProgressUpdate::SetProgress(progress), move |updates| flushed.lock().unwrap().extend(updates)));
+        }
+        tokio::time::sleep(PROGRESS_THROTTLE_INTERVAL * 3).await;
+
+        // the first update is emitted right away, the latest one by the timer
+        assert!(progress_values(&emitted).len() < 10, "{} updates were emitted", emitted.len());
+        assert_eq!(progress_values(&flushed.lock().unwrap()).last(), Some(&999));
+    }
+
+    #[tokio::test]
+    async fn labels_flush_the_held_back_progress() {
+        static THROTTLE: ProgressThrottle = ProgressThrottle::new();
+
+        THROTTLE.coalesce(ProgressUpdate::SetProgress(1), |_| {});
+        assert!(THROTTLE.coalesce(ProgressUpdate::SetProgress(2), |_| {}).is_empty());
+        let updates = THROTTLE.coalesce(ProgressUpdate::set_label("Downloading..."), |_| {});
+
+        assert_eq!(progress_values(&updates), vec![2]);
+        assert!(matches!(updates.last(), Some(ProgressUpdate::SetLabel(label)) if label == "Downloading..."));
+    }
+
+    #[test]
+    fn held_back_weighted_progress_is_pending() {
+        let weighted = WeightedProgress::new();
+        weighted.start(100);
+        assert!(weighted.pending_update().is_none());
+
+        weighted.add_bytes(10);
+        assert!(weighted.throttled_update().is_some());
+        weighted.add_bytes(90);
+        assert!(weighted.throttled_update().is_none());
+
+        let max = ProgressUpdateSteps::len() as u64 * PER_STEP;
+        assert_eq!(weighted.pending_update().map(|update| progress_values(&[update])), Some(vec![max]));
+        assert!(weighted.pending_update().is_none());
+    }
+
+    struct ByteCounter(AtomicU64);
+
+    impl ProgressReceiver for ByteCounter {
+        fn progress_update(&self, _update: ProgressUpdate) {}
+
+        fn add_transferred_bytes(&self, bytes: u64) {
+            self.0.fetch_add(bytes, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn transferred_bytes_are_reported_as_deltas() {
+        let counter = ByteCounter(AtomicU64::new(0));
+        let on_progress = track_transfer(&counter, |_, _| {});
+
+        // resumed at 40 bytes, then started over after a failure
+        for downloaded in [40, 60, 100, 0, 50, 100, 120] {
+            on_progress(downloaded, 120);
+        }
+
+        assert_eq!(counter.0.load(Ordering::SeqCst), 60 + 120);
+    }
+}
@@ -4,14 +4,15 @@ use anyhow::Result;
 use regex::Regex;
 
 use crate::minecraft::version::{Rule, RuleAction};
-use crate::utils::{OS, ARCHITECTURE, OS_VERSION};
+use crate::utils::{Architecture, OS, ARCHITECTURE, OS_VERSION};
 
 pub fn check_condition(rules: &Vec<Rule>, features: &HashSet<String>) -> Result<bool> {
     if rules.is_empty() {
         return Ok(true);
     }
 
-    let os_name = OS.get_simple_name()?;
+    // on an OS unknown to the launcher no OS specific rule applies
+    let os_name = OS.get_simple_name().ok();
     let os_version = &*OS_VERSION.clone();
 
     let mut allow = false;
@@ -20,11 +21,12 @@ pub fn check_condition(rules: &Vec<Rule>, features: &HashSet<String>) -> Result<
         let mut rule_applies = true;
 
         if let Some(os_requirement) = &rule.os {
-            if os_requirement.name.as_ref().map_or(false, |x| x != os_name) {
+            if os_requirement.name.as_ref().map_or(false, |x| Some(normalize_os_name(x)) != os_name) {
                 rule_applies = false;
             }
             if let Some(arch) = &os_requirement.arch {
-                if *arch != ARCHITECTURE {
+                // an architecture the launcher doesn't know never matches, not even on an unknown architecture
+                if *arch == Architecture::UNKNOWN || *arch != *ARCHITECTURE {
                     rule_applies = false;
                 }
             }
@@ -51,4 +53,45 @@ pub fn check_condition(rules: &Vec<Rule>, features: &HashSet<String>) -> Result<
     }
 
     Ok(allow)
-}
\ No newline at end of file
+}
+
+/// Mojang names macOS "osx" in most profiles, but "macos" in some
+fn normalize_os_name(name: &str) -> &str {
+    match name {
+        "macos" => "osx",
+        name => name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow_on(os: serde_json::Value) -> Vec<Rule> {
+        vec![serde_json::from_value(serde_json::json!({ "action": "allow", "os": os })).unwrap()]
+    }
+
+    #[test]
+    fn os_names_are_normalized() {
+        assert_eq!(normalize_os_name("windows"), "windows");
+        assert_eq!(normalize_os_name("linux"), "linux");
+        assert_eq!(normalize_os_name("osx"), "osx");
+        assert_eq!(normalize_os_name("macos"), "osx");
+        assert_eq!(normalize_os_name("beos"), "beos");
+    }
+
+    #[test]
+    fn only_rules_of_the_current_os_apply() {
+        let current_os = OS.get_simple_name().unwrap();
+        for name in ["windows", "linux", "osx", "macos"] {
+            let expected = normalize_os_name(name) == current_os;
+            assert_eq!(check_condition(&allow_on(serde_json::json!({ "name": name })), &HashSet::new()).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn unknown_os_and_architecture_never_match() {
+        assert!(!check_condition(&allow_on(serde_json::json!({ "name": "beos" })), &HashSet::new()).unwrap());
+        assert!(!check_condition(&allow_on(serde_json::json!({ "arch": "riscv64" })), &HashSet::new()).unwrap());
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::*;
+
+use crate::app::app_data::write_atomically;
+use crate::utils::{NbtTag, read_nbt, TAG_COMPOUND, write_nbt};
+
+/// Server in the multiplayer server list of the game
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServerEntry {
+    pub name: String,
+    pub ip: String,
+}
+
+impl ServerEntry {
+    fn to_nbt(&self) -> NbtTag {
+        NbtTag::Compound(vec![
+            ("name".to_string(), NbtTag::String(self.name.clone())),
+            ("ip".to_string(), NbtTag::String(self.ip.clone())),
+        ])
+    }
+}
+
+///
+/// Adds the servers to the servers.dat of the game directory, servers whose ip is already listed are skipped.
+/// Servers added by the user are kept as they are. A missing or empty servers.dat is created.
+/// Returns the amount of added servers.
+///
+pub async fn seed_servers(game_dir: &Path, servers: &[ServerEntry]) -> Result<usize> {
+    let servers_file = game_dir.join("servers.dat");
+
+    let (root_name, mut root) = match fs::read(&servers_file).await {
+        Ok(content) if !content.is_empty() => read_nbt(&content)?,
+        _ => (String::new(), NbtTag::Compound(Vec::new()))
+    };
+
+    if root.get("servers").is_none() {
+        if let NbtTag::Compound(entries) = &mut root {
+            entries.push(("servers".to_string(), NbtTag::List(TAG_COMPOUND, Vec::new())));
+        }
+    }
+
+    let added = match root.get_mut("servers") {
+        Some(NbtTag::List(element_id, entries)) => {
+            // an empty list can have any type, the servers are compounds
+            if entries.is_empty() {
+                *element_id = TAG_COMPOUND;
+            } else if *element_id != TAG_COMPOUND {
+                bail!("servers of {:?} are not a list of compounds", servers_file);
+            }
+
+            let mut added = 0;
+            for server in servers {
+                let listed = entries.iter().any(|entry| entry.get("ip").and_then(NbtTag::as_str).map_or(false, |ip| ip.eq_ignore_ascii_case(&server.ip)));
+                if !listed {
+                    // appended, so the servers of the user stay at the top
+                    entries.push(server.to_nbt());
+                    added += 1;
+                }
+            }
+            added
+        }
+        _ => bail!("servers of {:?} are not a list", servers_file)
+    };
+
+    if added > 0 {
+        fs::create_dir_all(game_dir).await?;
+        // the game reads the file while running, it must never see a partially written list
+        write_atomically(&servers_file, &write_nbt(&root_name, &root)?).await?;
+        info!("Added {} servers to {:?}", added, servers_file);
+    }
+
+    Ok(added)
+}
@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{bail, Result};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate, ProgressUpdateSteps};
+use crate::minecraft::rule_interpreter;
+use crate::minecraft::version::{AssetIndex, DownloadSource, Library, LibraryDownloadInfo, VersionProfile};
+use crate::utils::{Checksum, download_file_checksummed, download_file_tracked};
+
+/// Folders of an installation
+pub struct InstallationDirs {
+    pub versions_folder: PathBuf,
+    pub libraries_folder: PathBuf,
+    pub assets_folder: PathBuf,
+}
+
+impl InstallationDirs {
+    pub fn new(data: &Path) -> Self {
+        InstallationDirs {
+            versions_folder: data.join("versions"),
+            libraries_folder: data.join("libraries"),
+            assets_folder: data.join("assets"),
+        }
+    }
+
+    /// Whether the path resolves to a file inside of one of the folders
+    fn contains(&self, path: &Path) -> bool {
+        let path = match path.absolutize() {
+            Ok(path) => path.to_path_buf(),
+            Err(_) => return false
+        };
+
+        [&self.versions_folder, &self.libraries_folder, &self.assets_folder].iter()
+            .filter_map(|folder| folder.absolutize().ok())
+            .any(|folder| path.starts_with(&folder) && path.as_path() != folder.as_ref())
+    }
+}
+
+/// A file of the installation which is missing or doesn't match its checksum
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InvalidFile {
+    pub path: PathBuf,
+    pub url: String,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+    pub missing: bool,
+}
+
+/// A file of the installation and what it is expected to be
+struct ExpectedFile {
+    path: PathBuf,
+    url: String,
+    sha1: Option<String>,
+    sha256: Option<String>,
+    size: Option<u64>,
+}
+
+impl ExpectedFile {
+    fn from_library(download: LibraryDownloadInfo, libraries_folder: &Path) -> Self {
+        ExpectedFile {
+            path: libraries_folder.join(&download.path),
+            url: download.url,
+            sha1: download.sha1,
+            sha256: download.sha256,
+            size: download.size.map(|x| x as u64),
+        }
+    }
+
+    fn checksum(&self) -> Option<Checksum> {
+        checksum_of(&self.sha1, &self.sha256)
+    }
+
+    /// Checks the file on disk, returns the invalid file if it is missing or doesn't match
+    fn verify(self) -> Result<Option<InvalidFile>> {
+        let missing = !self.path.exists();
+        let valid = !missing && match self.checksum() {
+            Some(checksum) => checksum.matches(&self.path)?,
+            None => self.size.map_or(true, |size| self.path.metadata().map_or(false, |x| x.len() == size))
+        };
+
+        if valid {
+            return Ok(None);
+        }
+
+        Ok(Some(InvalidFile {
+            path: self.path,
+            url: self.url,
+            sha1: self.sha1,
+            sha256: self.sha256,
+            size: self.size,
+            missing,
+        }))
+    }
+}
+
+/// SHA256 is preferred over SHA1
+fn checksum_of(sha1: &Option<String>, sha256: &Option<String>) -> Option<Checksum> {
+    if let Some(sha256) = sha256 {
+        Some(Checksum::Sha256(sha256.clone()))
+    } else {
+        sha1.as_ref().map(|x| Checksum::Sha1(x.clone()))
+    }
+}
+
+/// Collects the client jar, libraries and asset objects of the version profile
+fn expected_files(profile: &VersionProfile, dirs: &InstallationDirs, source: &DownloadSource, asset_index: Option<&AssetIndex>) -> Result<Vec<ExpectedFile>> {
+    let mut expected_files = Vec::new();
+
+    // Client
+    if let Some(client_download) = profile.downloads.as_ref().and_then(|x| x.client.as_ref()) {
+        expected_files.push(ExpectedFile {
+            path: dirs.versions_folder.join(&profile.id).join(format!("{}.jar", &profile.id)),
+            url: client_download.url.clone(),
+            sha1: Some(client_download.sha1.clone()),
+            sha256: None,
+            size: Some(client_download.size as u64),
+        });
+    }
+
+    // Libraries
+    let features = HashSet::new();
+    let libraries = Library::select_for_architecture(profile.libraries.iter()
+        .filter(|library| rule_interpreter::check_condition(&library.rules, &features).unwrap_or(false))
+        .cloned()
+        .collect());
+    for library in &libraries {
+        let download = if library.natives.is_some() {
+            library.get_natives_download(source)?
+        } else {
+            Some(library.get_library_download(source)?)
+        };
+
+        if let Some(download) = download {
+            expected_files.push(ExpectedFile::from_library(download, &dirs.libraries_folder));
+        }
+    }
+
+    // Assets
+    if let Some(asset_index) = asset_index {
+        let objects_folder = dirs.assets_folder.join("objects");
+        let asset_host = source.asset_hosts().remove(0);
+
+        for object in asset_index.objects.values() {
+            expected_files.push(ExpectedFile {
+                path: objects_folder.join(&object.hash[0..2]).join(&object.hash),
+                url: format!("{}/{}/{}", asset_host, &object.hash[0..2], &object.hash),
+                sha1: Some(object.hash.clone()),
+                sha256: None,
+                size: Some(object.size as u64),
+            });
+        }
+    }
+
+    Ok(expected_files)
+}
+
+///
+/// Checks the client jar, libraries and asset objects of the version profile without downloading any of them.
+/// Returns every missing or mismatching file, which can be passed to [repair_installation].
+///
+pub async fn verify_installation(profile: &VersionProfile, dirs: &InstallationDirs, source: &DownloadSource, concurrency: usize, progress: Arc<impl ProgressReceiver>) -> Result<Vec<InvalidFile>> {
+    let asset_index = match &profile.asset_index_location {
+        Some(asset_index_location) => Some(asset_index_location.load_asset_index(&dirs.assets_folder.join("indexes")).await?),
+        None => None
+    };
+
+    let expected_files = expected_files(profile, dirs, source, asset_index.as_ref())?;
+    check_files(expected_files, concurrency, progress).await
+}
+
+/// Summary of the files an install would download
+#[derive(Serialize, Clone, Debug)]
+pub struct InstallPlan {
+    pub files: u64,
+    pub bytes: u64,
+    /// Files whose size is unknown before downloading them, they are not included in the bytes
+    #[serde(rename = "unknownSizeFiles")]
+    pub unknown_size_files: u64,
+}
+
+///
+/// Determines which files of the version profile an install would download and how large they are, without writing any file.
+/// The profile should be resolved with [crate::minecraft::prelauncher::peek_version_profile], which doesn't install or cache anything either.
+///
+pub async fn plan_install(profile: &VersionProfile, dirs: &InstallationDirs, source: &DownloadSource, concurrency: usize, progress: Arc<impl ProgressReceiver>) -> Result<InstallPlan> {
+    let asset_index = match &profile.asset_index_location {
+        Some(asset_index_location) => Some(asset_index_location.peek_asset_index(&dirs.assets_folder.join("indexes")).await?),
+        None => None
+    };
+
+    let expected_files = expected_files(profile, dirs, source, asset_index.as_ref())?;
+    let missing_files = check_files(expected_files, concurrency, progress).await?;
+
+    let plan = InstallPlan {
+        files: missing_files.len() as u64,
+        bytes: missing_files.iter().filter_map(|file| file.size).sum(),
+        unknown_size_files: missing_files.iter().filter(|file| file.size.is_none()).count() as u64,
+    };
+    info!("Install of {} would download {} files with {} bytes", profile.id, plan.files, plan.bytes);
+
+    Ok(plan)
+}
+
+///
+/// Estimates the bytes an install would download from the files which don't exist yet. Unlike [plan_install] no file is hashed,
+/// so it is cheap enough to run before every launch.
+///
+pub async fn estimate_missing_bytes(profile: &VersionProfile, dirs: &InstallationDirs, source: &DownloadSource) -> Result<u64> {
+    let asset_index = match &profile.asset_index_location {
+        Some(asset_index_location) => Some(asset_index_location.peek_asset_index(&dirs.assets_folder.join("indexes")).await?),
+        None => None
+    };
+
+    Ok(expected_files(profile, dirs, source, asset_index.as_ref())?.iter()
+        .filter(|file| !file.path.exists())
+        .filter_map(|file| file.size)
+        .sum())
+}
+
+/// Hashes the expected files in parallel, returning the ones which are missing or invalid
+async fn check_files(expected_files: Vec<ExpectedFile>, concurrency: usize, progress: Arc<impl ProgressReceiver>) -> Result<Vec<InvalidFile>> {
+    let verified = Arc::new(AtomicU64::new(0));
+    let max = expected_files.len() as u64;
+
+    progress.progress_update(ProgressUpdate::set_label("Verifying installation..."));
+    progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyAssets, 0, max));
+
+    let invalid_files: Vec<Option<InvalidFile>> = stream::iter(
+        expected_files.into_iter().map(|expected_file| {
+            let verified = verified.clone();
+            let progress = progress.clone();
+
+            async move {
+                // hashing is blocking, so it has to run on the blocking pool to run in parallel
+                let invalid_file = tokio::task::spawn_blocking(move || expected_file.verify()).await??;
+
+                if let Some(invalid_file) = &invalid_file {
+                    warn!("Invalid file {:?} (missing: {})", invalid_file.path, invalid_file.missing);
+                    progress.progress_update(ProgressUpdate::set_label(format!("Invalid file {}", invalid_file.path.display())));
+                }
+
+                let curr = verified.fetch_add(1, Ordering::Relaxed) + 1;
+                progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyAssets, curr, max));
+
+                Ok::<_, anyhow::Error>(invalid_file)
+            }
+        })
+    ).buffer_unordered(concurrency).try_collect().await?;
+
+    let invalid_files = invalid_files.into_iter().flatten().collect::<Vec<_>>();
+    info!("Verified {} files, {} are invalid", max, invalid_files.len());
+    progress.progress_update(ProgressUpdate::set_label(format!("Verified installation, {} invalid files", invalid_files.len())));
+
+    Ok(invalid_files)
+}
+
+///
+/// Verifies the installation of the version profile like [verify_installation] and redownloads the invalid files.
+/// The files are determined from the profile again, so only the files of the installation are ever written.
+///
+pub async fn repair_installation(profile: &VersionProfile, dirs: &InstallationDirs, source: &DownloadSource, concurrency: usize, progress: Arc<impl ProgressReceiver>, cancellation: CancellationToken) -> Result<()> {
+    let invalid_files = verify_installation(profile, dirs, source, concurrency, progress.clone()).await?;
+
+    // library paths come from the version profile, a path leaving the installation is never written
+    if let Some(invalid_file) = invalid_files.iter().find(|invalid_file| !dirs.contains(&invalid_file.path)) {
+        bail!("{:?} is outside of the installation", invalid_file.path);
+    }
+
+    repair_files(invalid_files, concurrency, progress, cancellation).await
+}
+
+async fn repair_files(invalid_files: Vec<InvalidFile>, concurrency: usize, progress: Arc<impl ProgressReceiver>, cancellation: CancellationToken) -> Result<()> {
+    let repaired = Arc::new(AtomicU64::new(0));
+    let max = invalid_files.len() as u64;
+
+    progress.progress_update(ProgressUpdate::set_label("Repairing installation..."));
+
+    stream::iter(
+        invalid_files.into_iter().map(|invalid_file| {
+            let repaired = repaired.clone();
+            let progress = progress.clone();
+            let cancellation = cancellation.clone();
+
+            async move {
+                if let Some(parent) = invalid_file.path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+
+                match checksum_of(&invalid_file.sha1, &invalid_file.sha256) {
+                    Some(checksum) => download_file_checksummed(&invalid_file.url, &invalid_file.path, &checksum, invalid_file.size, &cancellation, |_, _| {}).await?,
+                    None => download_file_tracked(&invalid_file.url, &invalid_file.path, invalid_file.size, &cancellation, |_, _| {}).await?
+                }
+
+                let curr = repaired.fetch_add(1, Ordering::Relaxed) + 1;
+                progress.progress_update(ProgressUpdate::set_label(format!("Repaired {} ({}/{})", invalid_file.path.display(), curr, max)));
+
+                Ok::<_, anyhow::Error>(())
+            }
+        })
+    ).buffer_unordered(concurrency).try_collect::<Vec<_>>().await?;
+
+    info!("Repaired {} files", max);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_paths_inside_of_the_installation_are_contained() {
+        let dirs = InstallationDirs::new(Path::new("/data"));
+
+        assert!(dirs.contains(Path::new("/data/libraries/org/ow2/asm/asm/9.6/asm-9.6.jar")));
+        assert!(dirs.contains(Path::new("/data/assets/objects/bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a")));
+        assert!(!dirs.contains(Path::new("/data/libraries/../../etc/passwd")));
+        assert!(!dirs.contains(Path::new("/data/gameDir/mods/mod.jar")));
+        assert!(!dirs.contains(Path::new("/data/libraries")));
+    }
+}
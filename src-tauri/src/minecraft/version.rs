@@ -1,35 +1,215 @@
 use std::{collections::HashMap, fmt, marker::PhantomData, path::{Path, PathBuf}, str::FromStr};
 
-use anyhow::Result;
-use tracing::{debug, info};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use tracing::{debug, info, warn};
 use tokio::fs;
-use serde::{Deserialize, Deserializer, de::{self, MapAccess, Visitor}};
+use std::time::Duration;
+use reqwest::header::{ETAG, HeaderName, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize, de::{self, MapAccess, Visitor}};
 use void::Void;
 use std::collections::HashSet;
-use crate::{error::LauncherError, HTTP_CLIENT, LAUNCHER_DIRECTORY, utils::{download_file_untracked, Architecture}};
-use crate::utils::{get_maven_artifact_path, sha1sum};
+use crate::{error::LauncherError, HTTP_CLIENT, utils::{Checksum, download_file_checksummed, download_file_tracked, download_file_untracked, download_file_verified, sha1sum_bytes, Architecture}};
+use crate::utils::{authorize_request, compare_maven_versions, get_maven_artifact_path, parse_maven_coordinate, MavenCoordinate, sha1sum, split_arguments, ARCHITECTURE, IS_ROSETTA_TRANSLATED, OS};
 use std::sync::Arc;
-use crate::app::api::get_launcher_api_base;
-use crate::app::app_data::LauncherOptions;
-use crate::minecraft::launcher::LaunchingParameter;
-use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
+use crate::app::api::NORISK_LAUNCHER_API_VERSION;
+use crate::app::norisk_api::NoriskApi;
+use crate::minecraft::cosmetics::CosmeticManifest;
+use crate::minecraft::launcher::{LaunchingParameter, process_templates};
+use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate, track_transfer};
+use crate::utils::unpack_pack_xz;
+use tokio_util::sync::CancellationToken;
 
-// https://launchermeta.mojang.com/mc/game/version_manifest.json
+// https://piston-meta.mojang.com/mc/game/version_manifest_v2.json
+
+// v2 of the manifest additionally provides the sha1 of each version profile
+const VERSION_MANIFEST_URL: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+
+/// Path of the version manifest on the official host and mirrors
+const VERSION_MANIFEST_PATH: &str = "/mc/game/version_manifest_v2.json";
+
+/// Cached version manifest is used without revalidation for this long
+const VERSION_MANIFEST_MAX_AGE: Duration = Duration::from_secs(10 * 60);
 
 #[derive(Deserialize)]
 pub struct VersionManifest {
     pub versions: Vec<ManifestVersion>,
 }
 
+/// Validators of a cached file, sent to revalidate it with a conditional request
+#[derive(Deserialize, Serialize, Default)]
+struct CacheValidators {
+    etag: Option<String>,
+    #[serde(rename = "lastModified")]
+    last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// Validators stored next to the cached file, none if they are missing or broken
+    async fn load(meta_path: &Path) -> Self {
+        match fs::read(meta_path).await {
+            Ok(content) => serde_json::from_slice::<Self>(&content).unwrap_or_default(),
+            Err(_) => Self::default()
+        }
+    }
+
+    fn from_response(response: &reqwest::Response) -> Self {
+        let header_value = |name: HeaderName| response.headers().get(name).and_then(|x| x.to_str().ok()).map(ToOwned::to_owned);
+        CacheValidators {
+            etag: header_value(ETAG),
+            last_modified: header_value(LAST_MODIFIED),
+        }
+    }
+
+    fn apply(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
 impl VersionManifest {
-    pub async fn download() -> Result<Self> {
-        let response = HTTP_CLIENT.get("https://launchermeta.mojang.com/mc/game/version_manifest.json")
+    pub async fn download(source: &DownloadSource) -> Result<Self> {
+        let mut result = Err(anyhow::anyhow!("no version manifest url"));
+        for url in source.manifest_urls() {
+            result = Self::download_from(&url).await;
+            match &result {
+                Ok(_) => break,
+                Err(e) => warn!("Unable to download version manifest from {}: {:?}", url, e)
+            }
+        }
+
+        result
+    }
+
+    async fn download_from(url: &str) -> Result<Self> {
+        let response = HTTP_CLIENT.get(url)
             .send().await?
             .error_for_status()?;
         let manifest = response.json::<VersionManifest>().await?;
 
         Ok(manifest)
     }
+
+    /// Loads the version manifest from the cache, only revalidating it against Mojang (or the mirror) when it is stale or missing.
+    /// Falls back to the cached copy if Mojang is unreachable.
+    pub async fn load_cached(cache_dir: &Path, source: &DownloadSource) -> Result<Self> {
+        let manifest_path = cache_dir.join("version_manifest.json");
+        let meta_path = cache_dir.join("version_manifest.meta.json");
+
+        let cache_age = fs::metadata(&manifest_path).await
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        if cache_age.map_or(false, |age| age < VERSION_MANIFEST_MAX_AGE) {
+            if let Ok(manifest) = Self::read_cache(&manifest_path).await {
+                return Ok(manifest);
+            }
+        }
+
+        let mut result = Err(anyhow::anyhow!("no version manifest url"));
+        for url in source.manifest_urls() {
+            result = Self::revalidate(&url, cache_dir, &manifest_path, &meta_path).await;
+            match &result {
+                Ok(_) => break,
+                Err(e) => warn!("Unable to fetch version manifest from {}: {:?}", url, e)
+            }
+        }
+
+        match result {
+            Ok(manifest) => Ok(manifest),
+            Err(e) if manifest_path.exists() => {
+                warn!("Unable to fetch version manifest, using cached copy: {:?}", e);
+                Self::read_cache(&manifest_path).await
+            }
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Like [Self::load_cached], but the cached manifest is used regardless of its age and a missing one is only fetched and not written to disk
+    pub async fn peek_cached(cache_dir: &Path, source: &DownloadSource) -> Result<Self> {
+        match Self::read_cache(&cache_dir.join("version_manifest.json")).await {
+            Ok(manifest) => Ok(manifest),
+            Err(_) => Self::download(source).await
+        }
+    }
+
+    async fn read_cache(manifest_path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice::<Self>(&fs::read(manifest_path).await?)?)
+    }
+
+    async fn revalidate(url: &str, cache_dir: &Path, manifest_path: &Path, meta_path: &Path) -> Result<Self> {
+        let meta = if manifest_path.exists() {
+            CacheValidators::load(meta_path).await
+        } else {
+            CacheValidators::default()
+        };
+
+        let response = meta.apply(HTTP_CLIENT.get(url)).send().await?.error_for_status()?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            debug!("Version manifest not modified, using cached copy");
+            let manifest = Self::read_cache(manifest_path).await?;
+            // touch the cache so it is considered fresh again
+            fs::write(manifest_path, fs::read(manifest_path).await?).await?;
+            return Ok(manifest);
+        }
+
+        let meta = CacheValidators::from_response(&response);
+        let content = response.bytes().await?;
+        let manifest = serde_json::from_slice::<Self>(&content)?;
+
+        fs::create_dir_all(cache_dir).await?;
+        fs::write(manifest_path, &content).await?;
+        fs::write(meta_path, serde_json::to_vec(&meta)?).await?;
+
+        Ok(manifest)
+    }
+
+    /// Versions sorted by their release time, newest first
+    pub fn sorted_by_release_time(&self) -> Vec<&ManifestVersion> {
+        let mut versions = self.versions.iter().collect::<Vec<_>>();
+        versions.sort_by_key(|x| std::cmp::Reverse(x.release_date_time()));
+        versions
+    }
+
+    /// Versions of the given type (release, snapshot, old_beta or old_alpha) sorted by their release time, newest first
+    pub fn by_type(&self, version_type: &str) -> Vec<&ManifestVersion> {
+        self.sorted_by_release_time().into_iter()
+            .filter(|x| x.version_type == version_type)
+            .collect()
+    }
+
+    /// Versions of type release, newest first
+    pub fn releases(&self) -> Vec<&ManifestVersion> {
+        self.by_type("release")
+    }
+
+    /// Versions of type snapshot, newest first
+    pub fn snapshots(&self) -> Vec<&ManifestVersion> {
+        self.by_type("snapshot")
+    }
+
+    /// Newest version of type release
+    pub fn latest_release(&self) -> Option<&ManifestVersion> {
+        self.latest_of_type("release")
+    }
+
+    /// Newest version of type snapshot
+    pub fn latest_snapshot(&self) -> Option<&ManifestVersion> {
+        self.latest_of_type("snapshot")
+    }
+
+    fn latest_of_type(&self, version_type: &str) -> Option<&ManifestVersion> {
+        self.versions.iter()
+            .filter(|x| x.version_type == version_type)
+            .max_by_key(|x| x.release_date_time())
+    }
 }
 
 #[derive(Deserialize)]
@@ -38,9 +218,23 @@ pub struct ManifestVersion {
     #[serde(rename = "type")]
     pub version_type: String,
     pub url: String,
-    pub time: String,
+    pub time: Timestamp,
     #[serde(rename = "releaseTime")]
-    pub release_time: String,
+    pub release_time: Timestamp,
+    #[serde(default)]
+    pub sha1: Option<String>,
+}
+
+impl ManifestVersion {
+    /// Time the version was released
+    pub fn release_date_time(&self) -> DateTime<Utc> {
+        self.release_time.date_time()
+    }
+
+    /// Time the version was last updated
+    pub fn date_time(&self) -> DateTime<Utc> {
+        self.time.date_time()
+    }
 }
 
 #[derive(Deserialize)]
@@ -60,14 +254,111 @@ pub struct VersionProfile {
     #[serde(rename = "mainClass")]
     pub main_class: Option<String>,
     pub logging: Option<Logging>,
+    /// Java runtime Mojang ships for the version, missing in old and most modloader profiles
+    #[serde(rename = "javaVersion")]
+    pub java_version: Option<JavaVersion>,
     #[serde(rename = "type")]
     pub version_type: String,
+    #[serde(default)]
+    pub time: Option<Timestamp>,
+    #[serde(rename = "releaseTime", default)]
+    pub release_time: Option<Timestamp>,
     #[serde(flatten)]
     pub arguments: ArgumentDeclaration,
 }
 
+///
+/// Timestamp of a version, parsed when the version is deserialized. Invalid timestamps fail the deserialization.
+/// The original string is kept so it is serialized exactly as it was read.
+/// Timestamps are compared by the instant they describe, so the same time written with another offset is equal.
+///
+#[derive(Clone, Debug)]
+pub struct Timestamp {
+    raw: String,
+    date_time: DateTime<Utc>,
+}
+
+impl Timestamp {
+    pub fn date_time(&self) -> DateTime<Utc> {
+        self.date_time
+    }
+
+    /// The timestamp as it was written in the version
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Date for display, e.g. 2023-06-12
+    pub fn format_date(&self) -> String {
+        self.date_time.format("%Y-%m-%d").to_string()
+    }
+
+    /// Date and time for display, e.g. 2023-06-12 09:25 UTC
+    pub fn format_date_time(&self) -> String {
+        self.date_time.format("%Y-%m-%d %H:%M UTC").to_string()
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = anyhow::Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        // older versions use offsets without a colon, e.g. 2010-03-30T00:00:00-0700
+        let date_time = DateTime::parse_from_rfc3339(raw)
+            .or_else(|e| DateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f%z").map_err(|_| e))
+            .map_err(|e| anyhow::anyhow!("invalid timestamp {}: {}", raw, e))?
+            .with_timezone(&Utc);
+        Ok(Timestamp { raw: raw.to_string(), date_time })
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        self.date_time == other.date_time
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.date_time.cmp(&other.date_time)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct JavaVersion {
+    /// Name of Mojang's runtime, e.g. java-runtime-gamma
+    pub component: String,
+    #[serde(rename = "majorVersion")]
+    pub major_version: u32,
+}
+
 impl VersionProfile {
     pub(crate) fn merge(&mut self, mut parent: VersionProfile) -> Result<()> {
+        if self.id == parent.id {
+            return Err(LauncherError::ProfileMergeConflict(format!("version profile {} inherits from itself", self.id)).into());
+        }
+
         Self::merge_options(&mut self.asset_index_location, parent.asset_index_location);
         Self::merge_options(&mut self.assets, parent.assets);
 
@@ -76,28 +367,145 @@ impl VersionProfile {
         Self::merge_larger(&mut self.compliance_level, parent.compliance_level);
 
         self.libraries.append(&mut parent.libraries);
+        self.dedupe_libraries();
         Self::merge_options(&mut self.main_class, parent.main_class);
         Self::merge_options(&mut self.logging, parent.logging);
+        Self::merge_options(&mut self.java_version, parent.java_version);
+        Self::merge_options(&mut self.time, parent.time);
+        Self::merge_options(&mut self.release_time, parent.release_time);
+
+        let arguments = std::mem::replace(&mut self.arguments, ArgumentDeclaration::V14(V14ArgumentDeclaration { minecraft_arguments: None }));
+        self.arguments = match (arguments, parent.arguments) {
+            (ArgumentDeclaration::V14(mut v14_a), ArgumentDeclaration::V14(v14_b)) => {
+                Self::merge_options(&mut v14_a.minecraft_arguments, v14_b.minecraft_arguments);
+                ArgumentDeclaration::V14(v14_a)
+            }
+            (ArgumentDeclaration::V21(mut v21_a), ArgumentDeclaration::V21(mut v21_b)) => {
+                v21_a.arguments.game.append(&mut v21_b.arguments.game);
+                v21_a.arguments.jvm.append(&mut v21_b.arguments.jvm);
+                ArgumentDeclaration::V21(v21_a)
+            }
+            // Some modloader profiles mix both formats, the merged profile always uses the structured format
+            // and takes the legacy arguments over into the game arguments.
+            (ArgumentDeclaration::V14(v14_a), ArgumentDeclaration::V21(mut v21_b)) => {
+                debug!("Merging legacy arguments of {} into structured arguments of parent", self.id);
+                let mut game = Self::legacy_game_arguments(v14_a.minecraft_arguments.as_deref(), &v21_b.arguments.game);
+                game.append(&mut v21_b.arguments.game);
+                ArgumentDeclaration::V21(v21_b.with_game_arguments(game))
+            }
+            (ArgumentDeclaration::V21(mut v21_a), ArgumentDeclaration::V14(v14_b)) => {
+                debug!("Merging legacy arguments of parent into structured arguments of {}", self.id);
+                let mut legacy = Self::legacy_game_arguments(v14_b.minecraft_arguments.as_deref(), &v21_a.arguments.game);
+                v21_a.arguments.game.append(&mut legacy);
+                // legacy profiles rely on the launcher to provide these
+                if !v21_a.arguments.jvm.iter().any(|x| x.contains("${classpath}")) {
+                    for arg in ["-Djava.library.path=${natives_directory}", "-cp", "${classpath}"] {
+                        v21_a.arguments.jvm.push(Argument { rules: None, value: ArgumentValue::SINGLE(arg.to_string()) });
+                    }
+                }
+                ArgumentDeclaration::V21(v21_a)
+            }
+        };
+
+        Ok(())
+    }
 
-        match &mut self.arguments {
-            ArgumentDeclaration::V14(v14_a) => {
-                if let ArgumentDeclaration::V14(v14_b) = parent.arguments {
-                    Self::merge_options(&mut v14_a.minecraft_arguments, v14_b.minecraft_arguments);
-                } else {
-                    return Err(LauncherError::InvalidVersionProfile("version profile inherits from incompatible profile".to_string()).into());
+    /// Converts a legacy argument string into structured arguments, leaving out options which are already declared
+    fn legacy_game_arguments(minecraft_arguments: Option<&str>, declared: &[Argument]) -> Vec<Argument> {
+        let declared_options = declared.iter()
+            .filter_map(|x| match &x.value {
+                ArgumentValue::SINGLE(value) if value.starts_with("--") => Some(value.as_str()),
+                _ => None
+            })
+            .collect::<HashSet<_>>();
+
+        let mut arguments = Vec::new();
+        let mut tokens = minecraft_arguments.unwrap_or_default().split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            if token.starts_with("--") && declared_options.contains(token) {
+                // skip the option's value as well
+                if tokens.peek().map_or(false, |x| !x.starts_with("--")) {
+                    tokens.next();
                 }
+                continue;
             }
-            ArgumentDeclaration::V21(v21_a) => {
-                if let ArgumentDeclaration::V21(mut v21_b) = parent.arguments {
-                    v21_a.arguments.game.append(&mut v21_b.arguments.game);
-                    v21_a.arguments.jvm.append(&mut v21_b.arguments.jvm);
-                } else {
-                    return Err(LauncherError::InvalidVersionProfile("version profile inherits from incompatible profile".to_string()).into());
+
+            arguments.push(Argument { rules: None, value: ArgumentValue::SINGLE(token.to_string()) });
+        }
+
+        arguments
+    }
+
+    ///
+    /// Keeps only the highest version of every maven artifact, the library of the child wins on ties.
+    /// Libraries with rules or natives are left alone, vanilla profiles ship different versions of them per OS.
+    ///
+    pub(crate) fn dedupe_libraries(&mut self) {
+        let mut kept: Vec<Library> = Vec::with_capacity(self.libraries.len());
+        let mut artifact_indexes: HashMap<String, usize> = HashMap::new();
+
+        for library in self.libraries.drain(..) {
+            let coordinate = match library.maven_coordinate() {
+                Some(coordinate) if library.rules.is_empty() && library.natives.is_none() => coordinate,
+                _ => {
+                    kept.push(library);
+                    continue;
+                }
+            };
+
+            let key = coordinate.key();
+            match artifact_indexes.get(&key) {
+                Some(&idx) => {
+                    let existing_version = kept[idx].maven_coordinate().map(|x| x.version).unwrap_or_default();
+                    if compare_maven_versions(&coordinate.version, &existing_version) == std::cmp::Ordering::Greater {
+                        info!("Dropping duplicate library {} in favor of {}", kept[idx].name, library.name);
+                        kept[idx] = library;
+                    } else {
+                        info!("Dropping duplicate library {} in favor of {}", library.name, kept[idx].name);
+                    }
+                }
+                None => {
+                    artifact_indexes.insert(key, kept.len());
+                    kept.push(library);
                 }
             }
         }
 
-        Ok(())
+        self.libraries = kept;
+    }
+
+    /// Major version of the java runtime the version requires, guessed from the minecraft version if the profile doesn't specify it
+    pub fn required_java_major(&self) -> u32 {
+        if let Some(java_version) = &self.java_version {
+            return java_version.major_version;
+        }
+
+        let minecraft_version = self.inherits_from.as_ref().unwrap_or(&self.id);
+        let guessed = Self::guess_java_major(minecraft_version);
+        debug!("{} doesn't specify a java version, guessed Java {}", self.id, guessed);
+        guessed
+    }
+
+    /// 1.20.5+ requires Java 21, 1.18+ Java 17, 1.17 Java 16 and everything older Java 8
+    fn guess_java_major(minecraft_version: &str) -> u32 {
+        let parts = minecraft_version.split('.')
+            .map(|part| part.parse::<u32>().ok())
+            .collect::<Vec<_>>();
+
+        match parts.as_slice() {
+            [Some(1), Some(minor), rest @ ..] => {
+                let patch = rest.first().copied().flatten().unwrap_or(0);
+                match (*minor, patch) {
+                    (minor, _) if minor > 20 => 21,
+                    (20, patch) if patch >= 5 => 21,
+                    (minor, _) if minor >= 18 => 17,
+                    (17, _) => 16,
+                    _ => 8
+                }
+            }
+            // snapshots and unknown schemes are most likely recent versions
+            _ => 21
+        }
     }
 
     fn merge_options<T>(a: &mut Option<T>, b: Option<T>) {
@@ -127,21 +535,40 @@ pub enum ArgumentDeclaration {
 }
 
 impl ArgumentDeclaration {
-    pub(crate) fn add_jvm_args_to_vec(&self, norisk_token: &str, command_arguments: &mut Vec<String>, parameter: &LaunchingParameter, features: &HashSet<String>) -> Result<()> {
+    pub(crate) fn add_jvm_args_to_vec(&self, norisk_token: &str, command_arguments: &mut Vec<String>, parameter: &LaunchingParameter, features: &HashSet<String>, logging: Option<(&LoggingClient, &Path)>) -> Result<()> {
         command_arguments.push(format!("-Xmx{}M", parameter.memory));
-        command_arguments.push("-XX:+UnlockExperimentalVMOptions".to_string());
-        command_arguments.push("-XX:+UseG1GC".to_string());
-        command_arguments.push("-XX:G1NewSizePercent=20".to_string());
-        command_arguments.push("-XX:G1ReservePercent=20".to_string());
-        command_arguments.push("-XX:MaxGCPauseMillis=50".to_string());
-        command_arguments.push("-XX:G1HeapRegionSize=32M".to_string());
+        command_arguments.push(format!("-Xms{}M", parameter.initial_memory));
+        // Default G1GC tuning, users running another collector (ZGC, Shenandoah, Aikar's flags) are able to disable
+        // these flags and supply their own via custom java args:
+        // -XX:+UnlockExperimentalVMOptions -XX:+UseG1GC -XX:G1NewSizePercent=20 -XX:G1ReservePercent=20
+        // -XX:MaxGCPauseMillis=50 -XX:G1HeapRegionSize=32M
+        if parameter.use_default_gc_flags {
+            command_arguments.push("-XX:+UnlockExperimentalVMOptions".to_string());
+            command_arguments.push("-XX:+UseG1GC".to_string());
+            command_arguments.push("-XX:G1NewSizePercent=20".to_string());
+            command_arguments.push("-XX:G1ReservePercent=20".to_string());
+            command_arguments.push("-XX:MaxGCPauseMillis=50".to_string());
+            command_arguments.push("-XX:G1HeapRegionSize=32M".to_string());
+        }
         command_arguments.push(format!("-Dnorisk.token={}", norisk_token));
-        command_arguments.push(format!("-Dnorisk.experimental={}", parameter.dev_mode));
-        for arg in parameter.custom_java_args.split(" ") {
-            if arg != " " && arg != "" {
-                println!("Added custom java arg: {:?}", arg);
-                command_arguments.push(arg.to_string());
+        command_arguments.push(format!("-Dnorisk.experimental={}", parameter.norisk_experimental));
+        for arg in split_arguments(&parameter.custom_java_args) {
+            // the launcher already sets the maximum and initial heap size
+            if arg.starts_with("-Xmx") || arg.starts_with("-Xms") {
+                warn!("Custom java arg {:?} conflicts with the configured memory, skipping", arg);
+                continue;
             }
+            if let Some(policy) = &parameter.jvm_args_policy {
+                if !policy.permits(&arg) {
+                    warn!("Custom java arg {:?} is not permitted by the jvm policy, skipping", arg);
+                    continue;
+                }
+            }
+            debug!("Added custom java arg: {:?}", arg);
+            command_arguments.push(arg);
+        }
+        if let Some((logging_client, config_path)) = logging {
+            command_arguments.push(logging_client.jvm_argument(config_path)?);
         }
 
         match self {
@@ -172,6 +599,18 @@ impl ArgumentDeclaration {
         Ok(())
     }
 
+    /// Checks if any game argument of the version is gated behind the feature
+    pub(crate) fn supports_feature(&self, feature: &str) -> bool {
+        match self {
+            ArgumentDeclaration::V14(_) => false,
+            ArgumentDeclaration::V21(decl) => decl.arguments.game.iter()
+                .filter_map(|argument| argument.rules.as_ref())
+                .flatten()
+                .filter_map(|rule| rule.features.as_ref())
+                .any(|features| features.contains_key(feature))
+        }
+    }
+
     fn check_rules_and_add(command_arguments: &mut Vec<String>, args: &Vec<Argument>, features: &HashSet<String>) -> Result<()> {
         for argument in args {
             if let Some(rules) = &argument.rules {
@@ -201,11 +640,116 @@ pub struct V21ArgumentDeclaration {
     pub arguments: Arguments,
 }
 
+impl V21ArgumentDeclaration {
+    fn with_game_arguments(mut self, game: Vec<Argument>) -> Self {
+        self.arguments.game = game;
+        self
+    }
+}
+
 impl VersionProfile {
     pub async fn load(url: &String) -> Result<Self> {
-        dbg!(url);
         Ok(HTTP_CLIENT.get(url).send().await?.error_for_status()?.json::<VersionProfile>().await?)
     }
+
+    ///
+    /// Loads the version profile from versions/{id}/{id}.json, downloading it if it is missing or does not match the sha1.
+    /// Without a sha1 the cached profile is revalidated with its ETag and Last-Modified, so changes of the profile are picked up.
+    /// Falls back to the cached copy if the profile cannot be downloaded, which allows offline launches of installed versions.
+    ///
+    pub async fn load_cached(url: &String, id: &str, sha1: Option<&str>, cache_dir: &Path) -> Result<Self> {
+        let profile_path = cache_dir.join(id).join(format!("{}.json", id));
+        let meta_path = cache_dir.join(id).join(format!("{}.meta.json", id));
+
+        let mut validators = CacheValidators::default();
+        if profile_path.exists() {
+            match sha1 {
+                Some(sha1) if Checksum::Sha1(sha1.to_string()).matches(&profile_path)? => {
+                    match Self::read_cache(&profile_path).await {
+                        Ok(profile) => return Ok(profile),
+                        Err(e) => warn!("Cached version profile {} is invalid, downloading again: {:?}", id, e)
+                    }
+                }
+                Some(sha1) => info!("Cached version profile {} does not match {}, downloading again", id, sha1),
+                // a broken cache must not be confirmed by a not modified response
+                None if Self::read_cache(&profile_path).await.is_ok() => validators = CacheValidators::load(&meta_path).await,
+                None => warn!("Cached version profile {} is invalid, downloading again", id)
+            }
+        }
+
+        match Self::download_to_cache(url, sha1, &profile_path, &meta_path, &validators).await {
+            Ok(profile) => Ok(profile),
+            Err(e) if profile_path.exists() => {
+                warn!("Unable to download version profile {}, using cached copy: {:?}", id, e);
+                Self::read_cache(&profile_path).await
+            }
+            Err(e) => Err(e)
+        }
+    }
+
+    async fn read_cache(profile_path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice::<Self>(&fs::read(profile_path).await?)?)
+    }
+
+    /// Like [Self::load_cached], but a missing profile is only fetched and not written to disk
+    pub async fn peek_cached(url: &String, id: &str, sha1: Option<&str>, cache_dir: &Path) -> Result<Self> {
+        let profile_path = cache_dir.join(id).join(format!("{}.json", id));
+
+        if profile_path.exists() {
+            let valid = match sha1 {
+                Some(sha1) => Checksum::Sha1(sha1.to_string()).matches(&profile_path)?,
+                None => true
+            };
+            if valid {
+                if let Ok(profile) = Self::read_cache(&profile_path).await {
+                    return Ok(profile);
+                }
+            }
+        }
+
+        let content = HTTP_CLIENT.get(url).send().await?.error_for_status()?.bytes().await?;
+        Self::verify_sha1(url, sha1, &content)?;
+        Ok(serde_json::from_slice::<Self>(&content)?)
+    }
+
+    async fn download_to_cache(url: &String, sha1: Option<&str>, profile_path: &Path, meta_path: &Path, validators: &CacheValidators) -> Result<Self> {
+        let response = validators.apply(HTTP_CLIENT.get(url)).send().await?.error_for_status()?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            debug!("Version profile {} not modified, using cached copy", url);
+            return Self::read_cache(profile_path).await;
+        }
+
+        let meta = CacheValidators::from_response(&response);
+        let content = response.bytes().await?;
+        Self::verify_sha1(url, sha1, &content)?;
+
+        let profile = serde_json::from_slice::<Self>(&content)?;
+
+        if let Some(parent) = profile_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(profile_path, &content).await?;
+        fs::write(meta_path, serde_json::to_vec(&meta)?).await?;
+
+        Ok(profile)
+    }
+
+    fn verify_sha1(url: &String, sha1: Option<&str>, content: &[u8]) -> Result<()> {
+
+        if let Some(sha1) = sha1 {
+            let actual = sha1sum_bytes(content);
+            if !actual.eq_ignore_ascii_case(sha1.trim()) {
+                return Err(LauncherError::ChecksumMismatch {
+                    file: url.clone(),
+                    expected: sha1.to_owned(),
+                    got: actual,
+                }.into());
+            }
+        }
+
+        Ok(())
+    }
 }
 
 // Parsing the arguments was pain, please mojang. What in the hell did you do?
@@ -234,6 +778,16 @@ pub enum ArgumentValue {
     VEC(Vec<String>),
 }
 
+impl Argument {
+    /// Checks if any of the argument's values contain the given string
+    fn contains(&self, pattern: &str) -> bool {
+        match &self.value {
+            ArgumentValue::SINGLE(value) => value.contains(pattern),
+            ArgumentValue::VEC(vec) => vec.iter().any(|x| x.contains(pattern))
+        }
+    }
+}
+
 impl FromStr for Argument {
     type Err = Void;
 
@@ -250,7 +804,7 @@ fn vec_argument<'de, D>(deserializer: D) -> Result<Vec<Argument>, D::Error>
     #[derive(Deserialize)]
     struct Wrapper(#[serde(deserialize_with = "string_or_struct")] Argument);
 
-    let v = Vec::deserialize(deserializer).unwrap();
+    let v = Vec::deserialize(deserializer)?;
     Ok(v.into_iter().map(|Wrapper(a)| a).collect())
 }
 
@@ -275,7 +829,7 @@ fn string_or_struct<'de, T, D>(deserializer: D) -> Result<T, D::Error>
             where
                 E: serde::de::Error,
         {
-            Ok(FromStr::from_str(value).unwrap())
+            FromStr::from_str(value).map_err(|x| void::unreachable(x))
         }
 
         fn visit_map<M>(self, map: M) -> Result<T, M::Error>
@@ -300,33 +854,289 @@ pub struct AssetIndexLocation {
 }
 
 impl AssetIndexLocation {
+    /// Loads the asset index, which is downloaded if it is missing or doesn't match its size and sha1
     pub async fn load_asset_index(&self, assets_root: &PathBuf) -> Result<AssetIndex> {
         let asset_index = assets_root.join(format!("{}.json", &self.id));
 
-        if !asset_index.exists() {
+        if asset_index.exists() {
+            let content = fs::read(&asset_index).await?;
+            match self.verify(&content) {
+                Ok(()) => return Ok(serde_json::from_slice::<AssetIndex>(&content)?),
+                Err(e) => warn!("Asset index of {} is invalid, downloading it again: {:?}", self.id, e)
+            }
+        }
+
+        // a mismatch right after downloading can be a broken transfer, so it is downloaded a second time before giving up
+        let mut retried = false;
+        loop {
             info!("Downloading assets index of {}", self.id);
             download_file_untracked(&self.url, &asset_index).await?;
             info!("Downloaded {}", self.url);
+
+            let content = fs::read(&asset_index).await?;
+            match self.verify(&content) {
+                Ok(()) => return Ok(serde_json::from_slice::<AssetIndex>(&content)?),
+                Err(e) if !retried => {
+                    warn!("Downloaded asset index of {} is invalid, retrying: {:?}", self.id, e);
+                    retried = true;
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&asset_index).await;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Like [Self::load_asset_index], but a missing index is only fetched and not written to disk
+    pub async fn peek_asset_index(&self, assets_root: &PathBuf) -> Result<AssetIndex> {
+        let asset_index = assets_root.join(format!("{}.json", &self.id));
+
+        if asset_index.exists() {
+            let content = fs::read(&asset_index).await?;
+            if self.verify(&content).is_ok() {
+                return Ok(serde_json::from_slice::<AssetIndex>(&content)?);
+            }
         }
 
-        let content = &*fs::read(&asset_index).await?;
-        Ok(serde_json::from_slice::<AssetIndex>(content)?)
+        let content = HTTP_CLIENT.get(&self.url).send().await?.error_for_status()?.bytes().await?;
+        self.verify(&content)?;
+        Ok(serde_json::from_slice::<AssetIndex>(&content)?)
+    }
+
+    /// Checks the content of the asset index against the size and sha1 of the location
+    fn verify(&self, content: &[u8]) -> Result<()> {
+        if content.len() as i64 != self.size {
+            return Err(LauncherError::ChecksumMismatch {
+                file: format!("asset index {}", self.id),
+                expected: format!("{} bytes", self.size),
+                got: format!("{} bytes", content.len()),
+            }.into());
+        }
+
+        let hash = sha1sum_bytes(content);
+        if !hash.eq_ignore_ascii_case(&self.sha1) {
+            return Err(LauncherError::ChecksumMismatch {
+                file: format!("asset index {}", self.id),
+                expected: self.sha1.clone(),
+                got: hash,
+            }.into());
+        }
+
+        Ok(())
     }
 }
 
-#[derive(Deserialize)]
+/// Folder in the assets root with the asset index each version was last installed with
+const INSTALLED_INDEXES_FOLDER: &str = "installed_indexes";
+
+#[derive(Serialize, Deserialize)]
 pub struct AssetIndex {
     pub objects: HashMap<String, AssetObject>,
+    /// Pre-1.7 versions expect the assets in assets/virtual/{id} by their name
+    #[serde(rename = "virtual", default)]
+    pub is_virtual: bool,
+    /// Pre-1.6 versions expect the assets in the resources folder of the game directory by their name
+    #[serde(default)]
+    pub map_to_resources: bool,
 }
 
-#[derive(Deserialize, Clone)]
+impl AssetIndex {
+    /// Copies the hashed asset objects to their names in the target folder, files which already match are skipped.
+    /// Returns the amount of copied files.
+    pub async fn materialize(&self, objects_folder: &Path, target_folder: &Path) -> Result<u64> {
+        let mut copied = 0;
+
+        for (name, object) in &self.objects {
+            let target_path = target_folder.join(name);
+            if target_path.exists() && sha1sum(&target_path)?.eq_ignore_ascii_case(&object.hash) {
+                continue;
+            }
+
+            let object_path = objects_folder.join(&object.hash[0..2]).join(&object.hash);
+            if !object_path.exists() {
+                warn!("Asset object {} of {} is missing, unable to copy it", object.hash, name);
+                continue;
+            }
+
+            if let Some(parent) = target_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::copy(&object_path, &target_path).await?;
+            copied += 1;
+        }
+
+        Ok(copied)
+    }
+
+    ///
+    /// Objects of this index which are not part of the previous index. Objects are compared by hash,
+    /// as the object store is shared, a renamed object or one added under a known hash is already installed.
+    ///
+    pub fn changed_objects(&self, previous: &AssetIndex) -> Vec<AssetObject> {
+        let previous_hashes = previous.objects.values()
+            .map(|object| object.hash.as_str())
+            .collect::<HashSet<_>>();
+
+        self.objects.values()
+            .filter(|object| !previous_hashes.contains(object.hash.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Where the asset index the version was last installed with is stored
+    pub fn installed_path(assets_root: &Path, version_id: &str) -> PathBuf {
+        assets_root.join(INSTALLED_INDEXES_FOLDER).join(format!("{}.json", version_id))
+    }
+
+    /// Asset index the version was installed with by the last successful launch, none if there is none or it is broken
+    pub async fn load_installed(assets_root: &Path, version_id: &str) -> Option<AssetIndex> {
+        let path = Self::installed_path(assets_root, version_id);
+        let content = fs::read(&path).await.ok()?;
+        match serde_json::from_slice::<AssetIndex>(&content) {
+            Ok(index) => Some(index),
+            Err(e) => {
+                warn!("Installed asset index {:?} is broken, checking every asset: {:?}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Remembers this index as installed for the version, once all of its objects are in the object store
+    pub async fn store_installed(&self, assets_root: &Path, version_id: &str) -> Result<()> {
+        let path = Self::installed_path(assets_root, version_id);
+        fs::create_dir_all(assets_root.join(INSTALLED_INDEXES_FOLDER)).await?;
+        fs::write(path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+}
+
+/// Asset objects freed by [prune_unused_assets]
+#[derive(Serialize, Debug, Default)]
+pub struct PrunedAssets {
+    pub files: u64,
+    pub bytes: u64,
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+}
+
+/// Deletes every object in assets/objects which is not referenced by any of the active asset indexes, see [crate::minecraft::installed::active_asset_indexes].
+/// A dry run only reports what would be deleted.
+pub async fn prune_unused_assets(assets_root: &Path, active_indexes: &[AssetIndex], dry_run: bool, progress: &impl ProgressReceiver) -> Result<PrunedAssets> {
+    let objects_folder = assets_root.join("objects");
+    let mut pruned = PrunedAssets { dry_run, ..PrunedAssets::default() };
+
+    if !objects_folder.exists() {
+        return Ok(pruned);
+    }
+
+    let referenced = active_indexes.iter()
+        .flat_map(|index| index.objects.values())
+        .map(|object| object.hash.as_str())
+        .collect::<HashSet<_>>();
+
+    progress.progress_update(ProgressUpdate::set_label("Checking unused asset objects..."));
+
+    let mut prefixes = fs::read_dir(&objects_folder).await?;
+    while let Some(prefix) = prefixes.next_entry().await? {
+        // objects are stored in folders named after the first two characters of their hash
+        if !prefix.file_type().await?.is_dir() || prefix.file_name().len() != 2 {
+            continue;
+        }
+
+        let mut objects = fs::read_dir(prefix.path()).await?;
+        while let Some(object) = objects.next_entry().await? {
+            let hash = object.file_name().to_string_lossy().to_string();
+            if !object.file_type().await?.is_file() || referenced.contains(hash.as_str()) {
+                continue;
+            }
+
+            let size = object.metadata().await?.len();
+            if dry_run {
+                debug!("Would remove unused asset object {}", hash);
+            } else {
+                fs::remove_file(object.path()).await?;
+                debug!("Removed unused asset object {}", hash);
+            }
+
+            pruned.files += 1;
+            pruned.bytes += size;
+        }
+    }
+
+    // the installed indexes may reference removed objects, so every asset is checked again on the next launch
+    let installed_indexes = assets_root.join(INSTALLED_INDEXES_FOLDER);
+    if !dry_run && pruned.files > 0 && installed_indexes.exists() {
+        fs::remove_dir_all(&installed_indexes).await?;
+    }
+
+    let label = if dry_run {
+        format!("Found {} unused asset objects ({} bytes)", pruned.files, pruned.bytes)
+    } else {
+        format!("Removed {} unused asset objects ({} bytes)", pruned.files, pruned.bytes)
+    };
+    info!("{}", label);
+    progress.progress_update(ProgressUpdate::set_label(label));
+
+    Ok(pruned)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct AssetObject {
     pub hash: String,
     pub size: i64,
 }
 
+/// Default host for Minecraft asset objects
+pub const MOJANG_RESOURCES_HOST: &str = "https://resources.download.minecraft.net";
+
+/// Default host for Minecraft libraries
+pub const MOJANG_LIBRARIES_HOST: &str = "https://libraries.minecraft.net";
+
+/// Base url overrides of a download mirror, e.g. BMCLAPI uses https://bmclapi2.bangbang93.com as manifest host,
+/// https://bmclapi2.bangbang93.com/assets as asset host and https://bmclapi2.bangbang93.com/maven as library host.
+/// Hosts which are not overridden are downloaded from Mojang, failing mirror downloads fall back to Mojang once.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DownloadSource {
+    #[serde(rename = "manifestHost", default)]
+    pub manifest_host: Option<String>,
+    #[serde(rename = "assetHost", default)]
+    pub asset_host: Option<String>,
+    #[serde(rename = "libraryHost", default)]
+    pub library_host: Option<String>,
+}
+
+impl DownloadSource {
+    /// Version manifest urls in the order they should be tried
+    pub fn manifest_urls(&self) -> Vec<String> {
+        let mut urls = Vec::new();
+        if let Some(host) = &self.manifest_host {
+            urls.push(format!("{}{}", host.trim_end_matches('/'), VERSION_MANIFEST_PATH));
+        }
+        urls.push(VERSION_MANIFEST_URL.to_string());
+        urls
+    }
+
+    /// Asset hosts in the order they should be tried
+    pub fn asset_hosts(&self) -> Vec<String> {
+        let mut hosts = Vec::new();
+        if let Some(host) = &self.asset_host {
+            hosts.push(host.trim_end_matches('/').to_string());
+        }
+        hosts.push(MOJANG_RESOURCES_HOST.to_string());
+        hosts
+    }
+
+    /// Rewrites a library url of Mojang's library host to the mirror, if one is configured
+    pub fn library_url(&self, url: &str) -> Option<String> {
+        let host = self.library_host.as_ref()?;
+        url.strip_prefix(MOJANG_LIBRARIES_HOST).map(|path| format!("{}{}", host.trim_end_matches('/'), path))
+    }
+}
+
 impl AssetObject {
-    pub async fn download(&self, assets_objects_folder: impl AsRef<Path>, progress: Arc<impl ProgressReceiver>) -> Result<bool> {
+    /// Downloads the asset object, trying each of the given hosts in order until one delivers a file matching the hash
+    pub async fn download(&self, hosts: &[String], assets_objects_folder: impl AsRef<Path>, progress: Arc<impl ProgressReceiver>, cancellation: &CancellationToken) -> Result<bool> {
         let assets_objects_folder = assets_objects_folder.as_ref().to_owned();
         let asset_folder = assets_objects_folder.join(&self.hash[0..2]);
 
@@ -336,21 +1146,32 @@ impl AssetObject {
 
         let asset_path = asset_folder.join(&self.hash);
 
-        return if !asset_path.exists() {
-            progress.progress_update(ProgressUpdate::set_label(format!("Downloading asset object {}", self.hash)));
+        if asset_path.exists() {
+            return Ok(false);
+        }
 
-            info!("Downloading {}", self.hash);
-            download_file_untracked(&*format!("https://resources.download.minecraft.net/{}/{}", &self.hash[0..2], &self.hash), asset_path).await?;
-            info!("Downloaded {}", self.hash);
+        progress.progress_update(ProgressUpdate::set_label(format!("Downloading asset object {}", self.hash)));
 
-            Ok(true)
-        } else {
-            Ok(false)
-        };
+        for host in hosts {
+            let url = format!("{}/{}/{}", host.trim_end_matches('/'), &self.hash[0..2], &self.hash);
+
+            info!("Downloading {} from {}", self.hash, host);
+            // Verify regardless of the host the asset came from
+            let on_progress = track_transfer(&*progress, |downloaded, total| progress.progress_update(ProgressUpdate::set_bytes(downloaded, total)));
+            match download_file_verified(&url, &asset_path, &self.hash, Some(self.size as u64), cancellation, on_progress).await {
+                Ok(()) => {
+                    info!("Downloaded {}", self.hash);
+                    return Ok(true);
+                }
+                Err(_) if cancellation.is_cancelled() => return Err(LauncherError::Cancelled.into()),
+                Err(e) => warn!("Unable to download asset {} from {}: {:?}", self.hash, host, e)
+            }
+        }
+
+        anyhow::bail!("unable to download asset {} from any host", self.hash)
     }
 
-    pub async fn download_norisk_cosmetic(&self, branch: String, file_path: String, assets_objects_folder: impl AsRef<Path>, progress: Arc<impl ProgressReceiver>) -> Result<bool> {
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
+    pub async fn download_norisk_cosmetic(&self, api: &NoriskApi, branch: String, file_path: String, assets_objects_folder: impl AsRef<Path>, manifest: &CosmeticManifest, progress: Arc<impl ProgressReceiver>, cancellation: &CancellationToken) -> Result<bool> {
         let assets_objects_folder = assets_objects_folder.as_ref().to_owned();
 
         let mut path_parts: Vec<&str> = file_path.split("/").collect();
@@ -373,12 +1194,15 @@ impl AssetObject {
 
         let mut download = false;
 
-        if (asset_file_path.exists()) {
+        if manifest.is_unchanged(&file_path, &self.hash, &asset_file_path) {
+            debug!("Norisk asset {} is unchanged since it was verified.", &self.hash);
+        } else if (asset_file_path.exists()) {
             let sha1 = sha1sum(&asset_file_path)?;
 
             if &self.hash == &sha1 {
                 // If sha1 matches, return
                 info!("Norisk asset {} already exists and matches sha1.", &self.hash);
+                manifest.record(&file_path, &self.hash, &asset_file_path);
             } else {
                 info!("Norisk asset {} already exists but does not match sha1.", &self.hash);
                 download = true;
@@ -391,7 +1215,8 @@ impl AssetObject {
             progress.progress_update(ProgressUpdate::set_label(format!("Downloading asset object {}", self.hash)));
 
             info!("Downloading {}", self.hash);
-            download_file_untracked(&*format!("{}/launcherapi/v1/assets/{}/{}/{}", get_launcher_api_base(options.experimental_mode), branch, &self.hash[0..2], &self.hash), asset_file_path).await?;
+            download_file_verified(&api.url(&format!("{}/assets/{}/{}/{}", NORISK_LAUNCHER_API_VERSION, branch, &self.hash[0..2], &self.hash)), &asset_file_path, &self.hash, Some(self.size as u64), cancellation, |_, _| {}).await?;
+            manifest.record(&file_path, &self.hash, &asset_file_path);
             info!("Downloaded {}", self.hash);
 
             Ok(true)
@@ -400,12 +1225,12 @@ impl AssetObject {
         };
     }
 
-    pub async fn download_destructing(self, assets_objects_folder: impl AsRef<Path>, progress: Arc<impl ProgressReceiver>) -> Result<bool> {
-        return self.download(assets_objects_folder, progress).await;
+    pub async fn download_destructing(self, hosts: Arc<Vec<String>>, assets_objects_folder: impl AsRef<Path>, progress: Arc<impl ProgressReceiver>, cancellation: CancellationToken) -> Result<bool> {
+        return self.download(&hosts, assets_objects_folder, progress, &cancellation).await;
     }
 
-    pub async fn download_norisk_cosmetic_destructing(self, branch: String, file_path: String, assets_objects_folder: impl AsRef<Path>, progress: Arc<impl ProgressReceiver>) -> Result<bool> {
-        return self.download_norisk_cosmetic(branch, file_path, assets_objects_folder, progress).await;
+    pub async fn download_norisk_cosmetic_destructing(self, api: Arc<NoriskApi>, branch: String, file_path: String, assets_objects_folder: impl AsRef<Path>, manifest: Arc<CosmeticManifest>, progress: Arc<impl ProgressReceiver>, cancellation: CancellationToken) -> Result<bool> {
+        return self.download_norisk_cosmetic(&api, branch, file_path, assets_objects_folder, &manifest, progress, &cancellation).await;
     }
 }
 
@@ -427,8 +1252,8 @@ pub struct Download {
 }
 
 impl Download {
-    pub async fn download(&self, path: impl AsRef<Path>) -> Result<()> {
-        download_file_untracked(&self.url, path).await?;
+    pub async fn download(&self, path: impl AsRef<Path>, cancellation: &CancellationToken) -> Result<()> {
+        download_file_verified(&self.url, path, &self.sha1, Some(self.size as u64), cancellation, |_, _| {}).await?;
         info!("Downloaded {}", self.url);
         Ok(())
     }
@@ -442,26 +1267,139 @@ pub struct Library {
     #[serde(default)]
     pub rules: Vec<Rule>,
     pub url: Option<String>,
+    pub extract: Option<Extract>,
+    /// Checksums of legacy Forge libraries, which might be hosted as .pack.xz
+    pub checksums: Option<Vec<String>>,
+    /// Set on legacy Forge libraries, which might be hosted as .pack.xz
+    pub clientreq: Option<bool>,
+}
+
+/// Extraction rules of a natives library
+#[derive(Deserialize, Clone)]
+pub struct Extract {
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
 impl Library {
-    pub fn get_library_download(&self) -> Result<LibraryDownloadInfo> {
+    /// Parses the name of the library as group:artifact:version[:classifier][@extension]
+    pub fn maven_coordinate(&self) -> Option<MavenCoordinate> {
+        parse_maven_coordinate(&self.name).ok()
+    }
+
+    /// Libraries of old Forge versions, whose repository might only host them as .pack.xz
+    pub fn is_legacy_forge(&self) -> bool {
+        self.checksums.is_some() || self.clientreq.is_some()
+    }
+
+    pub fn get_library_download(&self, source: &DownloadSource) -> Result<LibraryDownloadInfo> {
         if let Some(artifact) = self.downloads.as_ref().and_then(|x| x.artifact.as_ref()) {
-            return Ok(artifact.into());
+            return Ok(LibraryDownloadInfo::from(artifact).with_source(source));
         }
 
         let path = get_maven_artifact_path(&self.name)?;
-        let url = self.url.as_deref().unwrap_or("https://libraries.minecraft.net/");
+        let url = self.url.clone().unwrap_or_else(|| format!("{}/", MOJANG_LIBRARIES_HOST));
 
         return Ok(
             LibraryDownloadInfo {
                 url: format!("{}{}", url, path),
+                fallback_url: None,
                 sha1: None,
+                sha256: None,
                 size: None,
+                packed: self.is_legacy_forge(),
                 path,
-            }
+            }.with_source(source)
         );
     }
+
+    /// Natives classifier of the current OS, ${arch} is replaced by the bitness of the current architecture
+    pub fn native_classifier(&self) -> Result<Option<String>> {
+        let classifier = match self.natives.as_ref().and_then(|x| x.get(OS.get_simple_name().ok()?)) {
+            Some(classifier) => classifier,
+            None => return Ok(None)
+        };
+
+        let bitness = match *ARCHITECTURE {
+            Architecture::X86 | Architecture::ARM => "32",
+            _ => "64"
+        };
+
+        Ok(Some(classifier.replace("${arch}", bitness)))
+    }
+
+    /// Checks if the zip entry has to be extracted from the natives artifact, META-INF/ is always excluded like Mojang does
+    pub fn should_extract(&self, entry_name: &str) -> bool {
+        if entry_name.starts_with("META-INF/") {
+            return false;
+        }
+
+        !self.extract.as_ref().map_or(false, |x| x.exclude.iter().any(|prefix| entry_name.starts_with(prefix.as_str())))
+    }
+
+    /// Download of the natives artifact of the current OS, arm64 natives are preferred on arm64 if available
+    pub fn get_natives_download(&self, source: &DownloadSource) -> Result<Option<LibraryDownloadInfo>> {
+        let classifier = match self.native_classifier()? {
+            Some(classifier) => classifier,
+            None => return Ok(None)
+        };
+
+        let classifiers = self.downloads.as_ref()
+            .and_then(|x| x.classifiers.as_ref())
+            .ok_or_else(|| LauncherError::InvalidVersionProfile("missing classifiers, but natives required.".to_string()))?;
+
+        if *ARCHITECTURE == Architecture::AARCH64 {
+            if let Some(artifact) = classifiers.get(&format!("{}-arm64", classifier)) {
+                return Ok(Some(LibraryDownloadInfo::from(artifact).with_source(source)));
+            }
+            warn!("Library {} has no arm64 natives, falling back to {} (requires Rosetta on macOS)", self.name, classifier);
+        }
+
+        Ok(classifiers.get(&classifier).map(|x| LibraryDownloadInfo::from(x).with_source(source)))
+    }
+
+    /// Classifier of libraries which are split into one entry per natives artifact, e.g. natives-macos-arm64
+    fn split_natives_classifier(&self) -> Option<&str> {
+        self.name.split(':').nth(3).filter(|x| x.starts_with("natives-"))
+    }
+
+    /// Whether the library is the arm64 natives entry of a split natives library
+    fn is_arm64_natives(&self) -> bool {
+        self.split_natives_classifier().map_or(false, |x| x.ends_with("-arm64") || x.ends_with("-aarch64"))
+    }
+
+    /// Selects the natives entries matching the current architecture out of libraries which are split into one entry per natives artifact.
+    /// On arm64 the x86_64 entries are replaced by their arm64 counterpart, if a library doesn't provide one the x86_64 entry is kept.
+    pub fn select_for_architecture(libraries: Vec<Library>) -> Vec<Library> {
+        if *IS_ROSETTA_TRANSLATED {
+            info!("Running under Rosetta, using x86_64 natives");
+        }
+
+        let names = libraries.iter().map(|x| x.name.clone()).collect::<HashSet<_>>();
+        let arm64 = *ARCHITECTURE == Architecture::AARCH64;
+
+        libraries.into_iter()
+            .filter(|library| {
+                if library.split_natives_classifier().is_none() {
+                    return true;
+                }
+
+                if library.is_arm64_natives() {
+                    return arm64;
+                }
+
+                if arm64 {
+                    let has_arm64 = names.contains(&format!("{}-arm64", library.name)) || names.contains(&format!("{}-aarch64", library.name));
+                    if !has_arm64 {
+                        warn!("Library {} has no arm64 natives, falling back to x86_64 (requires Rosetta on macOS)", library.name);
+                    }
+                    return !has_arm64;
+                }
+
+                true
+            })
+            .collect()
+    }
 }
 
 #[derive(Deserialize, Clone)]
@@ -492,10 +1430,14 @@ pub struct LibraryDownloads {
     pub classifiers: Option<HashMap<String, LibraryArtifact>>,
 }
 
+/// Extension of the legacy pack200 libraries compressed with xz, used by old Forge versions
+const PACK_XZ_EXTENSION: &str = ".pack.xz";
+
 #[derive(Deserialize, Clone)]
 pub struct LibraryArtifact {
     pub path: String,
-    pub sha1: String,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
     pub size: i64,
     pub url: String,
 }
@@ -504,33 +1446,90 @@ pub struct LibraryArtifact {
 pub struct LibraryDownloadInfo {
     pub path: String,
     pub sha1: Option<String>,
+    pub sha256: Option<String>,
     pub size: Option<i64>,
     pub url: String,
+    /// Official url if the library is downloaded from a mirror
+    #[serde(skip)]
+    pub fallback_url: Option<String>,
+    /// Tries the .pack.xz next to the jar first, set for legacy Forge libraries
+    #[serde(skip)]
+    pub packed: bool,
 }
 
 impl From<&LibraryArtifact> for LibraryDownloadInfo {
     fn from(artifact: &LibraryArtifact) -> Self {
         LibraryDownloadInfo {
             path: artifact.path.to_owned(),
-            sha1: Some(artifact.sha1.to_owned()),
+            sha1: artifact.sha1.to_owned(),
+            sha256: artifact.sha256.to_owned(),
             size: Some(artifact.size),
             url: artifact.url.to_owned(),
+            fallback_url: None,
+            packed: false,
         }
     }
 }
 
 impl LibraryDownloadInfo {
+    /// Downloads the library from the mirror of the download source, keeping the official url as fallback
+    pub fn with_source(mut self, source: &DownloadSource) -> Self {
+        if let Some(url) = source.library_url(&self.url) {
+            self.fallback_url = Some(std::mem::replace(&mut self.url, url));
+        }
+        self
+    }
+
     async fn fetch_sha1(&self) -> Result<String> {
-        HTTP_CLIENT.get(&format!("{}{}", &self.url, ".sha1"))
+        self.fetch_checksum_file("sha1").await
+    }
+
+    async fn fetch_sha256(&self) -> Result<String> {
+        self.fetch_checksum_file("sha256").await
+    }
+
+    async fn fetch_checksum_file(&self, extension: &str) -> Result<String> {
+        let url = format!("{}.{}", &self.url, extension);
+        let content = authorize_request(HTTP_CLIENT.get(&url), &url)
             .send().await?
             .error_for_status()?
             .text()
-            .await
-            .map_err(|e| anyhow::anyhow!(e))
+            .await?;
+
+        // checksum files might contain the file name after the hash
+        content.split_whitespace()
+            .next()
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| anyhow::anyhow!("empty {} file", extension))
     }
 
-    pub async fn download(&self, name: String, libraries_folder: &Path, progress: Arc<impl ProgressReceiver>) -> Result<PathBuf> {
-        info!("Downloading library {}, sha1: {:?}, size: {:?}", name, &self.sha1, &self.size);
+    /// Reads the checksum next to the library or fetches it from the repository, preferring sha256 over sha1
+    async fn load_remote_checksum(&self, libraries_folder: &Path) -> Result<Option<Checksum>> {
+        let sha256_path = libraries_folder.join(&self.path).with_extension("sha256");
+        let sha1_path = libraries_folder.join(&self.path).with_extension("sha1");
+
+        if sha256_path.exists() {
+            return Ok(Some(Checksum::Sha256(fs::read_to_string(&sha256_path).await?)));
+        }
+        if sha1_path.exists() {
+            return Ok(Some(Checksum::Sha1(fs::read_to_string(&sha1_path).await?)));
+        }
+
+        // If no checksum file exists, fetch it
+        if let Ok(sha256) = self.fetch_sha256().await {
+            fs::write(&sha256_path, &sha256).await?;
+            return Ok(Some(Checksum::Sha256(sha256)));
+        }
+        if let Ok(sha1) = self.fetch_sha1().await {
+            fs::write(&sha1_path, &sha1).await?;
+            return Ok(Some(Checksum::Sha1(sha1)));
+        }
+
+        Ok(None)
+    }
+
+    pub async fn download(&self, name: String, libraries_folder: &Path, progress: Arc<impl ProgressReceiver>, cancellation: &CancellationToken) -> Result<PathBuf> {
+        info!("Downloading library {}, sha1: {:?}, sha256: {:?}, size: {:?}", name, &self.sha1, &self.sha256, &self.size);
         debug!("Library download url: {}", &self.url);
 
         let path = libraries_folder.to_path_buf();
@@ -539,73 +1538,510 @@ impl LibraryDownloadInfo {
         // Create parent directories
         fs::create_dir_all(&library_path.parent().unwrap()).await?;
 
-        // SHA1
-        let sha1 = if let Some(sha1) = &self.sha1 {
-            Some(sha1.clone())
+        // Checksum, SHA256 is preferred over SHA1
+        let checksum = if let Some(sha256) = &self.sha256 {
+            Some(Checksum::Sha256(sha256.clone()))
+        } else if let Some(sha1) = &self.sha1 {
+            Some(Checksum::Sha1(sha1.clone()))
         } else {
-            // Check if sha1 file exists
-            let sha1_path = path.join(&self.path).with_extension("sha1");
-
-            if sha1_path.exists() {
-                // If sha1 file exists, read it
-                let sha1 = fs::read_to_string(&sha1_path).await?;
-                Some(sha1)
-            } else {
-                // If sha1 file doesn't exist, fetch it
-                let sha1 = self.fetch_sha1().await
-                    .map(Some)
-                    .unwrap_or(None);
-
-                // Write sha1 file
-                if let Some(sha1) = &sha1 {
-                    fs::write(&sha1_path, &sha1).await?;
-                }
-
-                sha1
-            }
+            self.load_remote_checksum(&path).await?
         };
 
         // Check if library already exists
         if library_path.exists() {
-            // Check if sha1 matches
-            let hash = sha1sum(&library_path)?;
+            if let Some(checksum) = &checksum {
+                if checksum.matches(&library_path)? {
+                    // If checksum matches, return
+                    info!("Library {} already exists and matches {}.", name, checksum);
+                    return Ok(library_path);
+                }
 
-            if let Some(sha1) = &sha1 {
-                if hash == *sha1 {
-                    // If sha1 matches, return
-                    info!("Library {} already exists and matches sha1.", name);
+                // If checksum doesn't match, remove the file
+                info!("Library {} already exists but checksum doesn't match, redownloading", name);
+            } else if let Some(size) = self.size {
+                // If checksum is not available, at least catch truncated files
+                let actual_size = fs::metadata(&library_path).await?.len();
+                if actual_size == size as u64 {
+                    info!("Library {} already exists and matches size {}.", name, size);
                     return Ok(library_path);
                 }
+
+                info!("Library {} already exists but its size {} doesn't match {}, redownloading", name, actual_size, size);
             } else {
-                // If sha1 is not available, assume it matches
+                // If neither checksum nor size is available, assume it matches
                 info!("Library {} already exists.", name);
                 return Ok(library_path);
             }
 
-            // If sha1 doesn't match, remove the file
-            info!("Library {} already exists but sha1 doesn't match, redownloading", name);
             fs::remove_file(&library_path).await?;
         }
 
         // Download library
         progress.progress_update(ProgressUpdate::set_label(format!("Downloading library {}", name)));
 
-        download_file_untracked(&self.url, &library_path).await?;
-        info!("Downloaded {}", self.url);
+        // After downloading, check checksum
+        let size = self.size.map(|x| x as u64);
+        let on_progress = track_transfer(&*progress, |downloaded, total| progress.progress_update(ProgressUpdate::set_bytes(downloaded, total)));
+        let url = match self.download_from(&self.url, &library_path, checksum.as_ref(), size, cancellation, &on_progress).await {
+            Ok(()) => &self.url,
+            Err(e) => match &self.fallback_url {
+                Some(fallback_url) if !cancellation.is_cancelled() => {
+                    warn!("Unable to download library {} from mirror, falling back to {}: {:?}", name, fallback_url, e);
+                    self.download_from(fallback_url, &library_path, checksum.as_ref(), size, cancellation, &on_progress).await
+                        .with_context(|| format!("unable to download library {}", name))?;
+                    fallback_url
+                }
+                _ => return Err(e.context(format!("unable to download library {}", name)))
+            }
+        };
+        info!("Downloaded {}", url);
+
+        Ok(library_path)
+    }
 
-        // After downloading, check sha1
-        if let Some(sha1) = &sha1 {
-            let hash = sha1sum(&library_path)?;
-            if hash != *sha1 {
-                anyhow::bail!("sha1 of downloaded library {} doesn't match", name);
+    async fn download_from<F>(&self, url: &str, library_path: &Path, checksum: Option<&Checksum>, size: Option<u64>, cancellation: &CancellationToken, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
+        if self.packed {
+            let packed_url = format!("{}{}", url, PACK_XZ_EXTENSION);
+            match Self::download_packed(&packed_url, library_path, checksum, cancellation, &on_progress).await {
+                Ok(()) => return Ok(()),
+                Err(e) if !cancellation.is_cancelled() => debug!("Unable to use packed library {}, downloading the jar: {:?}", packed_url, e),
+                Err(e) => return Err(e)
             }
         }
 
-        Ok(library_path)
+        match checksum {
+            Some(checksum) => download_file_checksummed(url, library_path, checksum, size, cancellation, on_progress).await,
+            None => download_file_tracked(url, library_path, size, cancellation, on_progress).await
+        }
+    }
+
+    ///
+    /// Downloads a legacy .pack.xz library and unpacks it to the jar. The checksum and size of the library are the ones of the jar,
+    /// so the packed file is downloaded unverified and the checksum is checked against the unpacked jar.
+    ///
+    async fn download_packed<F>(url: &str, library_path: &Path, checksum: Option<&Checksum>, cancellation: &CancellationToken, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
+        let mut packed_name = library_path.file_name().unwrap_or_default().to_os_string();
+        packed_name.push(PACK_XZ_EXTENSION);
+        let packed_path = library_path.with_file_name(packed_name);
+
+        // libraries are stored in data/libraries, next to the runtimes
+        let runtimes_folder = library_path.ancestors()
+            .find(|folder| folder.ends_with("libraries"))
+            .and_then(Path::parent)
+            .map(|data| data.join("runtimes"))
+            .unwrap_or_default();
+
+        download_file_tracked(url, &packed_path, None, cancellation, on_progress).await?;
+        let unpacked = unpack_pack_xz(&packed_path, library_path, &runtimes_folder).await;
+        fs::remove_file(&packed_path).await?;
+        unpacked?;
+
+        if let Some(checksum) = checksum {
+            let got = checksum.compute(&library_path.to_path_buf())?;
+            if !checksum.matches_hash(&got) {
+                fs::remove_file(library_path).await?;
+                return Err(LauncherError::ChecksumMismatch {
+                    file: url.to_owned(),
+                    expected: checksum.expected().to_owned(),
+                    got,
+                }.into());
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[derive(Deserialize)]
 pub struct Logging {
-    // TODO: Add logging configuration
+    pub client: Option<LoggingClient>,
+}
+
+#[derive(Deserialize)]
+pub struct LoggingClient {
+    pub argument: String,
+    pub file: LoggingFile,
+    #[serde(rename = "type")]
+    pub logging_type: String,
+}
+
+impl LoggingClient {
+    ///
+    /// JVM argument pointing to the downloaded configuration file, e.g. -Dlog4j.configurationFile=${path}.
+    /// ${path} only exists in the logging argument, so it is resolved here instead of by the placeholders of the launch context.
+    ///
+    pub fn jvm_argument(&self, config_path: &Path) -> Result<String> {
+        let config_path = config_path.to_string_lossy();
+        process_templates(&self.argument, |output, param| {
+            match param {
+                "path" => output.push_str(&config_path),
+                _ => {
+                    warn!("Unknown placeholder ${{{}}} in logging argument {}", param, self.argument);
+                    output.push_str(&format!("${{{}}}", param));
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoggingFile {
+    pub id: String,
+    pub sha1: String,
+    pub size: i64,
+    pub url: String,
+}
+
+impl LoggingFile {
+    /// Downloads the logging configuration unless it already exists and matches sha1
+    pub async fn download(&self, log_configs_folder: &Path, cancellation: &CancellationToken) -> Result<PathBuf> {
+        let config_path = log_configs_folder.join(&self.id);
+
+        if config_path.exists() {
+            if sha1sum(&config_path)? == self.sha1 {
+                info!("Logging config {} already exists and matches sha1.", self.id);
+                return Ok(config_path);
+            }
+
+            info!("Logging config {} already exists but sha1 doesn't match, redownloading", self.id);
+            fs::remove_file(&config_path).await?;
+        }
+
+        download_file_verified(&self.url, &config_path, &self.sha1, Some(self.size as u64), cancellation, |_, _| {}).await?;
+        info!("Downloaded {}", self.url);
+
+        Ok(config_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_zip::{Compression, ZipEntryBuilder};
+    use async_zip::write::ZipFileWriter;
+
+    use crate::app::api::LoginData;
+    use crate::app::app_data::{JvmArgsPolicy, LauncherOptions};
+    use crate::minecraft::launcher::LaunchingParameterBuilder;
+    use crate::utils::test_util::test_folder;
+
+    use super::*;
+
+    fn launching_parameter(options: LauncherOptions) -> LaunchingParameter {
+        let account = LoginData {
+            mc_token: "mc-token".to_string(),
+            access_token: "access-token".to_string(),
+            refresh_token: "refresh-token".to_string(),
+            uuid: "uuid".to_string(),
+            username: "Player".to_string(),
+            norisk_token: "norisk-token".to_string(),
+            experimental_token: None,
+            mc_token_expires_at: None,
+            needs_relogin: false,
+        };
+        LaunchingParameterBuilder::new(options).account(account).build().unwrap()
+    }
+
+    fn legacy_arguments() -> ArgumentDeclaration {
+        ArgumentDeclaration::V14(V14ArgumentDeclaration { minecraft_arguments: Some("--username ${auth_player_name}".to_string()) })
+    }
+
+    fn game_args(arguments: &ArgumentDeclaration) -> Vec<String> {
+        let mut command_arguments = Vec::new();
+        arguments.add_game_args_to_vec(&mut command_arguments, &HashSet::new()).unwrap();
+        command_arguments
+    }
+
+    fn jvm_args(arguments: &ArgumentDeclaration, parameter: &LaunchingParameter) -> Vec<String> {
+        let mut command_arguments = Vec::new();
+        arguments.add_jvm_args_to_vec("norisk-token", &mut command_arguments, parameter, &HashSet::new(), None).unwrap();
+        command_arguments
+    }
+
+    #[test]
+    fn java_args_denied_by_the_policy_are_stripped() {
+        let mut parameter = launching_parameter(LauncherOptions { custom_java_args: "-javaagent:agent.jar -Dfoo=bar".to_string(), ..LauncherOptions::default() });
+        parameter.jvm_args_policy = Some(JvmArgsPolicy { allow: None, deny: vec!["-javaagent".to_string()] });
+
+        let args = jvm_args(&legacy_arguments(), &parameter);
+        assert!(args.contains(&"-Dfoo=bar".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("-javaagent")));
+    }
+
+    #[test]
+    fn java_args_pass_without_a_policy() {
+        let parameter = launching_parameter(LauncherOptions { custom_java_args: "-javaagent:agent.jar".to_string(), ..LauncherOptions::default() });
+
+        assert!(jvm_args(&legacy_arguments(), &parameter).contains(&"-javaagent:agent.jar".to_string()));
+    }
+
+    #[test]
+    fn allowlist_only_permits_listed_prefixes() {
+        let policy = JvmArgsPolicy { allow: Some(vec!["-XX:".to_string(), "-Dfml.".to_string()]), deny: Vec::new() };
+
+        assert!(policy.permits("-XX:+UseZGC"));
+        assert!(policy.permits("-Dfml.ignorePatchDiscrepancies=true"));
+        assert!(!policy.permits("-javaagent:agent.jar"));
+    }
+
+    #[test]
+    fn custom_heap_flags_are_skipped() {
+        let parameter = launching_parameter(LauncherOptions { custom_java_args: "-Xmx8G -Xms1G -Xss4M".to_string(), ..LauncherOptions::default() });
+
+        let args = jvm_args(&legacy_arguments(), &parameter);
+        assert_eq!(args.iter().filter(|arg| arg.starts_with("-Xmx")).collect::<Vec<_>>(), vec![&format!("-Xmx{}M", parameter.memory)]);
+        assert_eq!(args.iter().filter(|arg| arg.starts_with("-Xms")).count(), 1);
+        assert!(args.contains(&"-Xss4M".to_string()));
+    }
+
+    fn profile(json: serde_json::Value) -> VersionProfile {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn legacy_child_merges_into_structured_parent() {
+        let mut child = profile(serde_json::json!({
+            "id": "1.12.2-forge", "inheritsFrom": "1.12.2", "type": "release", "libraries": [],
+            "minecraftArguments": "--username ${auth_player_name} --tweakClass net.minecraftforge.fml.common.launcher.FMLTweaker"
+        }));
+        let parent = profile(serde_json::json!({
+            "id": "1.12.2", "type": "release", "libraries": [],
+            "arguments": { "game": ["--username", "${auth_player_name}", "--version", "${version_name}"], "jvm": ["-cp", "${classpath}"] }
+        }));
+
+        child.merge(parent).unwrap();
+
+        let args = game_args(&child.arguments);
+        assert!(args.contains(&"--tweakClass".to_string()));
+        assert!(args.contains(&"--version".to_string()));
+        assert_eq!(args.iter().filter(|arg| *arg == "--username").count(), 1);
+    }
+
+    #[test]
+    fn structured_child_merges_legacy_parent() {
+        let mut child = profile(serde_json::json!({
+            "id": "fabric-loader", "inheritsFrom": "1.8.9", "type": "release", "libraries": [],
+            "arguments": { "game": ["--width", "854"] }
+        }));
+        let parent = profile(serde_json::json!({
+            "id": "1.8.9", "type": "release", "libraries": [],
+            "minecraftArguments": "--username ${auth_player_name} --width 1280"
+        }));
+
+        child.merge(parent).unwrap();
+
+        let args = game_args(&child.arguments);
+        assert_eq!(args, vec!["--width", "854", "--username", "${auth_player_name}"]);
+        let ArgumentDeclaration::V21(declaration) = &child.arguments else { panic!("merged arguments are not structured") };
+        assert!(declaration.arguments.jvm.iter().any(|arg| arg.contains("${classpath}")));
+    }
+
+    #[test]
+    fn broken_arguments_are_a_deserialization_error() {
+        for broken in [r#"{ "game": [42] }"#, r#"{ "game": "--username" }"#, r#"{ "game": [{ "rules": [], "value": 5 }] }"#] {
+            assert!(serde_json::from_str::<Arguments>(broken).is_err(), "{} was accepted", broken);
+        }
+    }
+
+    async fn natives_jar(entries: &[&str]) -> std::io::Cursor<Vec<u8>> {
+        let mut writer = ZipFileWriter::new(Vec::new());
+        for entry in entries {
+            writer.write_entry_whole(ZipEntryBuilder::new(entry.to_string(), Compression::Stored), b"native").await.unwrap();
+        }
+        std::io::Cursor::new(writer.close().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn excluded_natives_entries_are_not_extracted() {
+        let library: Library = serde_json::from_value(serde_json::json!({
+            "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4", "extract": { "exclude": ["docs/"] }
+        })).unwrap();
+        let natives_folder = test_folder("natives");
+
+        let jar = natives_jar(&["META-INF/MANIFEST.MF", "docs/README.txt", "foo.dll"]).await;
+        let extracted = crate::utils::zip_extract_filtered(jar, &natives_folder, |name| library.should_extract(name)).await.unwrap();
+
+        assert_eq!(extracted, vec![natives_folder.join("foo.dll")]);
+        assert!(!natives_folder.join("META-INF").exists());
+        assert!(!natives_folder.join("docs").exists());
+    }
+
+    #[test]
+    fn meta_inf_is_excluded_without_extract_rules() {
+        let library: Library = serde_json::from_value(serde_json::json!({ "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4" })).unwrap();
+
+        assert!(!library.should_extract("META-INF/MANIFEST.MF"));
+        assert!(library.should_extract("foo.dll"));
+    }
+
+    fn library_names(profile: &VersionProfile) -> Vec<&str> {
+        profile.libraries.iter().map(|library| library.name.as_str()).collect()
+    }
+
+    #[test]
+    fn only_the_newest_library_version_survives() {
+        let mut profile = profile(serde_json::json!({
+            "id": "fabric-loader", "type": "release",
+            "libraries": [
+                { "name": "org.ow2.asm:asm:9.2" },
+                { "name": "net.fabricmc:fabric-loader:0.14.21" },
+                { "name": "org.ow2.asm:asm:9.10" },
+                { "name": "org.ow2.asm:asm:9.6" }
+            ]
+        }));
+
+        profile.dedupe_libraries();
+
+        assert_eq!(library_names(&profile), vec!["org.ow2.asm:asm:9.10", "net.fabricmc:fabric-loader:0.14.21"]);
+    }
+
+    #[test]
+    fn child_library_wins_on_equal_versions() {
+        let mut profile = profile(serde_json::json!({
+            "id": "forge", "type": "release",
+            "libraries": [
+                { "name": "com.google.guava:guava:31.1-jre", "url": "https://maven.minecraftforge.net/" },
+                { "name": "com.google.guava:guava:31.1-jre" }
+            ]
+        }));
+
+        profile.dedupe_libraries();
+
+        assert_eq!(profile.libraries.len(), 1);
+        assert_eq!(profile.libraries[0].url.as_deref(), Some("https://maven.minecraftforge.net/"));
+    }
+
+    fn manifest() -> VersionManifest {
+        let version = |id: &str, version_type: &str, release_time: &str| serde_json::json!({
+            "id": id, "type": version_type, "url": format!("https://piston-meta.mojang.com/{}.json", id),
+            "time": release_time, "releaseTime": release_time
+        });
+        serde_json::from_value(serde_json::json!({ "versions": [
+            version("1.19.4", "release", "2023-03-14T12:56:18+00:00"),
+            version("23w31a", "snapshot", "2023-08-01T11:03:03+00:00"),
+            version("1.20.1", "release", "2023-06-12T13:25:51+00:00"),
+            version("b1.7.3", "old_beta", "2011-07-07T22:00:00+00:00"),
+            version("23w13a", "snapshot", "2023-03-29T12:23:33+00:00")
+        ] })).unwrap()
+    }
+
+    fn ids(versions: Vec<&ManifestVersion>) -> Vec<&str> {
+        versions.into_iter().map(|version| version.id.as_str()).collect()
+    }
+
+    #[test]
+    fn versions_are_filtered_by_type_newest_first() {
+        let manifest = manifest();
+
+        assert_eq!(ids(manifest.releases()), vec!["1.20.1", "1.19.4"]);
+        assert_eq!(ids(manifest.snapshots()), vec!["23w31a", "23w13a"]);
+        assert_eq!(ids(manifest.by_type("old_beta")), vec!["b1.7.3"]);
+        assert!(manifest.by_type("old_alpha").is_empty());
+    }
+
+    #[test]
+    fn latest_versions_use_the_release_time() {
+        let manifest = manifest();
+
+        assert_eq!(manifest.latest_release().map(|version| version.id.as_str()), Some("1.20.1"));
+        assert_eq!(manifest.latest_snapshot().map(|version| version.id.as_str()), Some("23w31a"));
+    }
+
+    const ASSET_INDEX: &[u8] = br#"{"objects":{"icons/icon_16x16.png":{"hash":"bdf48ef6b5d0d23bbb02e17d04865216179f510a","size":3665}}}"#;
+
+    fn asset_index_location(content: &[u8]) -> AssetIndexLocation {
+        AssetIndexLocation {
+            id: "test".to_string(),
+            sha1: sha1sum_bytes(content),
+            size: content.len() as i64,
+            total_size: 3665,
+            url: String::new(),
+        }
+    }
+
+    fn is_checksum_mismatch(err: &anyhow::Error) -> bool {
+        matches!(err.downcast_ref::<LauncherError>(), Some(LauncherError::ChecksumMismatch { .. }))
+    }
+
+    #[test]
+    fn asset_index_matching_the_location_is_accepted() {
+        assert!(asset_index_location(ASSET_INDEX).verify(ASSET_INDEX).is_ok());
+    }
+
+    #[test]
+    fn tampered_asset_index_fails_the_checksum() {
+        let location = asset_index_location(ASSET_INDEX);
+
+        // same size, so only the sha1 can tell
+        let tampered = String::from_utf8(ASSET_INDEX.to_vec()).unwrap().replace("3665", "3666");
+        let err = location.verify(tampered.as_bytes()).unwrap_err();
+        assert!(is_checksum_mismatch(&err));
+
+        let truncated = &ASSET_INDEX[..ASSET_INDEX.len() - 1];
+        let err = location.verify(truncated).unwrap_err();
+        assert!(is_checksum_mismatch(&err));
+    }
+
+    #[test]
+    fn logging_argument_points_to_the_downloaded_config() {
+        let logging: Logging = serde_json::from_value(serde_json::json!({
+            "client": {
+                "argument": "-Dlog4j.configurationFile=${path}",
+                "file": { "id": "client-1.12.xml", "sha1": "bd65e7d2e3c237be76cfbef4c2405033d7f91521", "size": 888, "url": "https://piston-data.mojang.com/v1/objects/bd65e7d2e3c237be76cfbef4c2405033d7f91521/client-1.12.xml" },
+                "type": "log4j2-xml"
+            }
+        })).unwrap();
+        let logging_client = logging.client.unwrap();
+        let config_path = Path::new("/data/assets/log_configs/client-1.12.xml");
+        let parameter = launching_parameter(LauncherOptions::default());
+
+        let mut command_arguments = Vec::new();
+        legacy_arguments().add_jvm_args_to_vec("norisk-token", &mut command_arguments, &parameter, &HashSet::new(), Some((&logging_client, config_path))).unwrap();
+
+        assert!(command_arguments.contains(&format!("-Dlog4j.configurationFile={}", config_path.display())));
+        assert!(!command_arguments.iter().any(|arg| arg.contains("${path}")));
+    }
+
+    fn experimental_flag(dev_mode: bool, disable_experimental_flag: bool) -> Option<String> {
+        let parameter = launching_parameter(LauncherOptions { dev_mode, disable_experimental_flag, ..LauncherOptions::default() });
+        jvm_args(&legacy_arguments(), &parameter).into_iter().find(|arg| arg.starts_with("-Dnorisk.experimental="))
+    }
+
+    #[test]
+    fn experimental_flag_follows_the_dev_mode_unless_disabled() {
+        assert_eq!(experimental_flag(false, false).as_deref(), Some("-Dnorisk.experimental=false"));
+        assert_eq!(experimental_flag(true, false).as_deref(), Some("-Dnorisk.experimental=true"));
+        assert_eq!(experimental_flag(true, true).as_deref(), Some("-Dnorisk.experimental=false"));
+        assert_eq!(experimental_flag(false, true).as_deref(), Some("-Dnorisk.experimental=false"));
+    }
+
+    #[test]
+    fn legacy_forge_libraries_try_the_packed_jar() {
+        let library: Library = serde_json::from_str(r#"{"name":"org.scala-lang:scala-library:2.11.1","url":"https://maven.minecraftforge.net/","checksums":["0e11da23da3eabab9f4777b9220e60d44c1aab6a"],"serverreq":true,"clientreq":true}"#).unwrap();
+        let download = library.get_library_download(&DownloadSource::default()).unwrap();
+        assert!(download.packed);
+        assert_eq!(download.url, "https://maven.minecraftforge.net/org/scala-lang/scala-library/2.11.1/scala-library-2.11.1.jar");
+
+        let library: Library = serde_json::from_str(r#"{"name":"com.google.guava:guava:17.0"}"#).unwrap();
+        assert!(!library.get_library_download(&DownloadSource::default()).unwrap().packed);
+    }
+
+    #[test]
+    fn timestamps_with_offsets_without_colon_are_parsed() {
+        let timestamp = "2010-03-30T00:00:00-0700".parse::<Timestamp>().unwrap();
+        assert_eq!(timestamp.format_date_time(), "2010-03-30 07:00 UTC");
+        assert_eq!(timestamp.raw(), "2010-03-30T00:00:00-0700");
+
+        assert!("30.03.2010".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn timestamps_of_the_same_instant_are_equal() {
+        let utc = "2023-06-12T09:25:00+00:00".parse::<Timestamp>().unwrap();
+        let offset = "2023-06-12T11:25:00+02:00".parse::<Timestamp>().unwrap();
+
+        assert_eq!(utc.cmp(&offset), std::cmp::Ordering::Equal);
+        assert_eq!(utc, offset);
+    }
 }
@@ -0,0 +1,65 @@
+/// Splits a command line like string into arguments.
+/// Arguments are separated by whitespace, double quotes group whitespace into a single argument
+/// and `\"` is a literal quote. Any other backslash is kept as is, so windows paths keep working.
+pub fn split_arguments(input: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut in_quotes = false;
+
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+                in_argument = true;
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                in_argument = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_argument = true;
+            }
+        }
+    }
+
+    if in_argument {
+        arguments.push(current);
+    }
+
+    arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_arguments_keep_their_whitespace() {
+        assert_eq!(split_arguments(r#"-Dfoo="C:\Program Files\x" -Xss4M"#), vec![r"-Dfoo=C:\Program Files\x", "-Xss4M"]);
+        assert_eq!(split_arguments(r#"-Dname=\"quoted\""#), vec![r#"-Dname="quoted""#]);
+    }
+
+    #[test]
+    fn tabs_and_repeated_whitespace_separate_arguments() {
+        assert_eq!(split_arguments("-Xss4M \t -XX:+UseZGC\t\t-Dfoo=bar  "), vec!["-Xss4M", "-XX:+UseZGC", "-Dfoo=bar"]);
+    }
+
+    #[test]
+    fn empty_input_has_no_arguments() {
+        assert!(split_arguments("").is_empty());
+        assert!(split_arguments(" \t ").is_empty());
+        // an empty pair of quotes is an empty argument on purpose
+        assert_eq!(split_arguments(r#"-Dempty= """#), vec!["-Dempty=", ""]);
+    }
+}
@@ -1,7 +1,84 @@
 use anyhow::Result;
 use sha1::{Sha1, Digest};
+use sha2::Sha256;
+use std::fmt::Display;
 use std::path::PathBuf;
 
+/// Expected hash of a file
+#[derive(Clone, Debug)]
+pub enum Checksum {
+    Sha1(String),
+    Sha256(String),
+}
+
+impl Checksum {
+    /// Checks if the file at path matches the checksum
+    pub fn matches(&self, path: &PathBuf) -> Result<bool> {
+        Ok(self.matches_hash(&self.compute(path)?))
+    }
+
+    /// Checks if an already computed hash matches the checksum
+    pub fn matches_hash(&self, hash: &str) -> bool {
+        hash.eq_ignore_ascii_case(self.expected().trim())
+    }
+
+    /// The expected hash
+    pub fn expected(&self) -> &str {
+        match self {
+            Checksum::Sha1(sha1) => sha1,
+            Checksum::Sha256(sha256) => sha256,
+        }
+    }
+
+    /// Hashes the file at path with the algorithm of the checksum
+    pub fn compute(&self, path: &PathBuf) -> Result<String> {
+        match self {
+            Checksum::Sha1(_) => sha1sum(path),
+            Checksum::Sha256(_) => sha256sum(path),
+        }
+    }
+
+    /// Hasher with the algorithm of the checksum, used to hash a file while it is being written
+    pub fn hasher(&self) -> ChecksumHasher {
+        match self {
+            Checksum::Sha1(_) => ChecksumHasher::Sha1(Sha1::new()),
+            Checksum::Sha256(_) => ChecksumHasher::Sha256(Sha256::new()),
+        }
+    }
+}
+
+/// Incremental hasher of a [Checksum] algorithm
+pub enum ChecksumHasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl ChecksumHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            ChecksumHasher::Sha1(hasher) => hasher.update(data),
+            ChecksumHasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Lowercase hex of the hash
+    pub fn finalize(self) -> String {
+        match self {
+            ChecksumHasher::Sha1(hasher) => base16ct::lower::encode_string(&hasher.finalize()),
+            ChecksumHasher::Sha256(hasher) => base16ct::lower::encode_string(&hasher.finalize()),
+        }
+    }
+}
+
+impl Display for Checksum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Checksum::Sha1(sha1) => write!(f, "sha1 {}", sha1),
+            Checksum::Sha256(sha256) => write!(f, "sha256 {}", sha256),
+        }
+    }
+}
+
 pub fn sha1sum(path: &PathBuf) -> Result<String> {
     // get sha1 of library file and check if it matches
     let mut file = std::fs::File::open(path)?;
@@ -11,4 +88,19 @@ pub fn sha1sum(path: &PathBuf) -> Result<String> {
     let hex_hash = base16ct::lower::encode_string(&hash);
 
     Ok(hex_hash)
-}
\ No newline at end of file
+}
+
+pub fn sha1sum_bytes(bytes: &[u8]) -> String {
+    let hash = Sha1::digest(bytes);
+    base16ct::lower::encode_string(&hash)
+}
+
+pub fn sha256sum(path: &PathBuf) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    let hash = hasher.finalize();
+    let hex_hash = base16ct::lower::encode_string(&hash);
+
+    Ok(hex_hash)
+}
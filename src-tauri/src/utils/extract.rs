@@ -1,21 +1,38 @@
-use anyhow::{Result, Context};
+use anyhow::{anyhow, bail, Result, Context};
+use std::env;
 use std::{path::{Path, PathBuf}};
-use async_compression::tokio::bufread::GzipDecoder;
+use async_compression::tokio::bufread::{GzipDecoder, XzDecoder};
 use async_zip::read::seek::ZipFileReader;
 use tokio::fs::{create_dir_all, OpenOptions};
 use tokio::io;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, BufReader};
+use tokio::process::Command;
+
+use super::{OperatingSystem, OS};
 
 /// Extracts everything from the ZIP archive to the output directory
-///
-/// Taken from https://github.com/Majored/rs-async-zip/blob/main/examples/file_extraction.rs
 pub async fn zip_extract<R>(archive: R, out_dir: &Path) -> Result<()>
     where R: AsyncRead + AsyncSeek + Unpin {
+    zip_extract_filtered(archive, out_dir, |_| true).await?;
+    Ok(())
+}
+
+/// Extracts the entries of the ZIP archive accepted by the filter to the output directory.
+/// Returns the paths of the extracted files.
+///
+/// Taken from https://github.com/Majored/rs-async-zip/blob/main/examples/file_extraction.rs
+pub async fn zip_extract_filtered<R, F>(archive: R, out_dir: &Path, filter: F) -> Result<Vec<PathBuf>>
+    where R: AsyncRead + AsyncSeek + Unpin, F: Fn(&str) -> bool {
+    let mut extracted = Vec::new();
     let mut reader = ZipFileReader::new(archive).await?;
     for index in 0..reader.file().entries().len() {
         let entry = &reader.file().entries().get(index).unwrap().entry();
         let file_name = entry.filename();
 
+        if !filter(file_name) {
+            continue;
+        }
+
         let path = out_dir.join(sanitize_file_path(file_name));
         // If the filename of the entry ends with '/', it is treated as a directory.
         // This is implemented by previous versions of this crate and the Python Standard Library.
@@ -44,9 +61,10 @@ pub async fn zip_extract<R>(archive: R, out_dir: &Path) -> Result<()>
                 .open(&path).await
                 .context("Failed to create extracted file")?;
             io::copy(&mut entry_reader, &mut writer).await?;
+            extracted.push(path);
         }
     }
-    Ok(())
+    Ok(extracted)
 }
 
 pub async fn tar_gz_extract<R>(archive: R, out_dir: &Path) -> Result<()>
@@ -60,6 +78,70 @@ pub async fn tar_gz_extract<R>(archive: R, out_dir: &Path) -> Result<()>
     Ok(())
 }
 
+/// Trailer of signed pack files: the signature block, its length as little endian u32 and this magic
+const PACK_SIGNATURE_MAGIC: &[u8] = b"SIGN";
+
+///
+/// Unpacks a legacy .pack.xz library, used by old Forge versions, to the jar. The xz stream is decompressed,
+/// the signature trailer is stripped and the pack200 archive is turned into the jar by unpack200,
+/// which ships with Java 13 and older.
+///
+pub async fn unpack_pack_xz(packed: &Path, jar: &Path, runtimes_folder: &Path) -> Result<()> {
+    let mut decoder = XzDecoder::new(BufReader::new(tokio::fs::File::open(packed).await?));
+    let mut pack = Vec::new();
+    decoder.read_to_end(&mut pack).await.with_context(|| format!("unable to decompress {:?}", packed))?;
+
+    if pack.ends_with(PACK_SIGNATURE_MAGIC) && pack.len() >= 8 {
+        let length_start = pack.len() - 8;
+        let signature_length = u32::from_le_bytes(pack[length_start..length_start + 4].try_into()?) as usize;
+        if signature_length > length_start {
+            bail!("signature of {:?} is longer than the pack", packed);
+        }
+        pack.truncate(length_start - signature_length);
+    }
+
+    let unpack200 = find_unpack200(runtimes_folder).ok_or_else(|| anyhow!("unpack200 is required to unpack {:?}, install Java 8", packed))?;
+    let pack_path = jar.with_extension("pack");
+    tokio::fs::write(&pack_path, &pack).await?;
+
+    let output = Command::new(&unpack200).arg(&pack_path).arg(jar).output().await;
+    let _ = tokio::fs::remove_file(&pack_path).await;
+    let output = output.with_context(|| format!("unable to run {:?}", unpack200))?;
+    if !output.status.success() {
+        bail!("unpack200 failed on {:?}: {}", packed, String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(())
+}
+
+/// Searches the Java 8 runtime of the launcher, JAVA_HOME and the PATH for unpack200
+fn find_unpack200(runtimes_folder: &Path) -> Option<PathBuf> {
+    let binary_name = match OS {
+        OperatingSystem::WINDOWS => "unpack200.exe",
+        _ => "unpack200"
+    };
+
+    let mut dirs = Vec::new();
+    // versions old enough for packed libraries run on the Java 8 runtime, which still ships unpack200
+    if let Ok(entries) = std::fs::read_dir(runtimes_folder.join("8")) {
+        for entry in entries.flatten() {
+            let java_home = match OS {
+                OperatingSystem::OSX => entry.path().join("Contents").join("Home"),
+                _ => entry.path()
+            };
+            dirs.push(java_home.join("bin"));
+        }
+    }
+    if let Some(java_home) = env::var_os("JAVA_HOME") {
+        dirs.push(PathBuf::from(java_home).join("bin"));
+    }
+    if let Some(path) = env::var_os("PATH") {
+        dirs.extend(env::split_paths(&path));
+    }
+
+    dirs.into_iter().map(|dir| dir.join(binary_name)).find(|candidate| candidate.is_file())
+}
+
 /// Returns a relative path without reserved names, redundant separators, ".", or "..".
 fn sanitize_file_path(path: &str) -> PathBuf {
     // Replaces backwards slashes
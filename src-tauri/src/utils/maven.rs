@@ -1,12 +1,141 @@
+use std::cmp::Ordering;
+
 use crate::error::LauncherError;
 use anyhow::Result;
 
+/// Extension of artifacts whose coordinate doesn't specify one
+const DEFAULT_EXTENSION: &str = "jar";
+
+/// Maven coordinate in the form group:artifact:version[:classifier][@extension]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MavenCoordinate {
+    pub group: String,
+    pub artifact: String,
+    pub version: String,
+    pub classifier: Option<String>,
+    /// jar unless the coordinate specifies another one, e.g. zip for natives-linux@zip
+    pub extension: String,
+}
+
+impl MavenCoordinate {
+    /// group:artifact[:classifier], identifies the artifact regardless of its version
+    pub fn key(&self) -> String {
+        match &self.classifier {
+            Some(classifier) => format!("{}:{}:{}", self.group, self.artifact, classifier),
+            None => format!("{}:{}", self.group, self.artifact)
+        }
+    }
+
+    /// File name of the artifact, e.g. lwjgl-3.3.1-natives-linux.jar
+    pub fn file_name(&self) -> String {
+        match &self.classifier {
+            Some(classifier) => format!("{}-{}-{}.{}", self.artifact, self.version, classifier, self.extension),
+            None => format!("{}-{}.{}", self.artifact, self.version, self.extension)
+        }
+    }
+
+    /// Path of the artifact relative to the root of the repository
+    pub fn path(&self) -> String {
+        format!("{}/{}/{}/{}", self.group.replace('.', "/"), self.artifact, self.version, self.file_name())
+    }
+}
+
+/// Parses a maven coordinate in the form group:artifact:version[:classifier][@extension]
+pub fn parse_maven_coordinate(coordinate: &str) -> Result<MavenCoordinate> {
+    let invalid = || LauncherError::InvalidVersionProfile(format!("Invalid artifact name: {}", coordinate));
+
+    let (name, extension) = match coordinate.split_once('@') {
+        Some((name, extension)) if !extension.is_empty() && !extension.contains(':') => (name, extension),
+        Some(_) => return Err(invalid().into()),
+        None => (coordinate, DEFAULT_EXTENSION)
+    };
+
+    let parts = name.split(':').collect::<Vec<_>>();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(invalid().into());
+    }
+
+    let (group, artifact, version, classifier) = match parts.as_slice() {
+        [group, artifact, version] => (group, artifact, version, None),
+        [group, artifact, version, classifier] => (group, artifact, version, Some(classifier.to_string())),
+        _ => return Err(invalid().into())
+    };
+
+    Ok(MavenCoordinate {
+        group: group.to_string(),
+        artifact: artifact.to_string(),
+        version: version.to_string(),
+        classifier,
+        extension: extension.to_string(),
+    })
+}
+
+/// Path of a maven artifact in the form group:name:version[:classifier][@extension]
 pub fn get_maven_artifact_path(artifact_id: &String) -> Result<String> {
-    let split = artifact_id.split(':').collect::<Vec<_>>();
+    Ok(parse_maven_coordinate(artifact_id)?.path())
+}
+
+/// Compares maven versions part by part, numeric parts are compared by their value (e.g. 9.5 < 9.10)
+pub fn compare_maven_versions(a: &str, b: &str) -> Ordering {
+    let split = |version: &str| version.split(|c: char| c == '.' || c == '-' || c == '_' || c == '+').map(str::to_owned).collect::<Vec<_>>();
+    let (parts_a, parts_b) = (split(a), split(b));
 
-    if split.len() != 3 {
-        return Err(LauncherError::InvalidVersionProfile(format!("Invalid artifact name: {}", artifact_id)).into());
+    for (part_a, part_b) in parts_a.iter().zip(parts_b.iter()) {
+        let ordering = match (part_a.parse::<u64>(), part_b.parse::<u64>()) {
+            (Ok(num_a), Ok(num_b)) => num_a.cmp(&num_b),
+            _ => part_a.cmp(part_b)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    parts_a.len().cmp(&parts_b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_coordinate_is_a_jar() {
+        let coordinate = parse_maven_coordinate("net.fabricmc:fabric-loader:0.14.21").unwrap();
+
+        assert_eq!(coordinate.classifier, None);
+        assert_eq!(coordinate.extension, "jar");
+        assert_eq!(coordinate.path(), "net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar");
+    }
+
+    #[test]
+    fn classifier_only_coordinate() {
+        let coordinate = parse_maven_coordinate("org.lwjgl:lwjgl:3.3.1:natives-linux").unwrap();
+
+        assert_eq!(coordinate.classifier.as_deref(), Some("natives-linux"));
+        assert_eq!(coordinate.key(), "org.lwjgl:lwjgl:natives-linux");
+        assert_eq!(coordinate.path(), "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar");
+    }
+
+    #[test]
+    fn extension_only_coordinate() {
+        let coordinate = parse_maven_coordinate("de.oceanlabs.mcp:mcp_config:1.20.1-20230612.114412@zip").unwrap();
+
+        assert_eq!(coordinate.classifier, None);
+        assert_eq!(coordinate.extension, "zip");
+        assert_eq!(coordinate.path(), "de/oceanlabs/mcp/mcp_config/1.20.1-20230612.114412/mcp_config-1.20.1-20230612.114412.zip");
     }
 
-    Ok(format!("{}/{name}/{ver}/{name}-{ver}.jar", split[0].replace('.', "/"), name = split[1], ver = split[2]))
-}
\ No newline at end of file
+    #[test]
+    fn classifier_and_extension_coordinate() {
+        let coordinate = parse_maven_coordinate("net.minecraft:client:1.20.1-20230612.114412:mappings@txt").unwrap();
+
+        assert_eq!(coordinate.file_name(), "client-1.20.1-20230612.114412-mappings.txt");
+    }
+
+    #[test]
+    fn malformed_coordinates_are_rejected() {
+        for coordinate in ["net.fabricmc:fabric-loader", "net.fabricmc::0.14.21", "a:b:c:d:e", "a:b:c@", "a:b:c@zip:x"] {
+            assert!(parse_maven_coordinate(coordinate).is_err(), "{} was accepted", coordinate);
+        }
+    }
+}
@@ -3,6 +3,10 @@ mod extract;
 mod download;
 mod maven;
 mod checksum;
+mod args;
+mod nbt;
+#[cfg(test)]
+pub(crate) mod test_util;
 
 pub use {
     sys::*,
@@ -10,5 +14,7 @@ pub use {
     download::*,
     maven::*,
     checksum::*,
+    args::*,
+    nbt::*,
 };
 
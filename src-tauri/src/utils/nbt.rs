@@ -0,0 +1,312 @@
+use std::io::{Cursor, Read, Write};
+
+use anyhow::{anyhow, bail, Result};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+pub const TAG_END: u8 = 0;
+pub const TAG_BYTE: u8 = 1;
+pub const TAG_SHORT: u8 = 2;
+pub const TAG_INT: u8 = 3;
+pub const TAG_LONG: u8 = 4;
+pub const TAG_FLOAT: u8 = 5;
+pub const TAG_DOUBLE: u8 = 6;
+pub const TAG_BYTE_ARRAY: u8 = 7;
+pub const TAG_STRING: u8 = 8;
+pub const TAG_LIST: u8 = 9;
+pub const TAG_COMPOUND: u8 = 10;
+pub const TAG_INT_ARRAY: u8 = 11;
+pub const TAG_LONG_ARRAY: u8 = 12;
+
+/// Nesting deeper than this is rejected, so a broken file can't overflow the stack
+const MAX_DEPTH: usize = 512;
+
+///
+/// A tag of Minecraft's uncompressed NBT format, as used by servers.dat.
+/// Compounds keep the order of their entries, so unknown data is written back as it was read.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum NbtTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// Type of the elements and the elements, which all have that type
+    List(u8, Vec<NbtTag>),
+    Compound(Vec<(String, NbtTag)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl NbtTag {
+    fn id(&self) -> u8 {
+        match self {
+            NbtTag::Byte(_) => TAG_BYTE,
+            NbtTag::Short(_) => TAG_SHORT,
+            NbtTag::Int(_) => TAG_INT,
+            NbtTag::Long(_) => TAG_LONG,
+            NbtTag::Float(_) => TAG_FLOAT,
+            NbtTag::Double(_) => TAG_DOUBLE,
+            NbtTag::ByteArray(_) => TAG_BYTE_ARRAY,
+            NbtTag::String(_) => TAG_STRING,
+            NbtTag::List(_, _) => TAG_LIST,
+            NbtTag::Compound(_) => TAG_COMPOUND,
+            NbtTag::IntArray(_) => TAG_INT_ARRAY,
+            NbtTag::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    /// Entry of a compound by name
+    pub fn get(&self, name: &str) -> Option<&NbtTag> {
+        match self {
+            NbtTag::Compound(entries) => entries.iter().find(|(key, _)| key == name).map(|(_, tag)| tag),
+            _ => None
+        }
+    }
+
+    /// Mutable entry of a compound by name
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut NbtTag> {
+        match self {
+            NbtTag::Compound(entries) => entries.iter_mut().find(|(key, _)| key == name).map(|(_, tag)| tag),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtTag::String(value) => Some(value),
+            _ => None
+        }
+    }
+}
+
+///
+/// Reads an uncompressed NBT file, returning the name and the tag of its root compound.
+///
+pub fn read_nbt(bytes: &[u8]) -> Result<(String, NbtTag)> {
+    let mut reader = Cursor::new(bytes);
+
+    let id = reader.read_u8()?;
+    if id != TAG_COMPOUND {
+        bail!("root tag has to be a compound, got type {}", id);
+    }
+    let name = read_string(&mut reader)?;
+    let root = read_payload(&mut reader, id, 0)?;
+
+    Ok((name, root))
+}
+
+///
+/// Writes the root compound as uncompressed NBT.
+///
+pub fn write_nbt(name: &str, root: &NbtTag) -> Result<Vec<u8>> {
+    if root.id() != TAG_COMPOUND {
+        bail!("root tag has to be a compound");
+    }
+
+    let mut bytes = Vec::new();
+    bytes.write_u8(TAG_COMPOUND)?;
+    write_string(&mut bytes, name)?;
+    write_payload(&mut bytes, root)?;
+
+    Ok(bytes)
+}
+
+fn read_payload(reader: &mut impl Read, id: u8, depth: usize) -> Result<NbtTag> {
+    if depth > MAX_DEPTH {
+        bail!("nbt is nested too deeply");
+    }
+
+    Ok(match id {
+        TAG_BYTE => NbtTag::Byte(reader.read_i8()?),
+        TAG_SHORT => NbtTag::Short(reader.read_i16::<BigEndian>()?),
+        TAG_INT => NbtTag::Int(reader.read_i32::<BigEndian>()?),
+        TAG_LONG => NbtTag::Long(reader.read_i64::<BigEndian>()?),
+        TAG_FLOAT => NbtTag::Float(reader.read_f32::<BigEndian>()?),
+        TAG_DOUBLE => NbtTag::Double(reader.read_f64::<BigEndian>()?),
+        TAG_BYTE_ARRAY => {
+            let length = read_length(reader)?;
+            NbtTag::ByteArray((0..length).map(|_| reader.read_i8()).collect::<Result<_, _>>()?)
+        }
+        TAG_STRING => NbtTag::String(read_string(reader)?),
+        TAG_LIST => {
+            let element_id = reader.read_u8()?;
+            let length = read_length(reader)?;
+            let elements = (0..length).map(|_| read_payload(reader, element_id, depth + 1)).collect::<Result<_>>()?;
+            NbtTag::List(element_id, elements)
+        }
+        TAG_COMPOUND => {
+            let mut entries = Vec::new();
+            loop {
+                let entry_id = reader.read_u8()?;
+                if entry_id == TAG_END {
+                    break;
+                }
+                let name = read_string(reader)?;
+                entries.push((name, read_payload(reader, entry_id, depth + 1)?));
+            }
+            NbtTag::Compound(entries)
+        }
+        TAG_INT_ARRAY => {
+            let length = read_length(reader)?;
+            NbtTag::IntArray((0..length).map(|_| reader.read_i32::<BigEndian>()).collect::<Result<_, _>>()?)
+        }
+        TAG_LONG_ARRAY => {
+            let length = read_length(reader)?;
+            NbtTag::LongArray((0..length).map(|_| reader.read_i64::<BigEndian>()).collect::<Result<_, _>>()?)
+        }
+        _ => bail!("unknown nbt tag type {}", id)
+    })
+}
+
+fn write_payload(writer: &mut impl Write, tag: &NbtTag) -> Result<()> {
+    match tag {
+        NbtTag::Byte(value) => writer.write_i8(*value)?,
+        NbtTag::Short(value) => writer.write_i16::<BigEndian>(*value)?,
+        NbtTag::Int(value) => writer.write_i32::<BigEndian>(*value)?,
+        NbtTag::Long(value) => writer.write_i64::<BigEndian>(*value)?,
+        NbtTag::Float(value) => writer.write_f32::<BigEndian>(*value)?,
+        NbtTag::Double(value) => writer.write_f64::<BigEndian>(*value)?,
+        NbtTag::ByteArray(values) => {
+            writer.write_i32::<BigEndian>(values.len() as i32)?;
+            for value in values {
+                writer.write_i8(*value)?;
+            }
+        }
+        NbtTag::String(value) => write_string(writer, value)?,
+        NbtTag::List(element_id, elements) => {
+            // an empty list is written with the end type like Minecraft does
+            writer.write_u8(if elements.is_empty() { TAG_END } else { *element_id })?;
+            writer.write_i32::<BigEndian>(elements.len() as i32)?;
+            for element in elements {
+                if element.id() != *element_id {
+                    bail!("list of type {} contains a tag of type {}", element_id, element.id());
+                }
+                write_payload(writer, element)?;
+            }
+        }
+        NbtTag::Compound(entries) => {
+            for (name, entry) in entries {
+                writer.write_u8(entry.id())?;
+                write_string(writer, name)?;
+                write_payload(writer, entry)?;
+            }
+            writer.write_u8(TAG_END)?;
+        }
+        NbtTag::IntArray(values) => {
+            writer.write_i32::<BigEndian>(values.len() as i32)?;
+            for value in values {
+                writer.write_i32::<BigEndian>(*value)?;
+            }
+        }
+        NbtTag::LongArray(values) => {
+            writer.write_i32::<BigEndian>(values.len() as i32)?;
+            for value in values {
+                writer.write_i64::<BigEndian>(*value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_length(reader: &mut impl Read) -> Result<i32> {
+    let length = reader.read_i32::<BigEndian>()?;
+    if length < 0 {
+        bail!("negative nbt length {}", length);
+    }
+    Ok(length)
+}
+
+/// Java writes modified UTF-8, which encodes null characters as two bytes and characters outside the BMP as surrogate pairs
+fn read_string(reader: &mut impl Read) -> Result<String> {
+    let length = reader.read_u16::<BigEndian>()?;
+    let mut bytes = vec![0; length as usize];
+    reader.read_exact(&mut bytes)?;
+    decode_modified_utf8(&bytes)
+}
+
+fn write_string(writer: &mut impl Write, value: &str) -> Result<()> {
+    let bytes = encode_modified_utf8(value);
+    if bytes.len() > u16::MAX as usize {
+        bail!("nbt string is too long");
+    }
+    writer.write_u16::<BigEndian>(bytes.len() as u16)?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+fn decode_modified_utf8(bytes: &[u8]) -> Result<String> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&first) = iter.next() {
+        let mut continuation = || match iter.next() {
+            Some(&byte) if byte & 0xC0 == 0x80 => Ok((byte & 0x3F) as u16),
+            _ => Err(anyhow!("malformed modified utf-8 in nbt string"))
+        };
+
+        let unit = match first {
+            0x01..=0x7F => first as u16,
+            0xC0..=0xDF => ((first & 0x1F) as u16) << 6 | continuation()?,
+            0xE0..=0xEF => ((first & 0x0F) as u16) << 12 | continuation()? << 6 | continuation()?,
+            _ => bail!("malformed modified utf-8 in nbt string")
+        };
+        units.push(unit);
+    }
+
+    // surrogates without their pair can't be represented, java writes them anyway
+    Ok(String::from_utf16_lossy(&units))
+}
+
+fn encode_modified_utf8(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+    for unit in value.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | (unit >> 6 & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_characters_are_written_as_two_bytes() {
+        assert_eq!(encode_modified_utf8("a\0b"), vec![b'a', 0xC0, 0x80, b'b']);
+        assert_eq!(decode_modified_utf8(&[b'a', 0xC0, 0x80, b'b']).unwrap(), "a\0b");
+    }
+
+    #[test]
+    fn characters_outside_the_bmp_are_written_as_surrogate_pairs() {
+        let encoded = encode_modified_utf8("\u{1F600}");
+        assert_eq!(encoded, vec![0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+        assert_eq!(decode_modified_utf8(&encoded).unwrap(), "\u{1F600}");
+    }
+
+    #[test]
+    fn strings_survive_a_round_trip() {
+        let root = NbtTag::Compound(vec![("name".to_string(), NbtTag::String("Grüße \u{1F600}\0".to_string()))]);
+        let bytes = write_nbt("", &root).unwrap();
+        assert_eq!(read_nbt(&bytes).unwrap(), (String::new(), root));
+    }
+
+    #[test]
+    fn malformed_strings_are_rejected() {
+        assert!(decode_modified_utf8(&[0xC3]).is_err());
+        assert!(decode_modified_utf8(&[0x00]).is_err());
+    }
+}
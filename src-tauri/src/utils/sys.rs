@@ -1,14 +1,58 @@
 use std::fmt::Display;
+use std::path::Path;
 use anyhow::{bail, Result};
 use once_cell::sync::Lazy;
+use path_absolutize::*;
 use serde::Deserialize;
-use sysinfo::{RefreshKind, System, SystemExt};
+use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
+use tracing::{debug, warn};
 
-/// Get the total memory of the system in bytes
-pub fn percentage_of_total_memory(memory_percentage: i32) -> i64 {
+use crate::error::LauncherError;
+
+/// Space kept free on top of the required space, extracting natives and unknown file sizes need some room
+const DISK_SPACE_MARGIN: u64 = 512 * 1024 * 1024;
+
+/// Get the total memory of the system in megabytes
+pub fn total_memory() -> i64 {
     let sys = System::new_with_specifics(RefreshKind::new().with_memory());
 
-    ((sys.total_memory() / 1000000) as f64 * (memory_percentage as f64 / 100.0)) as i64
+    (sys.total_memory() / 1000000) as i64
+}
+
+/// Available space in bytes of the disk the path is located on, None if the disk is unknown
+pub fn available_disk_space(path: &Path) -> Option<u64> {
+    let path = path.absolutize().ok()?;
+    let sys = System::new_with_specifics(RefreshKind::new().with_disks_list());
+
+    // the disk with the longest mount point containing the path is the one it is located on
+    sys.disks().iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+/// Fails with [LauncherError::InsufficientDiskSpace] if the disk of the target doesn't fit the required bytes and a safety margin
+pub fn check_disk_space(target: &Path, required_bytes: u64) -> Result<()> {
+    let available = match available_disk_space(target) {
+        Some(available) => available,
+        None => {
+            warn!("Unable to determine the available disk space of {:?}, skipping the check", target);
+            return Ok(());
+        }
+    };
+    debug!("{} bytes required, {} bytes available on the disk of {:?}", required_bytes, available, target);
+
+    let required = required_bytes + DISK_SPACE_MARGIN;
+    if available < required {
+        return Err(LauncherError::InsufficientDiskSpace { required, available }.into());
+    }
+
+    Ok(())
+}
+
+/// Get the percentage of the total memory of the system in megabytes
+pub fn percentage_of_total_memory(memory_percentage: i32) -> i64 {
+    (total_memory() as f64 * (memory_percentage as f64 / 100.0)) as i64
 }
 
 pub const OS: OperatingSystem = if cfg!(target_os = "windows") {
@@ -21,17 +65,20 @@ pub const OS: OperatingSystem = if cfg!(target_os = "windows") {
     OperatingSystem::UNKNOWN
 };
 
-pub const ARCHITECTURE: Architecture = if cfg!(target_arch = "x86") {
-    Architecture::X86 // 32-bit
-} else if cfg!(target_arch = "x86_64") {
-    Architecture::X64 // 64-bit
-} else if cfg!(target_arch = "arm") {
-    Architecture::ARM // ARM
-} else if cfg!(target_arch = "aarch64") {
-    Architecture::AARCH64 // AARCH64
-} else {
-    Architecture::UNKNOWN // Unsupported architecture
-};
+/// Architecture the launcher runs on, see [Architecture::host]
+pub static ARCHITECTURE: Lazy<Architecture> = Lazy::new(Architecture::host);
+
+/// Whether the launcher is an x86_64 build translated by Rosetta 2 on Apple Silicon
+pub static IS_ROSETTA_TRANSLATED: Lazy<bool> = Lazy::new(|| {
+    if OS != OperatingSystem::OSX || *ARCHITECTURE != Architecture::X64 {
+        return false;
+    }
+
+    std::process::Command::new("sysctl")
+        .args(["-in", "sysctl.proc_translated"])
+        .output()
+        .map_or(false, |output| String::from_utf8_lossy(&output.stdout).trim() == "1")
+});
 
 pub const OS_VERSION: Lazy<String> = Lazy::new(|| {
     os_info::get().version().to_string()
@@ -49,7 +96,10 @@ pub enum OperatingSystem {
     UNKNOWN
 }
 
+/// Profiles spell architectures differently, all common aliases are deserialized to the same variant.
+/// Architectures unknown to the launcher are deserialized as UNKNOWN instead of failing the whole profile
 #[derive(Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+#[serde(from = "String")]
 pub enum Architecture {
     #[serde(rename = "x86")]
     X86,
@@ -98,7 +148,32 @@ impl Display for OperatingSystem {
     }
 }
 
+impl From<String> for Architecture {
+    fn from(value: String) -> Self {
+        Architecture::from_alias(&value).unwrap_or_else(|| {
+            warn!("Unknown architecture {}", value);
+            Architecture::UNKNOWN
+        })
+    }
+}
+
 impl Architecture {
+    /// Architecture of one of the names used by profiles, Rust and Java
+    pub fn from_alias(name: &str) -> Option<Architecture> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "x86" | "i386" | "i686" => Architecture::X86,
+            "x64" | "x86_64" | "amd64" => Architecture::X64,
+            "arm" | "arm32" | "armv7" => Architecture::ARM,
+            "aarch64" | "arm64" => Architecture::AARCH64,
+            _ => return None
+        })
+    }
+
+    /// Architecture the launcher runs on, as reported by the standard library
+    pub fn host() -> Architecture {
+        Architecture::from_alias(std::env::consts::ARCH).unwrap_or(Architecture::UNKNOWN)
+    }
+
     pub fn get_simple_name(&self) -> Result<&'static str> {
         Ok(match self {
             Architecture::X86 => "x86",
@@ -114,4 +189,42 @@ impl Display for Architecture {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.get_simple_name().unwrap())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn architecture_aliases_are_normalized() {
+        for (alias, architecture) in [
+            ("x86", Architecture::X86), ("i386", Architecture::X86), ("i686", Architecture::X86),
+            ("x64", Architecture::X64), ("x86_64", Architecture::X64), ("AMD64", Architecture::X64),
+            ("arm", Architecture::ARM), ("arm32", Architecture::ARM), ("armv7", Architecture::ARM),
+            ("aarch64", Architecture::AARCH64), ("arm64", Architecture::AARCH64),
+        ] {
+            assert_eq!(serde_json::from_str::<Architecture>(&format!("\"{}\"", alias)).unwrap(), architecture, "{}", alias);
+        }
+    }
+
+    #[test]
+    fn unknown_architecture_is_deserialized_as_unknown() {
+        assert_eq!(serde_json::from_str::<Architecture>("\"riscv64\"").unwrap(), Architecture::UNKNOWN);
+    }
+
+    #[test]
+    fn host_architecture_matches_the_build_target() {
+        let expected = if cfg!(target_arch = "x86") {
+            Architecture::X86
+        } else if cfg!(target_arch = "x86_64") {
+            Architecture::X64
+        } else if cfg!(target_arch = "arm") {
+            Architecture::ARM
+        } else if cfg!(target_arch = "aarch64") {
+            Architecture::AARCH64
+        } else {
+            Architecture::UNKNOWN
+        };
+        assert_eq!(Architecture::host(), expected);
+        assert_eq!(*ARCHITECTURE, expected);
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Empty folder in the temp directory, unique to the test run and the name
+pub fn test_folder(name: &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("noriskclient-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+/// Local HTTP server answering every request with the status and body returned for its path
+pub struct MockServer {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Starts the server, every response is sent after the delay so concurrent requests overlap
+    pub async fn start(delay: Duration, respond: fn(&str) -> (u16, &'static [u8])) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let mut buffer = vec![0; 4096];
+                    let read = stream.read(&mut buffer).await.unwrap_or_default();
+                    let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                    let (status, body) = respond(request.split_whitespace().nth(1).unwrap_or_default());
+                    received.lock().unwrap().push(request);
+
+                    tokio::time::sleep(delay).await;
+                    let header = format!("HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len());
+                    let _ = stream.write_all(header.as_bytes()).await;
+                    let _ = stream.write_all(body).await;
+                });
+            }
+        });
+
+        MockServer { base_url, requests }
+    }
+
+    /// Heads of the requests received so far
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}